@@ -0,0 +1,136 @@
+use crate::vojo::cli::Coding;
+use flate2::read::DeflateDecoder;
+use flate2::read::GzDecoder;
+use flate2::read::ZlibDecoder;
+use flate2::write::GzEncoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use hyper::header::CONTENT_ENCODING;
+use hyper::HeaderMap;
+use std::io::Read;
+use std::io::Write;
+
+impl Coding {
+    /// The name used in Content-Encoding and Accept-Encoding.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Coding::Gzip => "gzip",
+            Coding::Deflate => "deflate",
+            Coding::Br => "br",
+            Coding::Zstd => "zstd",
+        }
+    }
+}
+
+/// `body` encoded with `coding` at the default level of each format.
+pub fn compress(body: &[u8], coding: Coding) -> Result<Vec<u8>, anyhow::Error> {
+    let compressed = match coding {
+        Coding::Gzip => {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+        Coding::Deflate => {
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+        Coding::Br => {
+            let mut compressed = vec![];
+            let params = brotli::enc::BrotliEncoderParams::default();
+            brotli::BrotliCompress(&mut &body[..], &mut compressed, &params)?;
+            compressed
+        }
+        Coding::Zstd => zstd::encode_all(body, 0)?,
+    };
+    Ok(compressed)
+}
+
+/// Undoes the codings a response lists in its Content-Encoding, last applied first.
+pub fn decompress(headers: &HeaderMap, body: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    let codings: Vec<String> = headers
+        .get_all(CONTENT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|coding| coding.trim().to_lowercase())
+        .filter(|coding| !coding.is_empty() && coding != "identity")
+        .collect();
+    let mut body = body;
+    for coding in codings.iter().rev() {
+        let mut decoded = vec![];
+        match coding.as_str() {
+            "gzip" | "x-gzip" => GzDecoder::new(&body[..]).read_to_end(&mut decoded),
+            // Servers disagree on whether deflate is zlib wrapped, so both are accepted.
+            "deflate" => ZlibDecoder::new(&body[..])
+                .read_to_end(&mut decoded)
+                .or_else(|_| {
+                    decoded.clear();
+                    DeflateDecoder::new(&body[..]).read_to_end(&mut decoded)
+                }),
+            "br" => brotli::Decompressor::new(&body[..], 4096).read_to_end(&mut decoded),
+            "zstd" => zstd::stream::copy_decode(&body[..], &mut decoded).map(|_| 0),
+            _ => return Err(anyhow!("Unsupported Content-Encoding {coding}")),
+        }
+        .map_err(|e| anyhow!("Failed to decompress the {coding} response: {e}"))?;
+        body = decoded;
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use hyper::header::HeaderValue;
+
+    const BODY: &[u8] = b"{\"items\":[1,2,3],\"items\":[1,2,3],\"items\":[1,2,3]}";
+
+    fn encoded_as(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn round_trips_every_coding() {
+        for coding in [Coding::Gzip, Coding::Deflate, Coding::Br, Coding::Zstd] {
+            let compressed = compress(BODY, coding).unwrap();
+            assert_ne!(compressed, BODY, "{}", coding.as_str());
+            let headers = encoded_as(coding.as_str());
+            assert_eq!(decompress(&headers, compressed).unwrap(), BODY);
+        }
+    }
+
+    #[test]
+    fn accepts_raw_deflate() {
+        let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+        encoder.write_all(BODY).unwrap();
+        let raw = encoder.finish().unwrap();
+        assert_eq!(decompress(&encoded_as("deflate"), raw).unwrap(), BODY);
+    }
+
+    #[test]
+    fn undoes_stacked_codings_last_first() {
+        let gzipped = compress(BODY, Coding::Gzip).unwrap();
+        let stacked = compress(&gzipped, Coding::Br).unwrap();
+        let headers = encoded_as("gzip, br");
+        assert_eq!(decompress(&headers, stacked.clone()).unwrap(), BODY);
+        // The same codings split over two headers
+        let mut headers = encoded_as("gzip");
+        headers.append(CONTENT_ENCODING, HeaderValue::from_static("BR"));
+        assert_eq!(decompress(&headers, stacked).unwrap(), BODY);
+        // identity and no header leave the body as is.
+        let headers = encoded_as("identity");
+        assert_eq!(decompress(&headers, BODY.to_vec()).unwrap(), BODY);
+        assert_eq!(decompress(&HeaderMap::new(), BODY.to_vec()).unwrap(), BODY);
+    }
+
+    #[test]
+    fn rejects_unknown_and_corrupt_codings() {
+        let error = decompress(&encoded_as("compress"), BODY.to_vec()).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported Content-Encoding compress");
+        let error = decompress(&encoded_as("gzip"), BODY.to_vec()).unwrap_err();
+        assert!(error.to_string().contains("decompress the gzip"), "{error}");
+    }
+}
@@ -0,0 +1,378 @@
+use super::proxy::ProxyConfig;
+use super::proxy::ProxyKind;
+use crate::output::report::StatisticList;
+use crate::vojo::cli::Cli;
+use hyper::rt::Read;
+use hyper::rt::ReadBufCursor;
+use hyper::rt::Write;
+use hyper::Uri;
+use hyper_util::client::legacy::connect::Connected;
+use hyper_util::client::legacy::connect::Connection;
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use tokio::net::TcpSocket;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tower_service::Service;
+
+pub const PHASE_TCP_CONNECT: &str = "TCP Connect";
+pub const PHASE_PROXY_SETUP: &str = "Proxy Setup";
+pub const PHASE_UNIX_CONNECT: &str = "Unix Connect";
+
+pub const LABEL_REMOTE_IP: &str = "Remote IP";
+pub const LABEL_SOURCE_ADDRESS: &str = "Source Address";
+
+/// Opens the TCP connections for the client, directly or through a proxy,
+/// and records how long each phase of the setup took.
+#[derive(Clone)]
+pub struct KtConnector {
+    proxy: Arc<ProxyConfig>,
+    // Every connection goes to this socket when set, whatever the URL host is.
+    unix_socket: Option<PathBuf>,
+    resolve_overrides: Arc<HashMap<(String, u16), Vec<IpAddr>>>,
+    spread_ips: bool,
+    next_addr: Arc<AtomicUsize>,
+    local_addrs: Arc<Vec<IpAddr>>,
+    next_local_addr: Arc<AtomicUsize>,
+    interface: Option<String>,
+    shared_list: Arc<Mutex<StatisticList>>,
+}
+
+/// A failed connection attempt, with the addresses that were used when there were any.
+#[derive(Debug)]
+pub struct ConnectError {
+    pub remote_addr: Option<SocketAddr>,
+    pub local_addr: Option<IpAddr>,
+    pub error: anyhow::Error,
+}
+
+impl Display for ConnectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+impl From<anyhow::Error> for ConnectError {
+    fn from(error: anyhow::Error) -> Self {
+        ConnectError {
+            remote_addr: None,
+            local_addr: None,
+            error,
+        }
+    }
+}
+
+impl KtConnector {
+    pub fn new(cli: &Cli, shared_list: Arc<Mutex<StatisticList>>) -> Result<Self, anyhow::Error> {
+        let resolve_overrides = cli
+            .resolve
+            .iter()
+            .map(|entry| ((entry.host.clone(), entry.port), entry.addrs.clone()))
+            .collect();
+        if cli.interface.is_some() && !cfg!(any(target_os = "linux", target_os = "android")) {
+            return Err(anyhow!("--interface is only supported on Linux"));
+        }
+        Ok(KtConnector {
+            proxy: Arc::new(ProxyConfig::new(cli.proxy.as_deref())?),
+            unix_socket: cli.unix_socket.clone(),
+            resolve_overrides: Arc::new(resolve_overrides),
+            spread_ips: cli.spread_ips,
+            next_addr: Arc::new(AtomicUsize::new(0)),
+            local_addrs: Arc::new(cli.local_address.clone()),
+            next_local_addr: Arc::new(AtomicUsize::new(0)),
+            interface: cli.interface.clone(),
+            shared_list,
+        })
+    }
+
+    pub fn proxy(&self) -> Arc<ProxyConfig> {
+        self.proxy.clone()
+    }
+
+    async fn connect(self, dst: Uri) -> Result<KtStream, ConnectError> {
+        if let Some(path) = &self.unix_socket {
+            let now = Instant::now();
+            let stream = unix_connect(path).await?;
+            self.record_phase(PHASE_UNIX_CONNECT, now).await;
+            return Ok(stream);
+        }
+
+        let host = dst
+            .host()
+            .ok_or_else(|| anyhow!("URL has no host: {dst}"))?
+            .trim_matches(['[', ']'])
+            .to_string();
+        let port = dst.port_u16().unwrap_or(match dst.scheme_str() {
+            Some("https") => 443,
+            _ => 80,
+        });
+        let proxy = self.proxy.for_uri(&dst).cloned();
+
+        let now = Instant::now();
+        let (mut stream, remote_addr) = match &proxy {
+            Some(proxy) => self
+                .tcp_connect(&proxy.host, proxy.port)
+                .await
+                .map_err(|e| ConnectError {
+                    error: anyhow!("Failed to connect to proxy {}: {}", proxy.host, e.error),
+                    ..e
+                })?,
+            None => self.tcp_connect(&host, port).await?,
+        };
+        self.record_phase(PHASE_TCP_CONNECT, now).await;
+
+        let mut proxied = false;
+        if let Some(proxy) = &proxy {
+            // Plain http through an http proxy needs no tunnel, the client sends absolute URIs.
+            if proxy.kind == ProxyKind::Http && dst.scheme_str() != Some("https") {
+                proxied = true;
+            } else {
+                let now = Instant::now();
+                proxy
+                    .handshake(&mut stream, &host, port)
+                    .await
+                    .map_err(|error| ConnectError {
+                        remote_addr: Some(remote_addr),
+                        local_addr: stream.local_addr().ok().map(|addr| addr.ip()),
+                        error,
+                    })?;
+                self.record_phase(PHASE_PROXY_SETUP, now).await;
+            }
+        }
+        Ok(KtStream {
+            inner: StreamKind::Tcp(TokioIo::new(stream)),
+            proxied,
+        })
+    }
+
+    /// Connects to the first address of `host` that accepts, or to the next address in turn
+    /// when spreading connections across IPs.
+    async fn tcp_connect(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<(TcpStream, SocketAddr), ConnectError> {
+        let addrs = self.candidates(self.resolve(host, port).await?);
+        let mut last_error = None;
+        for addr in addrs {
+            match self.open_socket(addr) {
+                Ok((socket, local_addr)) => match socket.connect(addr).await {
+                    Ok(stream) => return Ok((stream, addr)),
+                    Err(e) => {
+                        last_error = Some(ConnectError {
+                            remote_addr: Some(addr),
+                            local_addr,
+                            error: anyhow!(e),
+                        })
+                    }
+                },
+                Err(error) => {
+                    last_error = Some(ConnectError {
+                        remote_addr: Some(addr),
+                        local_addr: None,
+                        error,
+                    })
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("Failed to resolve {host}").into()))
+    }
+
+    /// Creates a socket for `addr`, bound to the next source address of the same family
+    /// and to the selected interface when those are configured.
+    fn open_socket(&self, addr: SocketAddr) -> Result<(TcpSocket, Option<IpAddr>), anyhow::Error> {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        if let Some(interface) = &self.interface {
+            bind_device(&socket, interface)?;
+        }
+        let Some(local_addr) = self.next_local_addr(addr)? else {
+            return Ok((socket, None));
+        };
+        // Port 0 lets the kernel pick an ephemeral port on the chosen address.
+        socket
+            .bind(SocketAddr::new(local_addr, 0))
+            .map_err(|e| anyhow!("Failed to bind local address {local_addr}: {e}"))?;
+        Ok((socket, Some(local_addr)))
+    }
+
+    /// The resolved addresses to try in order, or only the next one in turn when spreading
+    /// connections across IPs.
+    pub fn candidates(&self, addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        // No fallback when spreading, so a bad node shows up as errors against its own IP.
+        if self.spread_ips && !addrs.is_empty() {
+            let index = self.next_addr.fetch_add(1, Ordering::Relaxed) % addrs.len();
+            return vec![addrs[index]];
+        }
+        addrs
+    }
+
+    /// The next --local-address of the same family as `addr`, or None without the option.
+    pub fn next_local_addr(&self, addr: SocketAddr) -> Result<Option<IpAddr>, anyhow::Error> {
+        if self.local_addrs.is_empty() {
+            return Ok(None);
+        }
+        let candidates: Vec<&IpAddr> = self
+            .local_addrs
+            .iter()
+            .filter(|ip| ip.is_ipv4() == addr.is_ipv4())
+            .collect();
+        if candidates.is_empty() {
+            return Err(anyhow!(
+                "No --local-address has the same address family as {}",
+                addr.ip()
+            ));
+        }
+        let index = self.next_local_addr.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        Ok(Some(*candidates[index]))
+    }
+
+    pub async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, anyhow::Error> {
+        if let Some(addrs) = self
+            .resolve_overrides
+            .get(&(host.to_ascii_lowercase(), port))
+        {
+            return Ok(addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect());
+        }
+        Ok(tokio::net::lookup_host((host, port)).await?.collect())
+    }
+
+    async fn record_phase(&self, phase: &str, started: Instant) {
+        let elapsed = started.elapsed().as_nanos() as u64;
+        self.shared_list.lock().await.record_phase(phase, elapsed);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_device(socket: &TcpSocket, interface: &str) -> Result<(), anyhow::Error> {
+    socket
+        .bind_device(Some(interface.as_bytes()))
+        .map_err(|e| anyhow!("Failed to bind to interface {interface}: {e}"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_device(_: &TcpSocket, _: &str) -> Result<(), anyhow::Error> {
+    Err(anyhow!("--interface is only supported on Linux"))
+}
+
+#[cfg(unix)]
+async fn unix_connect(path: &Path) -> Result<KtStream, anyhow::Error> {
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(|e| anyhow!("Failed to connect to {}: {e}", path.display()))?;
+    Ok(KtStream {
+        inner: StreamKind::Unix(TokioIo::new(stream)),
+        proxied: false,
+    })
+}
+
+#[cfg(not(unix))]
+async fn unix_connect(_: &Path) -> Result<KtStream, anyhow::Error> {
+    Err(anyhow!(
+        "Unix domain sockets are not supported on this platform"
+    ))
+}
+
+impl Service<Uri> for KtConnector {
+    type Response = KtStream;
+    type Error = ConnectError;
+    type Future = Pin<Box<dyn Future<Output = Result<KtStream, ConnectError>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        Box::pin(self.clone().connect(dst))
+    }
+}
+
+/// A connection opened by [`KtConnector`].
+pub struct KtStream {
+    inner: StreamKind,
+    // Set when talking to an http proxy without a tunnel, so requests use absolute URIs.
+    proxied: bool,
+}
+
+enum StreamKind {
+    Tcp(TokioIo<TcpStream>),
+    #[cfg(unix)]
+    Unix(TokioIo<tokio::net::UnixStream>),
+}
+
+// Forwards a call to whichever stream is inside.
+macro_rules! with_stream {
+    ($kind:expr, $stream:ident => $body:expr) => {
+        match $kind {
+            StreamKind::Tcp($stream) => $body,
+            #[cfg(unix)]
+            StreamKind::Unix($stream) => $body,
+        }
+    };
+}
+
+impl Connection for KtStream {
+    fn connected(&self) -> Connected {
+        with_stream!(&self.inner, stream => stream.connected()).proxy(self.proxied)
+    }
+}
+
+impl Read for KtStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        with_stream!(&mut self.inner, stream => Pin::new(stream).poll_read(cx, buf))
+    }
+}
+
+impl Write for KtStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        with_stream!(&mut self.inner, stream => Pin::new(stream).poll_write(cx, buf))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        with_stream!(&mut self.inner, stream => Pin::new(stream).poll_flush(cx))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        with_stream!(&mut self.inner, stream => Pin::new(stream).poll_shutdown(cx))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        with_stream!(&self.inner, stream => stream.is_write_vectored())
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        with_stream!(&mut self.inner, stream => Pin::new(stream).poll_write_vectored(cx, bufs))
+    }
+}
@@ -0,0 +1,62 @@
+use cookie_store::CookieStore;
+use cookie_store::RawCookie;
+use hyper::header::HeaderValue;
+use hyper::header::COOKIE;
+use hyper::header::SET_COOKIE;
+use hyper::HeaderMap;
+use hyper::Uri;
+use std::sync::Arc;
+use std::sync::Mutex;
+use url::Url;
+
+/// A cookie jar owned by one worker, or shared by all of them.
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    store: Arc<Mutex<CookieStore>>,
+}
+
+impl CookieJar {
+    /// Adds the cookies matching `uri` to the `Cookie` header, after any cookie set with -H.
+    pub fn apply(&self, uri: &Uri, headers: &mut HeaderMap) {
+        let Ok(url) = Url::parse(&uri.to_string()) else {
+            return;
+        };
+        let store = self.store.lock().unwrap();
+        let mut pairs: Vec<String> = headers
+            .get(COOKIE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| vec![v.to_string()])
+            .unwrap_or_default();
+        pairs.extend(
+            store
+                .get_request_values(&url)
+                .map(|(name, value)| format!("{name}={value}")),
+        );
+        if pairs.is_empty() {
+            return;
+        }
+        if let Ok(value) = HeaderValue::from_str(&pairs.join("; ")) {
+            headers.insert(COOKIE, value);
+        }
+    }
+
+    /// Stores every `Set-Cookie` header of a response received from `uri`.
+    pub fn store(&self, uri: &Uri, headers: &HeaderMap) {
+        let Ok(url) = Url::parse(&uri.to_string()) else {
+            return;
+        };
+        let cookies = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| RawCookie::parse(v.to_string()).ok())
+            .collect::<Vec<_>>();
+        if cookies.is_empty() {
+            return;
+        }
+        self.store
+            .lock()
+            .unwrap()
+            .store_response_cookies(cookies.into_iter(), &url);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::output::report::Labels;
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::HeaderValue;
+use http::Method;
+use http::Request;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use serde_json::json;
+use serde_json::Value;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const LABEL_OPERATION: &str = "Operation";
+
+/// One GraphQL operation to send, named after the operation or its file.
+pub struct Operation {
+    pub name: String,
+    // Sent as operationName when the document holds more than one operation
+    operation_name: Option<String>,
+    document: String,
+}
+
+impl Operation {
+    /// The JSON POST body for this operation.
+    pub fn body(&self, variables: Option<&Value>) -> Result<Vec<u8>, anyhow::Error> {
+        let mut body = json!({ "query": self.document });
+        if let Some(operation_name) = &self.operation_name {
+            body["operationName"] = json!(operation_name);
+        }
+        if let Some(variables) = variables {
+            body["variables"] = variables.clone();
+        }
+        Ok(serde_json::to_vec(&body)?)
+    }
+}
+
+/// Reads the operations of a `.graphql` file. A document with several operations yields one
+/// operation each, sent with its operationName.
+pub fn load_operations(path: &Path) -> Result<Vec<Operation>, anyhow::Error> {
+    let document = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let names = operation_names(&document);
+    if names.is_empty() {
+        return Err(anyhow!("{} has no GraphQL operation", path.display()));
+    }
+    if names.len() == 1 {
+        let file_name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        return Ok(vec![Operation {
+            name: names[0].clone().unwrap_or(file_name),
+            operation_name: None,
+            document,
+        }]);
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let name = name.ok_or_else(|| {
+                anyhow!(
+                    "{} mixes an anonymous operation with others",
+                    path.display()
+                )
+            })?;
+            Ok(Operation {
+                name: name.clone(),
+                operation_name: Some(name),
+                document: document.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Fails when a GraphQL response carries a non-empty `errors` array, whatever its status.
+pub fn check_response(body: &[u8]) -> Result<(), anyhow::Error> {
+    let Ok(response) = serde_json::from_slice::<Value>(body) else {
+        return Ok(());
+    };
+    let Some(first) = response
+        .get("errors")
+        .and_then(Value::as_array)
+        .and_then(|errors| errors.first())
+    else {
+        return Ok(());
+    };
+    let message = first
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown error");
+    Err(anyhow!("GraphQL error: {message}"))
+}
+
+/// The names of the top-level operations in `document`, None for an anonymous one.
+/// Fragments are skipped, as are comments and strings.
+fn operation_names(document: &str) -> Vec<Option<String>> {
+    let mut names = vec![];
+    let mut depth = 0;
+    // Object default values of variables use braces too, so braces inside parentheses are skipped.
+    let mut parens = 0;
+    // Set after `query`, `mutation` or `subscription` until its selection set opens
+    let mut in_operation = false;
+    let mut in_fragment = false;
+    let mut chars = document.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '#' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            '"' => {
+                if document[start..].starts_with("\"\"\"") {
+                    chars.nth(1);
+                    let rest = start + 3;
+                    let end = document[rest..]
+                        .find("\"\"\"")
+                        .map_or(document.len(), |i| rest + i + 3);
+                    while chars.next_if(|&(i, _)| i < end).is_some() {}
+                } else {
+                    let mut escaped = false;
+                    for (_, c) in chars.by_ref() {
+                        match c {
+                            '\\' if !escaped => escaped = true,
+                            '"' if !escaped => break,
+                            _ => escaped = false,
+                        }
+                    }
+                }
+            }
+            '(' => parens += 1,
+            ')' => parens -= 1,
+            '{' if parens == 0 => {
+                if depth == 0 && !in_operation && !in_fragment {
+                    // Shorthand `{ ... }` query
+                    names.push(None);
+                }
+                in_operation = false;
+                in_fragment = false;
+                depth += 1;
+            }
+            '}' if parens == 0 => depth -= 1,
+            c if depth == 0 && parens == 0 && (c.is_ascii_alphabetic() || c == '_') => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, _)) =
+                    chars.next_if(|&(_, c)| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    end = i + 1;
+                }
+                match &document[start..end] {
+                    "query" | "mutation" | "subscription" if !in_operation => {
+                        in_operation = true;
+                        while chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+                        let name_start = chars.peek().map_or(document.len(), |&(i, _)| i);
+                        let mut name_end = name_start;
+                        while let Some((i, _)) =
+                            chars.next_if(|&(_, c)| c.is_ascii_alphanumeric() || c == '_')
+                        {
+                            name_end = i + 1;
+                        }
+                        let name = &document[name_start..name_end];
+                        names.push((!name.is_empty()).then(|| name.to_string()));
+                    }
+                    "fragment" if !in_operation => in_fragment = true,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Reads `--graphql-variables`, given inline or as @file.
+pub async fn read_variables(arg: &str) -> Result<Value, anyhow::Error> {
+    let text = match arg.strip_prefix('@') {
+        Some(path) => tokio::fs::read_to_string(path).await?,
+        None => arg.to_string(),
+    };
+    let variables: Value = serde_json::from_str(&text)
+        .map_err(|e| anyhow!("GraphQL variables are not valid JSON: {e}"))?;
+    if !variables.is_object() {
+        return Err(anyhow!("GraphQL variables must be a JSON object"));
+    }
+    Ok(variables)
+}
+
+/// One JSON POST request per operation in `paths`, labelled with the operation name.
+pub fn build_requests(
+    parts: &Parts,
+    paths: &[PathBuf],
+    variables: Option<&Value>,
+) -> Result<Vec<Request<Full<Bytes>>>, anyhow::Error> {
+    let mut parts = parts.clone();
+    parts.method = Method::POST;
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    let mut requests = vec![];
+    for path in paths {
+        for operation in load_operations(path)? {
+            let body = operation.body(variables)?;
+            let mut request = Request::from_parts(parts.clone(), Full::new(body.into()));
+            let labels: Labels = vec![(LABEL_OPERATION, operation.name)];
+            request.extensions_mut().insert(labels);
+            requests.push(request);
+        }
+    }
+    Ok(requests)
+}
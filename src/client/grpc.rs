@@ -0,0 +1,103 @@
+use super::protobuf::DescriptorPool;
+use http::header::CONTENT_TYPE;
+use http::header::TE;
+use http::request::Parts;
+use http::HeaderMap;
+use http::HeaderValue;
+use http::Method;
+use http::Request;
+use http::Uri;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use std::path::Path;
+
+/// Status of a call the client gave up on, used when a stream is cut by the test.
+pub const STATUS_CANCELLED: u16 = 1;
+
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+pub fn status_name(code: u16) -> &'static str {
+    STATUS_NAMES
+        .get(code as usize)
+        .copied()
+        .unwrap_or("UNKNOWN")
+}
+
+/// Turns the request parts into a call of `method` ("package.Service/Method") carrying
+/// `body` as the request message. With a descriptor set `body` is JSON, otherwise it is the
+/// serialized protobuf message.
+pub fn call_request(
+    mut parts: Parts,
+    method: &str,
+    proto_set: Option<&Path>,
+    body: &[u8],
+) -> Result<Request<Full<Bytes>>, anyhow::Error> {
+    let method = method.trim_start_matches('/');
+    let message = match proto_set {
+        Some(path) => {
+            let descriptor_set = std::fs::read(path)
+                .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+            let pool = DescriptorPool::decode(&descriptor_set)?;
+            let descriptor = pool.method(method)?;
+            if descriptor.client_streaming {
+                return Err(anyhow!(
+                    "{method} is a client streaming method, only unary and server streaming calls are supported"
+                ));
+            }
+            let json = if body.is_empty() {
+                serde_json::Value::Object(Default::default())
+            } else {
+                serde_json::from_slice(body)
+                    .map_err(|e| anyhow!("The request message is not valid JSON: {e}"))?
+            };
+            pool.encode_json(&descriptor.input_type, &json)?
+        }
+        None => body.to_vec(),
+    };
+    // Uncompressed flag, then the message length in big endian
+    let mut frame = Vec::with_capacity(message.len() + 5);
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&message);
+
+    let path = format!("{}/{method}", parts.uri.path().trim_end_matches('/'));
+    let mut uri_parts = parts.uri.clone().into_parts();
+    uri_parts.path_and_query = Some(path.parse()?);
+    parts.uri = Uri::from_parts(uri_parts)?;
+    parts.method = Method::POST;
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+    parts
+        .headers
+        .insert(TE, HeaderValue::from_static("trailers"));
+    Ok(Request::from_parts(parts, Full::new(frame.into())))
+}
+
+/// The status of a finished call, from the trailers or from the headers of a
+/// trailers-only response.
+pub fn status(headers: &HeaderMap, trailers: Option<&HeaderMap>) -> Option<u16> {
+    trailers
+        .and_then(|trailers| trailers.get("grpc-status"))
+        .or_else(|| headers.get("grpc-status"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
@@ -0,0 +1,217 @@
+use super::connector::KtConnector;
+use crate::output::report::ResponseStatistic;
+use crate::output::report::StatisticList;
+use crate::vojo::cli::Cli;
+use futures::future::poll_fn;
+use h3::client::SendRequest;
+use http::Request;
+use http::Uri;
+use http_body_util::BodyExt;
+use http_body_util::Full;
+use hyper::body::Buf;
+use hyper::body::Bytes;
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::ClientConfig;
+use quinn::Endpoint;
+use quinn::VarInt;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+pub const PHASE_QUIC_HANDSHAKE: &str = "QUIC Handshake";
+pub const PHASE_ZERO_RTT: &str = "0-RTT Handshake";
+pub const PHASE_ZERO_RTT_REJECTED: &str = "0-RTT Rejected";
+
+// H3_NO_ERROR, for closing a connection that is no longer needed
+const H3_NO_ERROR: VarInt = VarInt::from_u32(0x100);
+
+/// Sends requests over HTTP/3. Every worker holds one QUIC connection, opened on first use
+/// and again after it fails or has sent --http3-reconnect-after requests. Session tickets
+/// are shared, so reconnects can use 0-RTT.
+#[derive(Clone)]
+pub struct Http3Client {
+    config: ClientConfig,
+    connector: KtConnector,
+    zero_rtt: bool,
+    reconnect_after: Option<u64>,
+    shared_list: Arc<Mutex<StatisticList>>,
+    connection: Arc<Mutex<Option<Http3Connection>>>,
+}
+
+#[derive(Clone)]
+struct Http3Connection {
+    sender: SendRequest<h3_quinn::OpenStreams, Bytes>,
+    quic: quinn::Connection,
+    // Requests sent on this connection so far
+    sent: u64,
+    // The connection is closed once the last endpoint handle is gone.
+    _endpoint: Endpoint,
+}
+
+impl Http3Client {
+    pub fn new(cli: &Cli, shared_list: Arc<Mutex<StatisticList>>) -> Result<Self, anyhow::Error> {
+        if cli.url.scheme_str() != Some("https") {
+            return Err(anyhow!("--http3 needs an https:// URL"));
+        }
+        let mut tls_config = super::tls_config(cli.cacert.as_deref())?;
+        tls_config.alpn_protocols = vec![b"h3".to_vec()];
+        tls_config.enable_early_data = cli.zero_rtt;
+        let quic_config = QuicClientConfig::try_from(tls_config)
+            .map_err(|e| anyhow!("The TLS settings cannot be used for QUIC: {e}"))?;
+        Ok(Http3Client {
+            config: ClientConfig::new(Arc::new(quic_config)),
+            connector: KtConnector::new(cli, shared_list.clone())?,
+            zero_rtt: cli.zero_rtt,
+            reconnect_after: cli.http3_reconnect_after,
+            shared_list,
+            connection: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// A copy for the next spawned worker, which opens its own connection.
+    pub fn for_next_worker(&self) -> Http3Client {
+        Http3Client {
+            connection: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
+    /// Sends `request` and reads the response to its end. Like the other clients the latency
+    /// runs from `started` to the response headers, so it includes a handshake when one
+    /// was needed.
+    pub async fn send(
+        &self,
+        request: Request<Full<Bytes>>,
+        started: Instant,
+    ) -> Result<ResponseStatistic, anyhow::Error> {
+        let mut connection = self.connection.lock().await;
+        if let Some(open) = &*connection {
+            let used_up = self
+                .reconnect_after
+                .is_some_and(|reconnect_after| open.sent >= reconnect_after);
+            if used_up {
+                open.quic.close(H3_NO_ERROR, b"");
+            }
+            // Like the hyper pool, a connection the server already closed is not reused.
+            if used_up || open.quic.close_reason().is_some() {
+                *connection = None;
+            }
+        }
+        let open = match &mut *connection {
+            Some(open) => open,
+            None => connection.insert(self.connect(request.uri()).await?),
+        };
+        open.sent += 1;
+        let mut sender = open.sender.clone();
+        let (parts, body) = request.into_parts();
+        let body = body.collect().await?.to_bytes();
+        let result = async {
+            let mut stream = sender.send_request(Request::from_parts(parts, ())).await?;
+            if !body.is_empty() {
+                stream.send_data(body).await?;
+            }
+            stream.finish().await?;
+            let response = stream.recv_response().await?;
+            let time_cost_ns = started.elapsed().as_nanos() as u64;
+            let mut content_length = 0;
+            while let Some(chunk) = stream.recv_data().await? {
+                content_length += chunk.remaining() as u64;
+            }
+            Ok(ResponseStatistic {
+                time_cost_ns,
+                status_code: response.status().as_u16(),
+                content_length,
+                redirects: 0,
+            })
+        }
+        .await;
+        if result.is_err() {
+            // The next request starts over on a new connection.
+            *connection = None;
+        }
+        result
+    }
+
+    /// Connects to the addresses of the URL host in turn, like the TCP connector, until a
+    /// handshake succeeds.
+    async fn connect(&self, uri: &Uri) -> Result<Http3Connection, anyhow::Error> {
+        let host = uri.host().unwrap_or_default().trim_matches(['[', ']']);
+        let port = uri.port_u16().unwrap_or(443);
+        let addrs = self
+            .connector
+            .candidates(self.connector.resolve(host, port).await?);
+        let mut last_error = None;
+        for addr in addrs {
+            match self.connect_to(addr, host).await {
+                Ok(connection) => return Ok(connection),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("{host} did not resolve to any address")))
+    }
+
+    async fn connect_to(
+        &self,
+        addr: SocketAddr,
+        server_name: &str,
+    ) -> Result<Http3Connection, anyhow::Error> {
+        let local_ip = match self.connector.next_local_addr(addr)? {
+            Some(ip) => ip,
+            None if addr.is_ipv4() => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            None => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let mut endpoint = Endpoint::client(SocketAddr::new(local_ip, 0))
+            .map_err(|e| anyhow!("Failed to bind local address {local_ip}: {e}"))?;
+        endpoint.set_default_client_config(self.config.clone());
+
+        let now = Instant::now();
+        let connecting = endpoint.connect(addr, server_name)?;
+        // Without a session ticket there is nothing to resume, so 0-RTT falls back to a full
+        // handshake.
+        let zero_rtt = if self.zero_rtt {
+            connecting.into_0rtt()
+        } else {
+            Err(connecting)
+        };
+        let connection = match zero_rtt {
+            Ok((connection, accepted)) => {
+                let shared_list = self.shared_list.clone();
+                tokio::spawn(async move {
+                    let phase = if accepted.await {
+                        PHASE_ZERO_RTT
+                    } else {
+                        PHASE_ZERO_RTT_REJECTED
+                    };
+                    let elapsed = now.elapsed().as_nanos() as u64;
+                    shared_list.lock().await.record_phase(phase, elapsed);
+                });
+                connection
+            }
+            Err(connecting) => {
+                let connection = connecting.await?;
+                let elapsed = now.elapsed().as_nanos() as u64;
+                self.shared_list
+                    .lock()
+                    .await
+                    .record_phase(PHASE_QUIC_HANDSHAKE, elapsed);
+                connection
+            }
+        };
+        let quic = connection.clone();
+        let (mut driver, sender) = h3::client::new(h3_quinn::Connection::new(connection)).await?;
+        tokio::spawn(async move {
+            let error = poll_fn(|cx| driver.poll_close(cx)).await;
+            debug!("HTTP/3 connection closed: {error}");
+        });
+        Ok(Http3Connection {
+            sender,
+            quic,
+            sent: 0,
+            _endpoint: endpoint,
+        })
+    }
+}
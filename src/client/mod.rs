@@ -0,0 +1,50 @@
+pub mod compression;
+pub mod connector;
+pub mod cookie;
+pub mod graphql;
+pub mod grpc;
+pub mod http3;
+pub mod multipart;
+pub mod protobuf;
+pub mod proxy;
+pub mod redirect;
+pub mod retry;
+pub mod stream;
+pub mod websocket;
+
+use rustls::crypto::ring::default_provider;
+use rustls::crypto::ring::DEFAULT_CIPHER_SUITES;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use rustls::ClientConfig;
+use rustls::RootCertStore;
+use std::path::Path;
+
+/// The TLS settings shared by every client kt builds. Certificates in `ca_cert` are trusted
+/// on top of the built-in roots.
+pub fn tls_config(ca_cert: Option<&Path>) -> Result<ClientConfig, anyhow::Error> {
+    let mut root_store = RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(path) = ca_cert {
+        let pem =
+            std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+        for cert in CertificateDer::pem_slice_iter(&pem) {
+            let cert =
+                cert.map_err(|e| anyhow!("Invalid certificate in {}: {e}", path.display()))?;
+            root_store.add(cert)?;
+        }
+    }
+    let versions = rustls::DEFAULT_VERSIONS.to_vec();
+    let tls_config = ClientConfig::builder_with_provider(
+        CryptoProvider {
+            cipher_suites: DEFAULT_CIPHER_SUITES.to_vec(),
+            ..default_provider()
+        }
+        .into(),
+    )
+    .with_protocol_versions(&versions)?
+    .with_root_certificates(root_store)
+    .with_no_client_auth();
+    Ok(tls_config)
+}
@@ -0,0 +1,326 @@
+use http::HeaderValue;
+use hyper::body::Bytes;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Content types guessed from the file extension when --form gives no `type=`.
+const CONTENT_TYPES: [(&str, &str); 16] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("pdf", "application/pdf"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("zip", "application/zip"),
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+/// A multipart/form-data body built from --form fields. Text fields and single files are
+/// encoded up front. A directory can hold far more than fits in memory, so only its file
+/// list is kept and each request reads the file it picks.
+pub struct Form {
+    boundary: String,
+    content_type: HeaderValue,
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Encoded(Bytes),
+    Directory(Vec<FormFile>),
+}
+
+/// A file of a directory field, read when a request picks it.
+struct FormFile {
+    name: String,
+    path: PathBuf,
+    filename: String,
+    content_type: String,
+}
+
+impl Form {
+    /// Reads the files of `fields`, each given as NAME=VALUE or NAME=@PATH[;type=..][;filename=..].
+    pub fn load(fields: &[String]) -> Result<Form, anyhow::Error> {
+        let boundary = format!(
+            "------------------------kt{:016x}",
+            rand::thread_rng().gen::<u64>()
+        );
+        let mut parts = vec![];
+        for field in fields {
+            let (name, value) = field.split_once('=').ok_or_else(|| {
+                anyhow!("Invalid form field {field}, use NAME=VALUE or NAME=@FILE")
+            })?;
+            let Some(file) = value.strip_prefix('@') else {
+                parts.push(Part::Encoded(encode_part(
+                    &boundary,
+                    name,
+                    None,
+                    None,
+                    value.as_bytes(),
+                )));
+                continue;
+            };
+            let mut params = file.split(';');
+            let path = Path::new(params.next().unwrap_or_default());
+            let mut content_type = None;
+            let mut filename = None;
+            for param in params {
+                match param.split_once('=') {
+                    Some(("type", value)) => content_type = Some(value),
+                    Some(("filename", value)) => filename = Some(value),
+                    _ => return Err(anyhow!("Unknown parameter {param} in form field {field}")),
+                }
+            }
+            let describe = |path: &Path| {
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let guessed = path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .and_then(|extension| {
+                        let extension = extension.to_lowercase();
+                        CONTENT_TYPES
+                            .iter()
+                            .find(|(known, _)| *known == extension)
+                            .map(|(_, content_type)| *content_type)
+                    });
+                (
+                    filename.unwrap_or(&file_name).to_string(),
+                    content_type
+                        .or(guessed)
+                        .unwrap_or("application/octet-stream")
+                        .to_string(),
+                )
+            };
+            if !path.is_dir() {
+                let data = std::fs::read(path)
+                    .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+                let (filename, content_type) = describe(path);
+                parts.push(Part::Encoded(encode_part(
+                    &boundary,
+                    name,
+                    Some(&filename),
+                    Some(&content_type),
+                    &data,
+                )));
+                continue;
+            }
+            let mut files = vec![];
+            for entry in std::fs::read_dir(path)
+                .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?
+            {
+                let entry_path = entry?.path();
+                if entry_path.is_file() {
+                    let (filename, content_type) = describe(&entry_path);
+                    files.push(FormFile {
+                        name: name.to_string(),
+                        path: entry_path,
+                        filename,
+                        content_type,
+                    });
+                }
+            }
+            if files.is_empty() {
+                return Err(anyhow!("{} has no files to upload", path.display()));
+            }
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            parts.push(Part::Directory(files));
+        }
+        let content_type =
+            HeaderValue::from_str(&format!("multipart/form-data; boundary={boundary}"))?;
+        Ok(Form {
+            boundary,
+            content_type,
+            parts,
+        })
+    }
+
+    /// The Content-Type header naming the boundary.
+    pub fn content_type(&self) -> HeaderValue {
+        self.content_type.clone()
+    }
+
+    /// A body with one file picked at random for every directory field, read from disk now.
+    pub async fn body(&self) -> Result<Bytes, anyhow::Error> {
+        let picked: Vec<Option<&FormFile>> = {
+            let mut rng = rand::thread_rng();
+            self.parts
+                .iter()
+                .map(|part| match part {
+                    Part::Encoded(_) => None,
+                    Part::Directory(files) => files.choose(&mut rng),
+                })
+                .collect()
+        };
+        let mut encoded = Vec::with_capacity(self.parts.len());
+        for (part, file) in self.parts.iter().zip(picked) {
+            encoded.push(match (part, file) {
+                (Part::Encoded(bytes), _) => bytes.clone(),
+                (Part::Directory(_), Some(file)) => {
+                    let data = tokio::fs::read(&file.path)
+                        .await
+                        .map_err(|e| anyhow!("Failed to read {}: {e}", file.path.display()))?;
+                    encode_part(
+                        &self.boundary,
+                        &file.name,
+                        Some(&file.filename),
+                        Some(&file.content_type),
+                        &data,
+                    )
+                }
+                (Part::Directory(_), None) => Bytes::new(),
+            });
+        }
+        let closing = format!("--{}--\r\n", self.boundary);
+        let length = encoded.iter().map(|part| part.len()).sum::<usize>() + closing.len();
+        let mut body = Vec::with_capacity(length);
+        for part in encoded {
+            body.extend_from_slice(&part);
+        }
+        body.extend_from_slice(closing.as_bytes());
+        Ok(Bytes::from(body))
+    }
+}
+
+/// One part with its boundary line and headers.
+fn encode_part(
+    boundary: &str,
+    name: &str,
+    filename: Option<&str>,
+    content_type: Option<&str>,
+    data: &[u8],
+) -> Bytes {
+    // Quotes and line breaks are percent-encoded in names, as browsers do.
+    let quote = |text: &str| {
+        text.replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let mut head = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"",
+        quote(name)
+    );
+    if let Some(filename) = filename {
+        head.push_str(&format!("; filename=\"{}\"", quote(filename)));
+    }
+    head.push_str("\r\n");
+    if let Some(content_type) = content_type {
+        head.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    head.push_str("\r\n");
+    let mut part = head.into_bytes();
+    part.extend_from_slice(data);
+    part.extend_from_slice(b"\r\n");
+    Bytes::from(part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kt-form-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn body_text(form: &Form) -> String {
+        String::from_utf8(form.body().await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn encodes_text_fields_between_boundaries() {
+        let form = Form::load(&[String::from("name=Ann"), String::from("a\"b=x=y")]).unwrap();
+        let boundary = &form.boundary;
+        assert!(boundary.starts_with("------------------------kt"));
+        assert_eq!(
+            form.content_type(),
+            format!("multipart/form-data; boundary={boundary}").as_str()
+        );
+        assert_eq!(
+            body_text(&form).await,
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nAnn\r\n\
+                 --{boundary}\r\nContent-Disposition: form-data; name=\"a%22b\"\r\n\r\nx=y\r\n\
+                 --{boundary}--\r\n"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn describes_files_by_parameters_or_extension() {
+        let dir = temp_dir("files");
+        std::fs::write(dir.join("photo.PNG"), b"png").unwrap();
+        std::fs::write(dir.join("data.bin"), b"bin").unwrap();
+        let field = |spec: &str| spec.replace("DIR", &dir.display().to_string());
+        let form = Form::load(&[
+            field("a=@DIR/photo.PNG"),
+            field("b=@DIR/data.bin"),
+            field("c=@DIR/photo.PNG;type=image/x-custom;filename=me.png"),
+        ])
+        .unwrap();
+        let body = body_text(&form).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(body.contains(
+            "name=\"a\"; filename=\"photo.PNG\"\r\nContent-Type: image/png\r\n\r\npng\r\n"
+        ));
+        assert!(body.contains(
+            "name=\"b\"; filename=\"data.bin\"\r\nContent-Type: application/octet-stream\r\n"
+        ));
+        assert!(body.contains(
+            "name=\"c\"; filename=\"me.png\"\r\nContent-Type: image/x-custom\r\n\r\npng\r\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn reads_directory_files_per_request() {
+        let dir = temp_dir("dir");
+        std::fs::write(dir.join("one.txt"), b"first").unwrap();
+        let form = Form::load(&[format!("upload=@{}", dir.display())]).unwrap();
+        assert!(body_text(&form).await.contains(
+            "name=\"upload\"; filename=\"one.txt\"\r\nContent-Type: text/plain\r\n\r\nfirst\r\n"
+        ));
+        // The file is only read when a request picks it.
+        std::fs::write(dir.join("one.txt"), b"changed").unwrap();
+        assert!(body_text(&form).await.contains("\r\n\r\nchanged\r\n"));
+        std::fs::remove_file(dir.join("one.txt")).unwrap();
+        let error = form.body().await.unwrap_err();
+        assert!(error.to_string().contains("one.txt"), "{error}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_fields() {
+        let dir = temp_dir("empty");
+        let Err(error) = Form::load(&[format!("upload=@{}", dir.display())]) else {
+            panic!("an empty directory was accepted");
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            error.to_string().ends_with("has no files to upload"),
+            "{error}"
+        );
+        for (field, message) in [
+            ("no-value", "Invalid form field"),
+            ("f=@Cargo.toml;size=1", "Unknown parameter size=1"),
+            ("f=@/no/such/file", "Failed to read /no/such/file"),
+        ] {
+            let Err(error) = Form::load(&[field.to_string()]) else {
+                panic!("{field} was accepted");
+            };
+            assert!(error.to_string().contains(message), "{field}: {error}");
+        }
+    }
+}
@@ -0,0 +1,1009 @@
+use crate::calendar;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// FieldDescriptorProto.Type
+const TYPE_DOUBLE: i32 = 1;
+const TYPE_FLOAT: i32 = 2;
+const TYPE_INT64: i32 = 3;
+const TYPE_UINT64: i32 = 4;
+const TYPE_INT32: i32 = 5;
+const TYPE_FIXED64: i32 = 6;
+const TYPE_FIXED32: i32 = 7;
+const TYPE_BOOL: i32 = 8;
+const TYPE_STRING: i32 = 9;
+const TYPE_MESSAGE: i32 = 11;
+const TYPE_BYTES: i32 = 12;
+const TYPE_UINT32: i32 = 13;
+const TYPE_ENUM: i32 = 14;
+const TYPE_SFIXED32: i32 = 15;
+const TYPE_SFIXED64: i32 = 16;
+const TYPE_SINT32: i32 = 17;
+const TYPE_SINT64: i32 = 18;
+
+const LABEL_REPEATED: i32 = 3;
+
+const WIRE_VARINT: u32 = 0;
+const WIRE_FIXED64: u32 = 1;
+const WIRE_LEN: u32 = 2;
+const WIRE_FIXED32: u32 = 5;
+
+// Well-known types whose JSON form is not an object of their fields
+const WRAPPERS: [(&str, i32); 9] = [
+    (".google.protobuf.DoubleValue", TYPE_DOUBLE),
+    (".google.protobuf.FloatValue", TYPE_FLOAT),
+    (".google.protobuf.Int64Value", TYPE_INT64),
+    (".google.protobuf.UInt64Value", TYPE_UINT64),
+    (".google.protobuf.Int32Value", TYPE_INT32),
+    (".google.protobuf.UInt32Value", TYPE_UINT32),
+    (".google.protobuf.BoolValue", TYPE_BOOL),
+    (".google.protobuf.StringValue", TYPE_STRING),
+    (".google.protobuf.BytesValue", TYPE_BYTES),
+];
+const TIMESTAMP: &str = ".google.protobuf.Timestamp";
+const DURATION: &str = ".google.protobuf.Duration";
+const STRUCT: &str = ".google.protobuf.Struct";
+const VALUE: &str = ".google.protobuf.Value";
+const LIST_VALUE: &str = ".google.protobuf.ListValue";
+const FIELD_MASK: &str = ".google.protobuf.FieldMask";
+const EMPTY: &str = ".google.protobuf.Empty";
+const ANY: &str = ".google.protobuf.Any";
+
+/// The messages, enums and methods of a compiled `FileDescriptorSet`, enough to encode a
+/// request message from JSON.
+#[derive(Default)]
+pub struct DescriptorPool {
+    // Keyed by fully qualified name with a leading dot, as type_name references them
+    messages: HashMap<String, MessageDescriptor>,
+    enums: HashMap<String, HashMap<String, i32>>,
+    // Keyed by "package.Service/Method"
+    methods: HashMap<String, MethodDescriptor>,
+}
+
+#[derive(Default)]
+struct MessageDescriptor {
+    fields: Vec<FieldDescriptor>,
+    map_entry: bool,
+}
+
+#[derive(Default)]
+struct FieldDescriptor {
+    name: String,
+    json_name: String,
+    number: u32,
+    kind: i32,
+    repeated: bool,
+    type_name: String,
+}
+
+#[derive(Default)]
+pub struct MethodDescriptor {
+    pub input_type: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+}
+
+impl DescriptorPool {
+    /// Parses the output of `protoc --include_imports --descriptor_set_out=FILE`.
+    pub fn decode(descriptor_set: &[u8]) -> Result<Self, anyhow::Error> {
+        let mut pool = DescriptorPool::default();
+        for (number, value) in fields(descriptor_set)? {
+            if number == 1 {
+                pool.add_file(value.bytes()?)?;
+            }
+        }
+        Ok(pool)
+    }
+
+    pub fn method(&self, path: &str) -> Result<&MethodDescriptor, anyhow::Error> {
+        self.methods
+            .get(path)
+            .ok_or_else(|| anyhow!("Method {path} is not in the descriptor set"))
+    }
+
+    /// Encodes `json` as the message `type_name`, following the proto3 JSON mapping. The
+    /// well-known types are supported except google.protobuf.Any, which is rejected.
+    pub fn encode_json(&self, type_name: &str, json: &Value) -> Result<Vec<u8>, anyhow::Error> {
+        let mut out = vec![];
+        self.encode_message_json(type_name, json, "", &mut out)?;
+        Ok(out)
+    }
+
+    fn add_file(&mut self, file: &[u8]) -> Result<(), anyhow::Error> {
+        let mut package = String::new();
+        for (number, value) in fields(file)? {
+            if number == 2 {
+                package = value.string()?;
+            }
+        }
+        let scope = if package.is_empty() {
+            String::new()
+        } else {
+            format!(".{package}")
+        };
+        for (number, value) in fields(file)? {
+            match number {
+                4 => self.add_message(&scope, value.bytes()?)?,
+                5 => self.add_enum(&scope, value.bytes()?)?,
+                6 => self.add_service(&package, value.bytes()?)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn add_message(&mut self, scope: &str, message: &[u8]) -> Result<(), anyhow::Error> {
+        let mut name = String::new();
+        let mut descriptor = MessageDescriptor::default();
+        let mut nested = vec![];
+        for (number, value) in fields(message)? {
+            match number {
+                1 => name = value.string()?,
+                2 => descriptor.fields.push(decode_field(value.bytes()?)?),
+                3 | 4 => nested.push((number, value.bytes()?)),
+                7 => {
+                    for (option, value) in fields(value.bytes()?)? {
+                        if option == 7 {
+                            descriptor.map_entry = value.varint()? != 0;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let full_name = format!("{scope}.{name}");
+        for (number, bytes) in nested {
+            if number == 3 {
+                self.add_message(&full_name, bytes)?;
+            } else {
+                self.add_enum(&full_name, bytes)?;
+            }
+        }
+        self.messages.insert(full_name, descriptor);
+        Ok(())
+    }
+
+    fn add_enum(&mut self, scope: &str, descriptor: &[u8]) -> Result<(), anyhow::Error> {
+        let mut name = String::new();
+        let mut values = HashMap::new();
+        for (number, value) in fields(descriptor)? {
+            match number {
+                1 => name = value.string()?,
+                2 => {
+                    let mut value_name = String::new();
+                    let mut value_number = 0;
+                    for (number, value) in fields(value.bytes()?)? {
+                        match number {
+                            1 => value_name = value.string()?,
+                            2 => value_number = value.varint()? as i32,
+                            _ => {}
+                        }
+                    }
+                    values.insert(value_name, value_number);
+                }
+                _ => {}
+            }
+        }
+        self.enums.insert(format!("{scope}.{name}"), values);
+        Ok(())
+    }
+
+    fn add_service(&mut self, package: &str, service: &[u8]) -> Result<(), anyhow::Error> {
+        let mut name = String::new();
+        let mut methods = vec![];
+        for (number, value) in fields(service)? {
+            match number {
+                1 => name = value.string()?,
+                2 => {
+                    let mut method_name = String::new();
+                    let mut method = MethodDescriptor::default();
+                    for (number, value) in fields(value.bytes()?)? {
+                        match number {
+                            1 => method_name = value.string()?,
+                            2 => method.input_type = value.string()?,
+                            5 => method.client_streaming = value.varint()? != 0,
+                            6 => method.server_streaming = value.varint()? != 0,
+                            _ => {}
+                        }
+                    }
+                    methods.push((method_name, method));
+                }
+                _ => {}
+            }
+        }
+        let service = if package.is_empty() {
+            name
+        } else {
+            format!("{package}.{name}")
+        };
+        for (method_name, method) in methods {
+            self.methods
+                .insert(format!("{service}/{method_name}"), method);
+        }
+        Ok(())
+    }
+
+    fn encode_message(
+        &self,
+        type_name: &str,
+        object: &Map<String, Value>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), anyhow::Error> {
+        let message = self
+            .messages
+            .get(type_name)
+            .ok_or_else(|| anyhow!("Message {type_name} is not in the descriptor set"))?;
+        for (key, value) in object {
+            let field = message
+                .fields
+                .iter()
+                .find(|field| field.name == *key || field.json_name == *key)
+                .ok_or_else(|| anyhow!("{type_name} has no field '{key}'"))?;
+            // null leaves a field unset, except google.protobuf.Value where it is a value.
+            if value.is_null() && field.type_name != VALUE {
+                continue;
+            }
+            let entry_type = self
+                .messages
+                .get(&field.type_name)
+                .filter(|entry| entry.map_entry);
+            if let (Some(entry_type), Some(entries)) = (entry_type, value.as_object()) {
+                let malformed = || anyhow!("Map entry {} is malformed", field.type_name);
+                let key_field = entry_type.fields.first().ok_or_else(malformed)?;
+                let value_field = entry_type.fields.get(1).ok_or_else(malformed)?;
+                for (entry_key, entry_value) in entries {
+                    let mut entry = vec![];
+                    // JSON object keys are strings, integer keys are parsed from them.
+                    let key_value = if key_field.kind == TYPE_BOOL {
+                        Value::Bool(entry_key == "true")
+                    } else {
+                        Value::String(entry_key.clone())
+                    };
+                    self.encode_single(key_field, &key_value, &mut entry)?;
+                    self.encode_single(value_field, entry_value, &mut entry)?;
+                    write_tag(out, field.number, WIRE_LEN);
+                    write_bytes(out, &entry);
+                }
+            } else if field.repeated {
+                let items = value
+                    .as_array()
+                    .ok_or_else(|| anyhow!("Field '{key}' must be a JSON array"))?;
+                if packed_wire_type(field.kind).is_some() {
+                    let mut packed = vec![];
+                    for item in items {
+                        self.encode_value(field, item, &mut packed)?;
+                    }
+                    write_tag(out, field.number, WIRE_LEN);
+                    write_bytes(out, &packed);
+                } else {
+                    for item in items {
+                        self.encode_single(field, item, out)?;
+                    }
+                }
+            } else {
+                self.encode_single(field, value, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_single(
+        &self,
+        field: &FieldDescriptor,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), anyhow::Error> {
+        let wire_type = packed_wire_type(field.kind).unwrap_or(WIRE_LEN);
+        write_tag(out, field.number, wire_type);
+        self.encode_value(field, value, out)
+    }
+
+    /// Writes the value of `field` without its tag.
+    fn encode_value(
+        &self,
+        field: &FieldDescriptor,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), anyhow::Error> {
+        let name = &field.name;
+        match field.kind {
+            TYPE_DOUBLE => out.extend_from_slice(&json_f64(value, name)?.to_le_bytes()),
+            TYPE_FLOAT => out.extend_from_slice(&json_f32(value, name)?.to_le_bytes()),
+            TYPE_INT64 => write_varint(out, json_i64(value, name)? as u64),
+            TYPE_INT32 => write_varint(out, json_i32(value, name)? as i64 as u64),
+            TYPE_UINT64 => write_varint(out, json_u64(value, name)?),
+            TYPE_UINT32 => write_varint(out, json_u32(value, name)? as u64),
+            TYPE_SINT64 => {
+                let n = json_i64(value, name)?;
+                write_varint(out, ((n << 1) ^ (n >> 63)) as u64);
+            }
+            TYPE_SINT32 => {
+                let n = json_i32(value, name)?;
+                write_varint(out, ((n << 1) ^ (n >> 31)) as u32 as u64);
+            }
+            TYPE_FIXED64 => out.extend_from_slice(&json_u64(value, name)?.to_le_bytes()),
+            TYPE_SFIXED64 => out.extend_from_slice(&json_i64(value, name)?.to_le_bytes()),
+            TYPE_FIXED32 => out.extend_from_slice(&json_u32(value, name)?.to_le_bytes()),
+            TYPE_SFIXED32 => out.extend_from_slice(&json_i32(value, name)?.to_le_bytes()),
+            TYPE_BOOL => {
+                let b = value
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("Field '{name}' must be a boolean"))?;
+                write_varint(out, b as u64);
+            }
+            TYPE_ENUM => {
+                let number = match value {
+                    Value::String(s) => *self
+                        .enums
+                        .get(&field.type_name)
+                        .and_then(|values| values.get(s))
+                        .ok_or_else(|| anyhow!("'{s}' is not a value of {}", field.type_name))?,
+                    _ => json_i32(value, name)?,
+                };
+                write_varint(out, number as i64 as u64);
+            }
+            TYPE_STRING => {
+                let s = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Field '{name}' must be a string"))?;
+                write_bytes(out, s.as_bytes());
+            }
+            TYPE_BYTES => {
+                let s = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Field '{name}' must be a base64 string"))?;
+                write_bytes(out, &STANDARD.decode(s)?);
+            }
+            TYPE_MESSAGE => {
+                let mut nested = vec![];
+                self.encode_message_json(&field.type_name, value, name, &mut nested)?;
+                write_bytes(out, &nested);
+            }
+            other => return Err(anyhow!("Field '{name}' has unsupported type {other}")),
+        }
+        Ok(())
+    }
+
+    /// Encodes the fields of the message `type_name` given as `value`, which is an object
+    /// except for the well-known types with a JSON form of their own. `name` is the field
+    /// holding the message, empty for the request message itself.
+    fn encode_message_json(
+        &self,
+        type_name: &str,
+        value: &Value,
+        name: &str,
+        out: &mut Vec<u8>,
+    ) -> Result<(), anyhow::Error> {
+        if let Some((_, kind)) = WRAPPERS.iter().find(|(wrapper, _)| *wrapper == type_name) {
+            return self.encode_single(&scalar_field(1, *kind, name), value, out);
+        }
+        let what = if name.is_empty() {
+            String::from("The request message")
+        } else {
+            format!("Field '{name}'")
+        };
+        match type_name {
+            TIMESTAMP => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("{what} must be an RFC 3339 timestamp string"))?;
+                let (seconds, nanos) = parse_timestamp(text)
+                    .ok_or_else(|| anyhow!("{what} is not an RFC 3339 timestamp: {text}"))?;
+                write_seconds_nanos(out, seconds, nanos);
+            }
+            DURATION => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("{what} must be a duration string such as \"1.5s\""))?;
+                let (seconds, nanos) = parse_duration(text)
+                    .ok_or_else(|| anyhow!("{what} is not a duration such as \"1.5s\": {text}"))?;
+                write_seconds_nanos(out, seconds, nanos);
+            }
+            STRUCT => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| anyhow!("{what} must be a JSON object"))?;
+                for (key, value) in object {
+                    let mut entry = vec![];
+                    write_tag(&mut entry, 1, WIRE_LEN);
+                    write_bytes(&mut entry, key.as_bytes());
+                    let mut nested = vec![];
+                    self.encode_message_json(VALUE, value, name, &mut nested)?;
+                    write_tag(&mut entry, 2, WIRE_LEN);
+                    write_bytes(&mut entry, &nested);
+                    write_tag(out, 1, WIRE_LEN);
+                    write_bytes(out, &entry);
+                }
+            }
+            VALUE => match value {
+                Value::Null => {
+                    write_tag(out, 1, WIRE_VARINT);
+                    write_varint(out, 0);
+                }
+                Value::Number(n) => {
+                    write_tag(out, 2, WIRE_FIXED64);
+                    out.extend_from_slice(&n.as_f64().unwrap_or_default().to_le_bytes());
+                }
+                Value::String(s) => {
+                    write_tag(out, 3, WIRE_LEN);
+                    write_bytes(out, s.as_bytes());
+                }
+                Value::Bool(b) => {
+                    write_tag(out, 4, WIRE_VARINT);
+                    write_varint(out, *b as u64);
+                }
+                Value::Object(_) => {
+                    let mut nested = vec![];
+                    self.encode_message_json(STRUCT, value, name, &mut nested)?;
+                    write_tag(out, 5, WIRE_LEN);
+                    write_bytes(out, &nested);
+                }
+                Value::Array(_) => {
+                    let mut nested = vec![];
+                    self.encode_message_json(LIST_VALUE, value, name, &mut nested)?;
+                    write_tag(out, 6, WIRE_LEN);
+                    write_bytes(out, &nested);
+                }
+            },
+            LIST_VALUE => {
+                let items = value
+                    .as_array()
+                    .ok_or_else(|| anyhow!("{what} must be a JSON array"))?;
+                for item in items {
+                    let mut nested = vec![];
+                    self.encode_message_json(VALUE, item, name, &mut nested)?;
+                    write_tag(out, 1, WIRE_LEN);
+                    write_bytes(out, &nested);
+                }
+            }
+            FIELD_MASK => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("{what} must be a comma separated string of paths"))?;
+                for path in text.split(',').filter(|path| !path.is_empty()) {
+                    write_tag(out, 1, WIRE_LEN);
+                    write_bytes(out, to_snake_case(path).as_bytes());
+                }
+            }
+            EMPTY if value.as_object().is_some_and(Map::is_empty) => {}
+            EMPTY => return Err(anyhow!("{what} must be an empty JSON object")),
+            ANY => return Err(anyhow!("{what}: google.protobuf.Any is not supported")),
+            _ => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| anyhow!("{what} must be a JSON object"))?;
+                self.encode_message(type_name, object, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A stand-in descriptor for a scalar field of a well-known type.
+fn scalar_field(number: u32, kind: i32, name: &str) -> FieldDescriptor {
+    FieldDescriptor {
+        name: name.to_string(),
+        number,
+        kind,
+        ..Default::default()
+    }
+}
+
+/// The seconds and nanos fields shared by Timestamp and Duration, zeros left out.
+fn write_seconds_nanos(out: &mut Vec<u8>, seconds: i64, nanos: i32) {
+    if seconds != 0 {
+        write_tag(out, 1, WIRE_VARINT);
+        write_varint(out, seconds as u64);
+    }
+    if nanos != 0 {
+        write_tag(out, 2, WIRE_VARINT);
+        write_varint(out, nanos as i64 as u64);
+    }
+}
+
+/// Seconds and nanos since the epoch of an RFC 3339 time such as 2024-01-02T03:04:05.5Z,
+/// with a Z or a numeric offset.
+fn parse_timestamp(text: &str) -> Option<(i64, i32)> {
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = text.get(range)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    let separators = [(4, b'-'), (7, b'-'), (10, b'T'), (13, b':'), (16, b':')];
+    if separators.iter().any(|(index, c)| {
+        !text
+            .as_bytes()
+            .get(*index)
+            .is_some_and(|b| b.eq_ignore_ascii_case(c))
+    }) {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    let month_days = calendar::days_in_month(year, month);
+    if !(1..=month_days).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    // Leap seconds are not representable, as in the JSON mapping.
+    if second > 59 {
+        return None;
+    }
+    let mut rest = text.get(19..)?;
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 || digits > 9 {
+            return None;
+        }
+        nanos = format!("{:0<9}", &fraction[..digits]).parse().ok()?;
+        rest = &fraction[digits..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = rest[1..].split_once(':')?;
+            if hours.len() != 2 || minutes.len() != 2 {
+                return None;
+            }
+            sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60)
+        }
+    };
+    let days = calendar::days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    Some((seconds, nanos))
+}
+
+/// Seconds and nanos of a duration such as 1.5s or -0.25s, both with the sign.
+fn parse_duration(text: &str) -> Option<(i64, i32)> {
+    let text = text.strip_suffix('s')?;
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if whole.is_empty() || fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let seconds: i64 = whole.parse().ok().filter(|seconds: &i64| *seconds >= 0)?;
+    let nanos: i32 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{fraction:0<9}").parse().ok()?
+    };
+    // The JSON mapping allows about 10,000 years either way.
+    if seconds > 315_576_000_000 {
+        return None;
+    }
+    let sign = if negative { -1 } else { 1 };
+    Some((sign * seconds, sign as i32 * nanos))
+}
+
+/// A FieldMask path from its JSON lowerCamelCase form, e.g., user.displayName.
+fn to_snake_case(path: &str) -> String {
+    let mut snake = String::new();
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn decode_field(bytes: &[u8]) -> Result<FieldDescriptor, anyhow::Error> {
+    let mut field = FieldDescriptor::default();
+    for (number, value) in fields(bytes)? {
+        match number {
+            1 => field.name = value.string()?,
+            3 => field.number = value.varint()? as u32,
+            4 => field.repeated = value.varint()? as i32 == LABEL_REPEATED,
+            5 => field.kind = value.varint()? as i32,
+            6 => field.type_name = value.string()?,
+            10 => field.json_name = value.string()?,
+            _ => {}
+        }
+    }
+    Ok(field)
+}
+
+/// The wire type of a scalar that can be packed, None for strings, bytes and messages.
+fn packed_wire_type(kind: i32) -> Option<u32> {
+    match kind {
+        TYPE_DOUBLE | TYPE_FIXED64 | TYPE_SFIXED64 => Some(WIRE_FIXED64),
+        TYPE_FLOAT | TYPE_FIXED32 | TYPE_SFIXED32 => Some(WIRE_FIXED32),
+        TYPE_STRING | TYPE_BYTES | TYPE_MESSAGE => None,
+        _ => Some(WIRE_VARINT),
+    }
+}
+
+// The JSON mapping writes 64-bit integers as strings, so numbers are accepted either way.
+fn json_i64(value: &Value, name: &str) -> Result<i64, anyhow::Error> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("Field '{name}' must be an integer"))
+}
+
+fn json_i32(value: &Value, name: &str) -> Result<i32, anyhow::Error> {
+    i32::try_from(json_i64(value, name)?)
+        .map_err(|_| anyhow!("Field '{name}' is out of range for a 32-bit integer"))
+}
+
+fn json_u32(value: &Value, name: &str) -> Result<u32, anyhow::Error> {
+    u32::try_from(json_u64(value, name)?)
+        .map_err(|_| anyhow!("Field '{name}' is out of range for a 32-bit unsigned integer"))
+}
+
+fn json_u64(value: &Value, name: &str) -> Result<u64, anyhow::Error> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("Field '{name}' must be an unsigned integer"))
+}
+
+fn json_f64(value: &Value, name: &str) -> Result<f64, anyhow::Error> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("Field '{name}' must be a number"))
+}
+
+fn json_f32(value: &Value, name: &str) -> Result<f32, anyhow::Error> {
+    let n = json_f64(value, name)?;
+    if n.is_finite() && n.abs() > f32::MAX as f64 {
+        return Err(anyhow!("Field '{name}' is out of range for a float"));
+    }
+    Ok(n as f32)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_tag(out: &mut Vec<u8>, number: u32, wire_type: u32) {
+    write_varint(out, ((number << 3) | wire_type) as u64);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+enum WireValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+impl<'a> WireValue<'a> {
+    fn varint(&self) -> Result<u64, anyhow::Error> {
+        match self {
+            WireValue::Varint(v) => Ok(*v),
+            _ => Err(anyhow!("Malformed descriptor set: expected a varint")),
+        }
+    }
+
+    fn bytes(&self) -> Result<&'a [u8], anyhow::Error> {
+        match self {
+            WireValue::Bytes(b) => Ok(b),
+            _ => Err(anyhow!("Malformed descriptor set: expected bytes")),
+        }
+    }
+
+    fn string(&self) -> Result<String, anyhow::Error> {
+        Ok(String::from_utf8(self.bytes()?.to_vec())?)
+    }
+}
+
+/// Splits a serialized message into (field number, value) pairs.
+fn fields(mut buf: &[u8]) -> Result<Vec<(u32, WireValue<'_>)>, anyhow::Error> {
+    let mut out = vec![];
+    while !buf.is_empty() {
+        let key = read_varint(&mut buf)?;
+        let number = (key >> 3) as u32;
+        let value = match (key & 7) as u32 {
+            WIRE_VARINT => WireValue::Varint(read_varint(&mut buf)?),
+            WIRE_FIXED64 => {
+                buf = buf.get(8..).ok_or_else(truncated)?;
+                WireValue::Fixed
+            }
+            WIRE_LEN => {
+                let len = read_varint(&mut buf)? as usize;
+                let bytes = buf.get(..len).ok_or_else(truncated)?;
+                buf = &buf[len..];
+                WireValue::Bytes(bytes)
+            }
+            WIRE_FIXED32 => {
+                buf = buf.get(4..).ok_or_else(truncated)?;
+                WireValue::Fixed
+            }
+            other => return Err(anyhow!("Malformed descriptor set: wire type {other}")),
+        };
+        out.push((number, value));
+    }
+    Ok(out)
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u64, anyhow::Error> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first().ok_or_else(truncated)?;
+        *buf = rest;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(anyhow!("Malformed descriptor set: varint is too long"))
+}
+
+fn truncated() -> anyhow::Error {
+    anyhow!("Malformed descriptor set: truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn field(name: &str, number: u32, kind: i32, repeated: bool, type_name: &str) -> Vec<u8> {
+        let mut out = vec![];
+        write_tag(&mut out, 1, WIRE_LEN);
+        write_bytes(&mut out, name.as_bytes());
+        write_tag(&mut out, 3, WIRE_VARINT);
+        write_varint(&mut out, number as u64);
+        write_tag(&mut out, 4, WIRE_VARINT);
+        write_varint(&mut out, if repeated { LABEL_REPEATED } else { 1 } as u64);
+        write_tag(&mut out, 5, WIRE_VARINT);
+        write_varint(&mut out, kind as u64);
+        if !type_name.is_empty() {
+            write_tag(&mut out, 6, WIRE_LEN);
+            write_bytes(&mut out, type_name.as_bytes());
+        }
+        out
+    }
+
+    fn message(name: &str, fields: &[Vec<u8>], nested: &[Vec<u8>], map_entry: bool) -> Vec<u8> {
+        let mut out = vec![];
+        write_tag(&mut out, 1, WIRE_LEN);
+        write_bytes(&mut out, name.as_bytes());
+        for field in fields {
+            write_tag(&mut out, 2, WIRE_LEN);
+            write_bytes(&mut out, field);
+        }
+        for nested in nested {
+            write_tag(&mut out, 3, WIRE_LEN);
+            write_bytes(&mut out, nested);
+        }
+        if map_entry {
+            write_tag(&mut out, 7, WIRE_LEN);
+            write_bytes(&mut out, &[7 << 3, 1]);
+        }
+        out
+    }
+
+    /// A descriptor set of package `test` with the message Req and the service Svc/Call.
+    fn pool(tags_entry_fields: &[Vec<u8>]) -> DescriptorPool {
+        let tags_entry = message("TagsEntry", tags_entry_fields, &[], true);
+        let request = message(
+            "Req",
+            &[
+                field("id", 1, TYPE_INT32, false, ""),
+                field("name", 2, TYPE_STRING, false, ""),
+                field("nums", 3, TYPE_INT64, true, ""),
+                field("tags", 4, TYPE_MESSAGE, true, ".test.Req.TagsEntry"),
+                field("at", 5, TYPE_MESSAGE, false, TIMESTAMP),
+                field(
+                    "wrapped",
+                    6,
+                    TYPE_MESSAGE,
+                    false,
+                    ".google.protobuf.Int32Value",
+                ),
+                field("data", 7, TYPE_MESSAGE, false, STRUCT),
+                field("other", 8, TYPE_MESSAGE, false, ANY),
+                field("count", 9, TYPE_UINT32, false, ""),
+                field("delta", 10, TYPE_SINT32, false, ""),
+            ],
+            &[tags_entry],
+            false,
+        );
+        let mut method = vec![];
+        write_tag(&mut method, 1, WIRE_LEN);
+        write_bytes(&mut method, b"Call");
+        write_tag(&mut method, 2, WIRE_LEN);
+        write_bytes(&mut method, b".test.Req");
+        let mut service = vec![];
+        write_tag(&mut service, 1, WIRE_LEN);
+        write_bytes(&mut service, b"Svc");
+        write_tag(&mut service, 2, WIRE_LEN);
+        write_bytes(&mut service, &method);
+        let mut file = vec![];
+        write_tag(&mut file, 2, WIRE_LEN);
+        write_bytes(&mut file, b"test");
+        write_tag(&mut file, 4, WIRE_LEN);
+        write_bytes(&mut file, &request);
+        write_tag(&mut file, 6, WIRE_LEN);
+        write_bytes(&mut file, &service);
+        let mut set = vec![];
+        write_tag(&mut set, 1, WIRE_LEN);
+        write_bytes(&mut set, &file);
+        DescriptorPool::decode(&set).unwrap()
+    }
+
+    fn valid_pool() -> DescriptorPool {
+        pool(&[
+            field("key", 1, TYPE_STRING, false, ""),
+            field("value", 2, TYPE_INT32, false, ""),
+        ])
+    }
+
+    fn encode(json: Value) -> Result<Vec<u8>, anyhow::Error> {
+        valid_pool().encode_json(".test.Req", &json)
+    }
+
+    #[test]
+    fn finds_methods() {
+        let pool = valid_pool();
+        assert_eq!(
+            pool.method("test.Svc/Call").unwrap().input_type,
+            ".test.Req"
+        );
+        assert!(pool.method("test.Svc/Missing").is_err());
+    }
+
+    #[test]
+    fn encodes_scalars() {
+        assert_eq!(encode(json!({"id": 150})).unwrap(), [0x08, 0x96, 0x01]);
+        assert_eq!(
+            encode(json!({"name": "hi"})).unwrap(),
+            [0x12, 0x02, b'h', b'i']
+        );
+        // Negative int32 values take ten bytes, like int64.
+        assert_eq!(encode(json!({"id": -1})).unwrap().len(), 11);
+        assert_eq!(encode(json!({"delta": -1})).unwrap(), [0x50, 0x01]);
+        assert_eq!(
+            encode(json!({"id": "7", "name": null})).unwrap(),
+            [0x08, 0x07]
+        );
+    }
+
+    #[test]
+    fn packs_repeated_scalars() {
+        assert_eq!(
+            encode(json!({"nums": [1, 2, "300"]})).unwrap(),
+            [0x1a, 0x04, 0x01, 0x02, 0xac, 0x02]
+        );
+    }
+
+    #[test]
+    fn encodes_maps() {
+        assert_eq!(
+            encode(json!({"tags": {"a": 1}})).unwrap(),
+            [0x22, 0x05, 0x0a, 0x01, b'a', 0x10, 0x01]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_map_entries() {
+        let pool = pool(&[field("key", 1, TYPE_STRING, false, "")]);
+        let error = pool
+            .encode_json(".test.Req", &json!({"tags": {"a": 1}}))
+            .unwrap_err();
+        assert!(error.to_string().contains(".test.Req.TagsEntry"), "{error}");
+    }
+
+    #[test]
+    fn checks_32_bit_ranges() {
+        assert!(encode(json!({"id": 3_000_000_000u64})).is_err());
+        assert!(encode(json!({"id": -3_000_000_000i64})).is_err());
+        assert!(encode(json!({"count": 5_000_000_000u64})).is_err());
+        assert!(encode(json!({"count": -1})).is_err());
+        assert_eq!(encode(json!({"count": 4_294_967_295u64})).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_wrong_types() {
+        assert!(encode(json!({"missing": 1})).is_err());
+        assert!(encode(json!({"name": 1})).is_err());
+        assert!(encode(json!([1])).is_err());
+    }
+
+    #[test]
+    fn encodes_well_known_types() {
+        assert_eq!(
+            encode(json!({"at": "1970-01-01T00:00:01Z"})).unwrap(),
+            [0x2a, 0x02, 0x08, 0x01]
+        );
+        assert_eq!(
+            encode(json!({"wrapped": 5})).unwrap(),
+            [0x32, 0x02, 0x08, 0x05]
+        );
+        assert_eq!(
+            encode(json!({"data": {"k": null}})).unwrap(),
+            [0x3a, 0x09, 0x0a, 0x07, 0x0a, 0x01, b'k', 0x12, 0x02, 0x08, 0x00]
+        );
+        assert!(encode(json!({"at": "yesterday"})).is_err());
+        assert!(encode(json!({"at": {"seconds": 1}})).is_err());
+        let error = encode(json!({"other": {}})).unwrap_err();
+        assert!(error.to_string().contains("Any"), "{error}");
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(
+            parse_timestamp("1970-01-01T00:00:01.5Z"),
+            Some((1, 500_000_000))
+        );
+        assert_eq!(parse_timestamp("1970-01-01T01:00:00+01:00"), Some((0, 0)));
+        assert_eq!(
+            parse_timestamp("2024-02-29T12:00:00-02:30"),
+            Some((1_709_217_000, 0))
+        );
+        assert_eq!(
+            parse_timestamp("1969-12-31T23:59:59.25Z"),
+            Some((-1, 250_000_000))
+        );
+        assert_eq!(
+            parse_timestamp("2000-03-01t00:00:00z"),
+            Some((951_868_800, 0))
+        );
+        assert_eq!(parse_timestamp("2024-13-01T00:00:00Z"), None);
+        // Days past the end of the month are not rolled over.
+        assert_eq!(parse_timestamp("2023-02-31T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2023-02-29T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("1900-02-29T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2023-04-31T00:00:00Z"), None);
+        assert_eq!(
+            parse_timestamp("2000-02-29T00:00:00Z"),
+            Some((951_782_400, 0))
+        );
+        assert_eq!(parse_timestamp("2024-02-29 12:00"), None);
+        assert_eq!(parse_timestamp("2024-02-29T12:00:00é"), None);
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("1.5s"), Some((1, 500_000_000)));
+        assert_eq!(parse_duration("-0.25s"), Some((0, -250_000_000)));
+        assert_eq!(parse_duration("3s"), Some((3, 0)));
+        assert_eq!(parse_duration("0.000000001s"), Some((0, 1)));
+        assert_eq!(parse_duration("1"), None);
+        assert_eq!(parse_duration("1.0000000001s"), None);
+        assert_eq!(parse_duration("1.5ms"), None);
+        assert_eq!(parse_duration("--1s"), None);
+    }
+
+    #[test]
+    fn converts_field_masks() {
+        assert_eq!(to_snake_case("user.displayName"), "user.display_name");
+    }
+
+    #[test]
+    fn rejects_truncated_descriptor_sets() {
+        assert!(DescriptorPool::decode(&[0x0a, 0x05, 0x01]).is_err());
+        assert!(DescriptorPool::decode(&[0x80]).is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::header::HeaderValue;
+use hyper::Uri;
+use percent_encoding::percent_decode_str;
+use std::env;
+use std::net::IpAddr;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use url::Url;
+
+const DEFAULT_PROXY_PORT: u16 = 1080;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyKind {
+    Http,
+    // socks5 resolves the target locally, socks5h lets the proxy resolve it
+    Socks5 { remote_dns: bool },
+}
+
+#[derive(Clone, Debug)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+}
+
+/// Which proxy each target goes through, from --proxy or the proxy environment variables.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyConfig {
+    http: Option<Proxy>,
+    https: Option<Proxy>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Uses `proxy` for every target when given, otherwise HTTP_PROXY, HTTPS_PROXY and ALL_PROXY.
+    /// NO_PROXY is honored either way.
+    pub fn new(proxy: Option<&str>) -> Result<Self, anyhow::Error> {
+        let no_proxy = env_var("NO_PROXY")
+            .map(|v| {
+                v.split(',')
+                    .map(|entry| entry.trim().to_ascii_lowercase())
+                    .filter(|entry| !entry.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(proxy) = proxy {
+            let proxy = Proxy::parse(proxy)?;
+            return Ok(ProxyConfig {
+                http: Some(proxy.clone()),
+                https: Some(proxy),
+                no_proxy,
+            });
+        }
+        let all = env_var("ALL_PROXY");
+        let parse_env = |name: &str| -> Result<Option<Proxy>, anyhow::Error> {
+            env_var(name)
+                .or_else(|| all.clone())
+                .map(|v| Proxy::parse(&v).map_err(|e| anyhow!("Invalid {name}: {e}")))
+                .transpose()
+        };
+        Ok(ProxyConfig {
+            http: parse_env("HTTP_PROXY")?,
+            https: parse_env("HTTPS_PROXY")?,
+            no_proxy,
+        })
+    }
+
+    pub fn for_uri(&self, uri: &Uri) -> Option<&Proxy> {
+        let proxy = match uri.scheme_str() {
+            Some("https") => self.https.as_ref(),
+            _ => self.http.as_ref(),
+        }?;
+        let host = uri.host()?.trim_matches(['[', ']']).to_ascii_lowercase();
+        let bypass = self.no_proxy.iter().any(|entry| {
+            let entry = entry.trim_start_matches('.');
+            entry == "*" || host == entry || host.ends_with(&format!(".{entry}"))
+        });
+        (!bypass).then_some(proxy)
+    }
+
+    /// The Proxy-Authorization header for plain http requests sent through an http proxy.
+    /// https targets authenticate on the CONNECT request instead.
+    pub fn authorization_for(&self, uri: &Uri) -> Option<HeaderValue> {
+        if uri.scheme_str() == Some("https") {
+            return None;
+        }
+        match self.for_uri(uri)? {
+            proxy @ Proxy {
+                kind: ProxyKind::Http,
+                ..
+            } => proxy.basic_authorization(),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the authority of `url` names a port.
+fn has_explicit_port(url: &str) -> bool {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    match host_port.rsplit_once(']') {
+        Some((_, after_ipv6)) => after_ipv6.starts_with(':'),
+        None => host_port.contains(':'),
+    }
+}
+
+impl Proxy {
+    fn parse(value: &str) -> Result<Self, anyhow::Error> {
+        // curl accepts a bare host:port as an http proxy
+        let value = if value.contains("://") {
+            value.to_string()
+        } else {
+            format!("http://{value}")
+        };
+        let url = Url::parse(&value).map_err(|e| anyhow!("Invalid proxy URL '{value}': {e}"))?;
+        let kind = match url.scheme() {
+            "http" => ProxyKind::Http,
+            "socks5" => ProxyKind::Socks5 { remote_dns: false },
+            "socks5h" => ProxyKind::Socks5 { remote_dns: true },
+            other => {
+                return Err(anyhow!(
+                    "Unsupported proxy scheme '{other}'. Use http, socks5 or socks5h."
+                ))
+            }
+        };
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("Proxy URL must include a host"))?
+            .trim_matches(['[', ']'])
+            .to_string();
+        let credentials = if url.username().is_empty() {
+            None
+        } else {
+            let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+            Some((
+                decode(url.username()),
+                decode(url.password().unwrap_or_default()),
+            ))
+        };
+        // Url drops a port equal to the scheme default, e.g., http://proxy:80, so only an
+        // authority without a port falls back to 1080.
+        let port = if has_explicit_port(&value) {
+            url.port_or_known_default().unwrap_or(DEFAULT_PROXY_PORT)
+        } else {
+            DEFAULT_PROXY_PORT
+        };
+        Ok(Proxy {
+            kind,
+            host,
+            port,
+            credentials,
+        })
+    }
+
+    fn basic_authorization(&self) -> Option<HeaderValue> {
+        let (user, password) = self.credentials.as_ref()?;
+        let token = STANDARD.encode(format!("{user}:{password}"));
+        HeaderValue::from_str(&format!("Basic {token}")).ok()
+    }
+
+    /// Asks the proxy to open a tunnel to `host:port` over an established connection to it.
+    pub async fn handshake(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), anyhow::Error> {
+        match self.kind {
+            ProxyKind::Http => self.http_connect(stream, host, port).await,
+            ProxyKind::Socks5 { remote_dns } => {
+                self.socks5_connect(stream, host, port, remote_dns).await
+            }
+        }
+    }
+
+    async fn http_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), anyhow::Error> {
+        let authority = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => format!("[{host}]:{port}"),
+            _ => format!("{host}:{port}"),
+        };
+        let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+        if let Some(value) = self.basic_authorization() {
+            request.push_str(&format!("Proxy-Authorization: {}\r\n", value.to_str()?));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // Read byte by byte so nothing after the header block is consumed.
+        let mut head = Vec::with_capacity(128);
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() > 8192 {
+                return Err(anyhow!("Proxy CONNECT response is too large"));
+            }
+            head.push(stream.read_u8().await?);
+        }
+        let head = String::from_utf8_lossy(&head);
+        let status_line = head.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(anyhow!("Proxy CONNECT failed: {status_line}")),
+        }
+    }
+
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+        remote_dns: bool,
+    ) -> Result<(), anyhow::Error> {
+        let method = if self.credentials.is_some() {
+            0x02
+        } else {
+            0x00
+        };
+        stream.write_all(&[0x05, 0x01, method]).await?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != 0x05 || reply[1] != method {
+            return Err(anyhow!("SOCKS5 proxy refused the authentication method"));
+        }
+        if let Some((user, password)) = &self.credentials {
+            let mut auth = vec![0x01, socks5_length("user name", user)?];
+            auth.extend_from_slice(user.as_bytes());
+            auth.push(socks5_length("password", password)?);
+            auth.extend_from_slice(password.as_bytes());
+            stream.write_all(&auth).await?;
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0x00 {
+                return Err(anyhow!("SOCKS5 proxy authentication failed"));
+            }
+        }
+
+        let address = match host.parse::<IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) if remote_dns => None,
+            Err(_) => Some(
+                tokio::net::lookup_host((host, port))
+                    .await?
+                    .next()
+                    .ok_or_else(|| anyhow!("Failed to resolve {host}"))?
+                    .ip(),
+            ),
+        };
+        let mut request = vec![0x05, 0x01, 0x00];
+        match address {
+            Some(IpAddr::V4(ip)) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            Some(IpAddr::V6(ip)) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+            None => {
+                request.push(0x03);
+                request.push(socks5_length("host name", host)?);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut head = [0u8; 4];
+        stream.read_exact(&mut head).await?;
+        if head[1] != 0x00 {
+            return Err(anyhow!(
+                "SOCKS5 proxy failed to connect, reply code {}",
+                head[1]
+            ));
+        }
+        // Skip the bound address and port the proxy reports back.
+        let address_len = match head[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => stream.read_u8().await? as usize,
+            other => return Err(anyhow!("Unknown SOCKS5 address type {other}")),
+        };
+        let mut bound = vec![0u8; address_len + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(())
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name)
+        .or_else(|_| env::var(name.to_ascii_lowercase()))
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+/// The one-byte length SOCKS5 puts before a name or password, which caps them at 255 bytes.
+fn socks5_length(what: &str, text: &str) -> Result<u8, anyhow::Error> {
+    u8::try_from(text.len()).map_err(|_| anyhow!("The SOCKS5 {what} is longer than 255 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Accepts one SOCKS5 handshake with user name and password, returns the CONNECT request.
+    async fn fake_socks5(listener: TcpListener) -> Vec<u8> {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut greeting = [0u8; 3];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [0x05, 0x01, 0x02]);
+        stream.write_all(&[0x05, 0x02]).await.unwrap();
+        let mut auth = vec![0u8; 2];
+        stream.read_exact(&mut auth).await.unwrap();
+        let mut rest = vec![0u8; auth[1] as usize + 1];
+        stream.read_exact(&mut rest).await.unwrap();
+        let mut password = vec![0u8; rest[auth[1] as usize] as usize];
+        stream.read_exact(&mut password).await.unwrap();
+        assert_eq!(&rest[..auth[1] as usize], b"user");
+        assert_eq!(password, b"p@ss");
+        stream.write_all(&[0x01, 0x00]).await.unwrap();
+        let mut request = vec![0u8; 5];
+        stream.read_exact(&mut request).await.unwrap();
+        let mut rest = vec![0u8; request[4] as usize + 2];
+        stream.read_exact(&mut rest).await.unwrap();
+        request.extend(rest);
+        stream
+            .write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 80])
+            .await
+            .unwrap();
+        request
+    }
+
+    #[test]
+    fn parses_proxy_urls() {
+        let proxy = Proxy::parse("socks5h://user:p%40ss@[::1]:9050").unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Socks5 { remote_dns: true });
+        assert_eq!(proxy.host, "::1");
+        assert_eq!(proxy.port, 9050);
+        assert_eq!(
+            proxy.credentials,
+            Some((String::from("user"), String::from("p@ss")))
+        );
+        let proxy = Proxy::parse("proxy.local").unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Http);
+        assert_eq!(proxy.port, DEFAULT_PROXY_PORT);
+        assert_eq!(Proxy::parse("http://proxy:80").unwrap().port, 80);
+        assert_eq!(Proxy::parse("proxy:80").unwrap().port, 80);
+        assert_eq!(
+            Proxy::parse("http://u:p@[::1]/").unwrap().port,
+            DEFAULT_PROXY_PORT
+        );
+        assert_eq!(Proxy::parse("http://u:p@[::1]:8080").unwrap().port, 8080);
+        assert!(Proxy::parse("ftp://proxy.local").is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_socks5_connect_with_credentials() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(fake_socks5(listener));
+        let proxy = Proxy::parse(&format!("socks5h://user:p%40ss@{addr}")).unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        proxy
+            .handshake(&mut stream, "example.com", 443)
+            .await
+            .unwrap();
+        let mut expected = vec![0x05, 0x01, 0x00, 0x03, 11];
+        expected.extend_from_slice(b"example.com");
+        expected.extend_from_slice(&443u16.to_be_bytes());
+        assert_eq!(server.await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn rejects_socks5_names_over_255_bytes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let long = "a".repeat(256);
+        let proxy = Proxy::parse(&format!("socks5h://{long}:pw@{addr}")).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream.write_all(&[0x05, 0x02]).await.unwrap();
+        });
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let error = proxy
+            .handshake(&mut stream, "example.com", 443)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("255 bytes"), "{error}");
+        server.await.unwrap();
+        assert!(socks5_length("host name", &long).is_err());
+        assert_eq!(socks5_length("host name", "example.com").unwrap(), 11);
+    }
+}
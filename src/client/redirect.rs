@@ -0,0 +1,165 @@
+use http_body_util::BodyExt;
+use http_body_util::Full;
+use http_body_util::Limited;
+use hyper::body::Bytes;
+use hyper::body::Incoming;
+use hyper::header::AUTHORIZATION;
+use hyper::header::CONTENT_LENGTH;
+use hyper::header::CONTENT_TYPE;
+use hyper::header::COOKIE;
+use hyper::header::HOST;
+use hyper::Method;
+use hyper::Request;
+use hyper::StatusCode;
+use hyper::Uri;
+use url::Url;
+
+// Redirect bodies up to this size are read, so their connection goes back to the pool.
+const MAX_DRAINED_BODY: usize = 64 * 1024;
+
+/// Builds the request for the next hop when `status` is a redirect with a usable `Location`.
+///
+/// 303 switches to GET without a body, 301 and 302 do the same for POST like browsers do,
+/// and 307 and 308 repeat the original method and body.
+pub fn next_request(
+    request: &Request<Full<Bytes>>,
+    status: StatusCode,
+    location: Option<&str>,
+) -> Result<Option<Request<Full<Bytes>>>, anyhow::Error> {
+    let rewrite_to_get = match status {
+        StatusCode::SEE_OTHER => request.method() != Method::HEAD,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => request.method() == Method::POST,
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => false,
+        _ => return Ok(None),
+    };
+    let Some(location) = location else {
+        return Ok(None);
+    };
+    let target = resolve_location(request.uri(), location)?;
+
+    let mut next = request.clone();
+    if rewrite_to_get {
+        *next.method_mut() = Method::GET;
+        *next.body_mut() = Full::new(Bytes::new());
+        next.headers_mut().remove(CONTENT_TYPE);
+        next.headers_mut().remove(CONTENT_LENGTH);
+    }
+    // Credentials set with -H are only sent to the origin they were meant for, so a switch
+    // to plain http drops them too.
+    let origin = |uri: &Uri| (uri.scheme().cloned(), uri.authority().cloned());
+    if origin(&target) != origin(request.uri()) {
+        next.headers_mut().remove(AUTHORIZATION);
+        next.headers_mut().remove(COOKIE);
+        next.headers_mut().remove(HOST);
+    }
+    *next.uri_mut() = target;
+    Ok(Some(next))
+}
+
+/// Reads the body of a redirect response to its end, so the next hop can reuse the
+/// connection instead of opening a new one. A longer body is dropped with its connection.
+pub async fn drain(body: Incoming) {
+    let _ = Limited::new(body, MAX_DRAINED_BODY).collect().await;
+}
+
+/// Resolves an absolute or relative `Location` against the URI that was requested.
+fn resolve_location(base: &Uri, location: &str) -> Result<Uri, anyhow::Error> {
+    let base = Url::parse(&base.to_string())?;
+    let target = base
+        .join(location)
+        .map_err(|e| anyhow!("Invalid redirect location '{location}': {e}"))?;
+    match target.scheme() {
+        "http" | "https" => Ok(target.as_str().parse()?),
+        other => Err(anyhow!("Unsupported redirect scheme '{other}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::Body;
+
+    fn post(uri: &str) -> Request<Full<Bytes>> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_LENGTH, "7")
+            .header(AUTHORIZATION, "Bearer t")
+            .header(COOKIE, "a=1")
+            .header(HOST, "api.example.com")
+            .body(Full::new(Bytes::from_static(b"{\"a\":1}")))
+            .unwrap()
+    }
+
+    fn next(request: &Request<Full<Bytes>>, status: u16, location: &str) -> Request<Full<Bytes>> {
+        let status = StatusCode::from_u16(status).unwrap();
+        next_request(request, status, Some(location))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn switches_see_other_to_get_without_the_body() {
+        let request = post("https://api.example.com/orders");
+        let next = next(&request, 303, "/orders/1");
+        assert_eq!(next.method(), Method::GET);
+        assert_eq!(next.body().size_hint().exact(), Some(0));
+        assert!(!next.headers().contains_key(CONTENT_TYPE));
+        assert!(!next.headers().contains_key(CONTENT_LENGTH));
+        // Same origin, so the credentials stay.
+        assert_eq!(next.headers()[AUTHORIZATION], "Bearer t");
+        assert_eq!(next.uri(), "https://api.example.com/orders/1");
+    }
+
+    #[test]
+    fn repeats_the_method_and_body_for_307_and_308() {
+        let request = post("https://api.example.com/orders");
+        for status in [307, 308] {
+            let next = next(&request, status, "https://api.example.com/v2/orders");
+            assert_eq!(next.method(), Method::POST);
+            assert_eq!(next.body().size_hint().exact(), Some(7));
+            assert_eq!(next.headers()[CONTENT_TYPE], "application/json");
+        }
+        // 301 and 302 only rewrite POST.
+        let mut put = post("https://api.example.com/orders");
+        *put.method_mut() = Method::PUT;
+        assert_eq!(next(&put, 302, "/x").method(), Method::PUT);
+        assert_eq!(next(&request, 301, "/x").method(), Method::GET);
+    }
+
+    #[test]
+    fn drops_credentials_on_cross_origin_hops() {
+        let request = post("https://api.example.com/orders");
+        for location in [
+            "https://cdn.example.com/orders",
+            "http://api.example.com/orders",
+        ] {
+            let next = next(&request, 307, location);
+            assert!(!next.headers().contains_key(AUTHORIZATION), "{location}");
+            assert!(!next.headers().contains_key(COOKIE), "{location}");
+            assert!(!next.headers().contains_key(HOST), "{location}");
+        }
+    }
+
+    #[test]
+    fn resolves_relative_locations() {
+        let request = post("http://example.com/a/b/c?q=1");
+        assert_eq!(next(&request, 302, "d").uri(), "http://example.com/a/b/d");
+        assert_eq!(
+            next(&request, 302, "../e?x=2").uri(),
+            "http://example.com/a/e?x=2"
+        );
+        assert_eq!(
+            next(&request, 302, "//other.example.com/f").uri(),
+            "http://other.example.com/f"
+        );
+        assert!(next_request(&request, StatusCode::FOUND, Some("ftp://example.com/")).is_err());
+        assert!(next_request(&request, StatusCode::FOUND, None)
+            .unwrap()
+            .is_none());
+        assert!(next_request(&request, StatusCode::NOT_MODIFIED, Some("/"))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::client::connector::ConnectError;
+use crate::vojo::cli::Cli;
+use hyper::header::RETRY_AFTER;
+use hyper::HeaderMap;
+use hyper::StatusCode;
+use rand::Rng;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// A request outcome that --retry-on can ask to retry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryCondition {
+    Status(u16),
+    // The hundreds digit, e.g., 5 for 5xx
+    StatusClass(u16),
+    Connect,
+    Timeout,
+    Error,
+}
+
+/// When and how long to wait before sending a failed request again.
+pub struct RetryPolicy {
+    pub retries: u32,
+    on: Vec<RetryCondition>,
+    backoff: Duration,
+    max_backoff: Duration,
+    honor_retry_after: bool,
+}
+
+impl RetryPolicy {
+    /// The policy set by --retries and its options, or None when retries are off.
+    pub fn for_cli(cli: &Cli) -> Option<Self> {
+        (cli.retries > 0).then(|| RetryPolicy {
+            retries: cli.retries,
+            on: cli.retry_on.clone(),
+            backoff: cli.retry_backoff,
+            max_backoff: cli.retry_max_backoff,
+            honor_retry_after: !cli.ignore_retry_after,
+        })
+    }
+
+    pub fn retries_status(&self, status: StatusCode) -> bool {
+        let status = status.as_u16();
+        self.on.iter().any(|condition| match condition {
+            RetryCondition::Status(code) => *code == status,
+            RetryCondition::StatusClass(class) => status / 100 == *class,
+            _ => false,
+        })
+    }
+
+    pub fn retries_error(&self, error: &anyhow::Error) -> bool {
+        let connect = error.chain().any(|cause| cause.is::<ConnectError>());
+        self.on.iter().any(|condition| match condition {
+            RetryCondition::Connect => connect,
+            RetryCondition::Error => true,
+            _ => false,
+        })
+    }
+
+    pub fn retries_timeout(&self) -> bool {
+        self.on
+            .iter()
+            .any(|condition| matches!(condition, RetryCondition::Timeout | RetryCondition::Error))
+    }
+
+    /// The wait before retry number `retry`, counted from 0. A Retry-After header in the
+    /// failed response wins over the backoff; both are capped by --retry-max-backoff.
+    pub fn delay(&self, retry: u32, headers: Option<&HeaderMap>) -> Duration {
+        let retry_after = headers
+            .filter(|_| self.honor_retry_after)
+            .and_then(|headers| headers.get(RETRY_AFTER)?.to_str().ok())
+            .and_then(parse_retry_after);
+        let delay = retry_after.unwrap_or_else(|| {
+            // Exponential backoff with full jitter, so retrying workers do not line up.
+            let ceiling = self
+                .backoff
+                .saturating_mul(1 << retry.min(16))
+                .min(self.max_backoff);
+            ceiling.mul_f64(rand::thread_rng().gen::<f64>())
+        });
+        delay.min(self.max_backoff)
+    }
+}
+
+/// A Retry-After value, either seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value.trim()).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn retry_on(on: Vec<RetryCondition>, honor_retry_after: bool) -> RetryPolicy {
+        RetryPolicy {
+            retries: 3,
+            on,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+            honor_retry_after,
+        }
+    }
+
+    fn retry_after(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        assert_eq!(parse_retry_after("3"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+        let later = SystemTime::now() + Duration::from_secs(120);
+        let delay = parse_retry_after(&httpdate::fmt_http_date(later)).unwrap();
+        // HTTP dates have whole seconds.
+        assert!(delay > Duration::from_secs(118) && delay <= Duration::from_secs(120));
+        // A date that already passed means no wait.
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn retry_after_wins_over_the_backoff() {
+        let policy = retry_on(vec![], true);
+        assert_eq!(
+            policy.delay(0, Some(&retry_after("1"))),
+            Duration::from_secs(1)
+        );
+        // Capped by --retry-max-backoff
+        assert_eq!(
+            policy.delay(0, Some(&retry_after("60"))),
+            Duration::from_secs(2)
+        );
+        // --ignore-retry-after falls back to the backoff.
+        let ignoring = RetryPolicy {
+            honor_retry_after: false,
+            ..policy
+        };
+        assert!(ignoring.delay(0, Some(&retry_after("1"))) <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn jitters_the_backoff_below_its_ceiling() {
+        let policy = retry_on(vec![], true);
+        for (retry, ceiling) in [
+            (0, 100),
+            (1, 200),
+            (3, 800),
+            (4, 1600),
+            (5, 2000),
+            (40, 2000),
+        ] {
+            for _ in 0..50 {
+                let delay = policy.delay(retry, None);
+                assert!(
+                    delay <= Duration::from_millis(ceiling),
+                    "{retry}: {delay:?}"
+                );
+            }
+        }
+        // Full jitter spreads the waits rather than using the ceiling every time.
+        let delays: Vec<_> = (0..50).map(|_| policy.delay(5, None)).collect();
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn matches_statuses_and_classes() {
+        let policy = retry_on(
+            vec![RetryCondition::Status(429), RetryCondition::StatusClass(5)],
+            true,
+        );
+        assert!(policy.retries_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(policy.retries_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(policy.retries_status(StatusCode::GATEWAY_TIMEOUT));
+        assert!(!policy.retries_status(StatusCode::NOT_FOUND));
+        assert!(!policy.retries_status(StatusCode::OK));
+        assert!(!policy.retries_timeout());
+
+        let policy = retry_on(vec![RetryCondition::StatusClass(4)], true);
+        assert!(policy.retries_status(StatusCode::NOT_FOUND));
+        assert!(policy.retries_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!policy.retries_status(StatusCode::BAD_GATEWAY));
+    }
+
+    #[test]
+    fn matches_errors_and_timeouts() {
+        let error = anyhow!("connection reset");
+        let policy = retry_on(vec![RetryCondition::Connect], true);
+        assert!(!policy.retries_error(&error));
+        assert!(!policy.retries_timeout());
+        let policy = retry_on(vec![RetryCondition::Error], true);
+        assert!(policy.retries_error(&error));
+        assert!(policy.retries_timeout());
+        assert!(retry_on(vec![RetryCondition::Timeout], true).retries_timeout());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::output::report::StreamResult;
+use http::HeaderMap;
+use http_body_util::BodyExt;
+use hyper::body::Incoming;
+use tokio::time::timeout_at;
+use tokio::time::Instant;
+
+/// What counts as one event in a streamed body.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Every data frame that arrives
+    Chunks,
+    /// A blank-line terminated Server-Sent Event
+    ServerSentEvents,
+    /// A length-prefixed gRPC message
+    Grpc,
+}
+
+/// Reads a streamed body frame by frame until it ends or `until` passes, timing every event.
+/// Returns the trailers too when the body ended with them.
+pub async fn read_events(
+    mut body: Incoming,
+    framing: Framing,
+    started: Instant,
+    until: Option<Instant>,
+) -> (StreamResult, Option<HeaderMap>) {
+    let mut sse_parser = SseParser::default();
+    let mut grpc_parser = GrpcParser::default();
+    let mut trailers = None;
+    let mut result = StreamResult {
+        first_event_ns: None,
+        event_gaps_ns: vec![],
+        lifetime_ns: 0,
+        events: 0,
+        bytes: 0,
+        interrupted: false,
+    };
+    let mut last_event: Option<Instant> = None;
+    loop {
+        let frame = match until {
+            Some(until) => match timeout_at(until, body.frame()).await {
+                Ok(frame) => frame,
+                // Cut by the test, not by the server
+                Err(_) => break,
+            },
+            None => body.frame().await,
+        };
+        let data = match frame {
+            Some(Ok(frame)) => match frame.into_data() {
+                Ok(data) => data,
+                Err(frame) => {
+                    trailers = frame.into_trailers().ok();
+                    continue;
+                }
+            },
+            Some(Err(_)) => {
+                result.interrupted = true;
+                break;
+            }
+            None => break,
+        };
+        let now = Instant::now();
+        result.bytes += data.len() as u64;
+        let events = match framing {
+            Framing::Chunks => u64::from(!data.is_empty()),
+            Framing::ServerSentEvents => sse_parser.feed(&data),
+            Framing::Grpc => grpc_parser.feed(&data),
+        };
+        for _ in 0..events {
+            match last_event {
+                Some(last) => result.event_gaps_ns.push((now - last).as_nanos() as u64),
+                None => result.first_event_ns = Some((now - started).as_nanos() as u64),
+            }
+            last_event = Some(now);
+        }
+        result.events += events;
+    }
+    result.lifetime_ns = started.elapsed().as_nanos() as u64;
+    (result, trailers)
+}
+
+/// Counts complete events in a `text/event-stream` body that arrives in arbitrary pieces.
+#[derive(Default)]
+struct SseParser {
+    line_len: usize,
+    comment: bool,
+    // The current event has a field line, so the next blank line dispatches it
+    has_field: bool,
+}
+
+impl SseParser {
+    fn feed(&mut self, chunk: &[u8]) -> u64 {
+        let mut events = 0;
+        for &byte in chunk {
+            match byte {
+                b'\r' => {}
+                b'\n' => {
+                    if self.line_len == 0 {
+                        if self.has_field {
+                            events += 1;
+                            self.has_field = false;
+                        }
+                    } else if !self.comment {
+                        self.has_field = true;
+                    }
+                    self.line_len = 0;
+                    self.comment = false;
+                }
+                _ => {
+                    // Lines starting with ':' are comments, often used as keep-alives.
+                    if self.line_len == 0 && byte == b':' {
+                        self.comment = true;
+                    }
+                    self.line_len += 1;
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Counts complete gRPC messages, each a 5-byte prefix with the length followed by the message.
+#[derive(Default)]
+struct GrpcParser {
+    // Bytes of the prefix seen so far, then the bytes of the message still to come
+    prefix: Vec<u8>,
+    remaining: usize,
+}
+
+impl GrpcParser {
+    fn feed(&mut self, mut chunk: &[u8]) -> u64 {
+        let mut messages = 0;
+        while !chunk.is_empty() {
+            if self.remaining > 0 {
+                let taken = self.remaining.min(chunk.len());
+                self.remaining -= taken;
+                chunk = &chunk[taken..];
+                if self.remaining == 0 {
+                    messages += 1;
+                }
+                continue;
+            }
+            let taken = (5 - self.prefix.len()).min(chunk.len());
+            self.prefix.extend_from_slice(&chunk[..taken]);
+            chunk = &chunk[taken..];
+            if self.prefix.len() == 5 {
+                let len = u32::from_be_bytes([
+                    self.prefix[1],
+                    self.prefix[2],
+                    self.prefix[3],
+                    self.prefix[4],
+                ]);
+                self.prefix.clear();
+                self.remaining = len as usize;
+                if self.remaining == 0 {
+                    messages += 1;
+                }
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_server_sent_events() {
+        let mut parser = SseParser::default();
+        assert_eq!(parser.feed(b"data: one\n\nevent: tick\ndata: two\n\n"), 2);
+        // CRLF line endings and a multi-line event
+        assert_eq!(parser.feed(b"data: a\r\ndata: b\r\n\r\n"), 1);
+        // Comments and stray blank lines are not events.
+        assert_eq!(parser.feed(b": keep-alive\n\n\n\n"), 0);
+    }
+
+    #[test]
+    fn counts_server_sent_events_split_across_chunks() {
+        let mut parser = SseParser::default();
+        let stream = b"data: one\n\n: ping\n\nid: 7\ndata: two\r\n\r\n";
+        let mut events = 0;
+        for byte in stream {
+            events += parser.feed(&[*byte]);
+        }
+        assert_eq!(events, 2);
+        // An event without its blank line is still pending.
+        assert_eq!(parser.feed(b"data: three\n"), 0);
+        assert_eq!(parser.feed(b"\n"), 1);
+    }
+
+    #[test]
+    fn counts_grpc_messages() {
+        let mut parser = GrpcParser::default();
+        let mut stream = vec![0, 0, 0, 0, 3, b'a', b'b', b'c'];
+        // An empty message is complete with its prefix.
+        stream.extend_from_slice(&[0, 0, 0, 0, 0]);
+        stream.extend_from_slice(&[0, 0, 0, 0, 2, b'x', b'y']);
+        assert_eq!(parser.feed(&stream), 3);
+
+        let mut events = 0;
+        for piece in stream.chunks(3) {
+            events += parser.feed(piece);
+        }
+        assert_eq!(events, 3);
+        assert_eq!(parser.feed(&[0, 0, 0, 0, 4, b'a']), 0);
+        assert_eq!(parser.feed(b"bcd"), 1);
+    }
+}
@@ -0,0 +1,231 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::header::CONNECTION;
+use http::header::SEC_WEBSOCKET_ACCEPT;
+use http::header::SEC_WEBSOCKET_KEY;
+use http::header::SEC_WEBSOCKET_VERSION;
+use http::header::UPGRADE;
+use http::HeaderMap;
+use http::HeaderValue;
+use http::StatusCode;
+use http::Uri;
+use hyper::body::Bytes;
+use ring::digest;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+// RFC 6455 section 1.3
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// Frames above this are treated as a protocol error rather than buffered.
+const MAX_PAYLOAD_LEN: u64 = 16 * 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Debug)]
+pub enum Message {
+    Text(String),
+    Binary(Bytes),
+    Ping(Bytes),
+    Pong,
+    Close,
+}
+
+/// Maps ws:// and wss:// to the http:// and https:// URL used for the upgrade request.
+pub fn handshake_uri(uri: &Uri) -> Result<Uri, anyhow::Error> {
+    let scheme = match uri.scheme_str() {
+        Some("wss") => "https",
+        _ => "http",
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.scheme = Some(scheme.parse()?);
+    Ok(Uri::from_parts(parts)?)
+}
+
+/// Adds the upgrade headers to `headers` and returns the key the server has to answer.
+pub fn add_handshake_headers(headers: &mut HeaderMap) -> Result<String, anyhow::Error> {
+    let mut nonce = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("Failed to generate a WebSocket key"))?;
+    let key = STANDARD.encode(nonce);
+    headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+    headers.insert(SEC_WEBSOCKET_KEY, HeaderValue::from_str(&key)?);
+    Ok(key)
+}
+
+/// Checks that the server accepted the upgrade for `key`.
+pub fn verify_handshake(
+    status: StatusCode,
+    headers: &HeaderMap,
+    key: &str,
+) -> Result<(), anyhow::Error> {
+    if status != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(anyhow!("WebSocket handshake failed with status {status}"));
+    }
+    let expected = digest::digest(
+        &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{key}{ACCEPT_GUID}").as_bytes(),
+    );
+    let accept = headers
+        .get(SEC_WEBSOCKET_ACCEPT)
+        .ok_or_else(|| anyhow!("WebSocket handshake response has no Sec-WebSocket-Accept"))?;
+    if accept.as_bytes() != STANDARD.encode(expected.as_ref()).as_bytes() {
+        return Err(anyhow!(
+            "WebSocket handshake returned a wrong Sec-WebSocket-Accept"
+        ));
+    }
+    Ok(())
+}
+
+/// Reads messages from the server side of a connection, joining fragmented ones.
+pub struct WebSocketReader<R> {
+    inner: R,
+    // Opcode and payload of a fragmented message that is not complete yet
+    fragments: Option<(u8, Vec<u8>)>,
+}
+
+impl<R: AsyncRead + Unpin> WebSocketReader<R> {
+    pub fn new(inner: R) -> Self {
+        WebSocketReader {
+            inner,
+            fragments: None,
+        }
+    }
+
+    pub async fn read_message(&mut self) -> Result<Message, anyhow::Error> {
+        loop {
+            let (fin, opcode, payload) = self.read_frame().await?;
+            match opcode {
+                OPCODE_PING => return Ok(Message::Ping(payload.into())),
+                OPCODE_PONG => return Ok(Message::Pong),
+                OPCODE_CLOSE => return Ok(Message::Close),
+                OPCODE_TEXT | OPCODE_BINARY if self.fragments.is_none() => {
+                    if fin {
+                        return message(opcode, payload);
+                    }
+                    self.fragments = Some((opcode, payload));
+                }
+                OPCODE_CONTINUATION => {
+                    let Some((first_opcode, mut buffer)) = self.fragments.take() else {
+                        return Err(anyhow!("Unexpected WebSocket continuation frame"));
+                    };
+                    buffer.extend_from_slice(&payload);
+                    if buffer.len() as u64 > MAX_PAYLOAD_LEN {
+                        return Err(anyhow!("WebSocket message is too large"));
+                    }
+                    if fin {
+                        return message(first_opcode, buffer);
+                    }
+                    self.fragments = Some((first_opcode, buffer));
+                }
+                other => return Err(anyhow!("Unexpected WebSocket opcode {other:#x}")),
+            }
+        }
+    }
+
+    async fn read_frame(&mut self) -> Result<(bool, u8, Vec<u8>), anyhow::Error> {
+        let mut header = [0u8; 2];
+        self.inner.read_exact(&mut header).await?;
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        let masked = header[1] & 0x80 != 0;
+        let len = match header[1] & 0x7F {
+            126 => self.inner.read_u16().await? as u64,
+            127 => self.inner.read_u64().await?,
+            len => len as u64,
+        };
+        if len > MAX_PAYLOAD_LEN {
+            return Err(anyhow!("WebSocket frame is too large ({len} bytes)"));
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            self.inner.read_exact(&mut mask).await?;
+        }
+        let mut payload = vec![0u8; len as usize];
+        self.inner.read_exact(&mut payload).await?;
+        if masked {
+            apply_mask(&mut payload, mask);
+        }
+        Ok((fin, opcode, payload))
+    }
+}
+
+fn message(opcode: u8, payload: Vec<u8>) -> Result<Message, anyhow::Error> {
+    if opcode == OPCODE_TEXT {
+        Ok(Message::Text(String::from_utf8(payload)?))
+    } else {
+        Ok(Message::Binary(payload.into()))
+    }
+}
+
+/// Writes masked client frames.
+pub struct WebSocketWriter<W> {
+    inner: W,
+    rng: SystemRandom,
+}
+
+impl<W: AsyncWrite + Unpin> WebSocketWriter<W> {
+    pub fn new(inner: W) -> Self {
+        WebSocketWriter {
+            inner,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    pub async fn send_text(&mut self, text: &str) -> Result<(), anyhow::Error> {
+        self.write_frame(OPCODE_TEXT, text.as_bytes()).await
+    }
+
+    pub async fn send_pong(&mut self, payload: &[u8]) -> Result<(), anyhow::Error> {
+        self.write_frame(OPCODE_PONG, payload).await
+    }
+
+    /// Sends a normal closure (1000).
+    pub async fn send_close(&mut self) -> Result<(), anyhow::Error> {
+        self.write_frame(OPCODE_CLOSE, &1000u16.to_be_bytes()).await
+    }
+
+    async fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), anyhow::Error> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode);
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len @ 126..=0xFFFF => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let mut mask = [0u8; 4];
+        self.rng
+            .fill(&mut mask)
+            .map_err(|_| anyhow!("Failed to generate a WebSocket mask"))?;
+        frame.extend_from_slice(&mask);
+        let start = frame.len();
+        frame.extend_from_slice(payload);
+        apply_mask(&mut frame[start..], mask);
+        self.inner.write_all(&frame).await?;
+        self.inner.flush().await?;
+        Ok(())
+    }
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
@@ -0,0 +1,58 @@
+use super::read_message;
+use super::send_message;
+use crate::vojo::cli::AgentArgs;
+use crate::vojo::cli::Cli;
+use crate::vojo::distributed::AgentMessage;
+use crate::vojo::distributed::ControllerMessage;
+use clap::Parser;
+use std::iter::once;
+use tokio::io::BufReader;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+
+/// Accepts controllers one at a time and runs the test plan each of them sends.
+pub async fn run(args: AgentArgs) -> Result<(), anyhow::Error> {
+    let listener = TcpListener::bind(&args.listen).await?;
+    info!("Agent listening on {}", listener.local_addr()?);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        info!("Controller {peer} connected");
+        if let Err(e) = handle_controller(stream).await {
+            warn!("Session with controller {peer} failed: {e}");
+        }
+    }
+}
+
+async fn handle_controller(stream: TcpStream) -> Result<(), anyhow::Error> {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let ControllerMessage::Plan { args } = read_message(&mut reader).await? else {
+        return Err(anyhow!("Expected a test plan from the controller"));
+    };
+    let cli = match Cli::try_parse_from(once(String::from("kt")).chain(args)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            let message = e.to_string();
+            send_message(&mut write_half, &AgentMessage::Error { message }).await?;
+            return Ok(());
+        }
+    };
+    send_message(&mut write_half, &AgentMessage::Ready).await?;
+
+    let ControllerMessage::Start = read_message(&mut reader).await? else {
+        return Err(anyhow!("Expected the start signal from the controller"));
+    };
+    info!("Running test against {}", cli.target());
+    let message = match crate::run_benchmark(cli).await {
+        Ok((shared_list, actual_duration)) => AgentMessage::Result {
+            actual_duration,
+            aggregate: Box::new(shared_list.lock().await.totals.clone()),
+        },
+        Err(e) => AgentMessage::Error {
+            message: e.to_string(),
+        },
+    };
+    send_message(&mut write_half, &message).await?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use super::read_message;
+use super::send_message;
+use crate::output::report::format_duration;
+use crate::output::report::StatisticAggregate;
+use crate::output::report::StatusKind;
+use crate::vojo::cli::Cli;
+use crate::vojo::cli::ControllerArgs;
+use crate::vojo::cli::DEFAULT_AGENT_PORT;
+use crate::vojo::distributed::AgentMessage;
+use crate::vojo::distributed::ControllerMessage;
+use clap::Parser;
+use prettytable::Table;
+use std::iter::once;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::BufReader;
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+
+struct AgentSession {
+    address: String,
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+/// Sends the plan to every agent, starts them together and merges what they report.
+pub async fn run(args: ControllerArgs) -> Result<(), anyhow::Error> {
+    let cli = Cli::try_parse_from(once(String::from("kt")).chain(args.plan.clone()))?;
+
+    let mut sessions = vec![];
+    for agent in &args.agents {
+        let address = agent_address(agent);
+        let stream = TcpStream::connect(&address)
+            .await
+            .map_err(|e| anyhow!("Failed to connect to agent {address}: {e}"))?;
+        let (read_half, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(read_half);
+        let plan = ControllerMessage::Plan {
+            args: args.plan.clone(),
+        };
+        send_message(&mut writer, &plan).await?;
+        match read_message(&mut reader).await? {
+            AgentMessage::Ready => {}
+            AgentMessage::Error { message } => {
+                return Err(anyhow!("Agent {address} rejected the plan: {message}"))
+            }
+            other => return Err(anyhow!("Unexpected reply from agent {address}: {other:?}")),
+        }
+        sessions.push(AgentSession {
+            address,
+            reader,
+            writer,
+        });
+    }
+
+    for session in sessions.iter_mut() {
+        send_message(&mut session.writer, &ControllerMessage::Start).await?;
+    }
+    info!("Started {} agents against {}", sessions.len(), cli.target());
+
+    let mut task_list = JoinSet::new();
+    for (index, mut session) in sessions.into_iter().enumerate() {
+        task_list.spawn(async move {
+            let reply = read_message::<AgentMessage>(&mut session.reader).await;
+            (index, session.address, reply)
+        });
+    }
+    let mut replies = vec![];
+    while let Some(r) = task_list.join_next().await {
+        replies.push(r?);
+    }
+    replies.sort_by_key(|(index, _, _)| *index);
+
+    let mut table = Table::new();
+    table.set_titles(row![
+        "Agent",
+        "Requests",
+        "Successful",
+        "Requests/sec",
+        "Average",
+        "P50",
+        "P99",
+        "Errors"
+    ]);
+    let mut combined = StatisticAggregate::default();
+    let mut combined_duration = Duration::default();
+    let mut reporting_agents = 0;
+    for (_, address, reply) in replies {
+        match reply {
+            Ok(AgentMessage::Result {
+                actual_duration,
+                aggregate,
+            }) => {
+                let summary =
+                    aggregate.summarize(cli.target(), cli.concurrency as u64, actual_duration);
+                table.add_row(row![
+                    address,
+                    summary.total_requests,
+                    summary.successful_requests,
+                    format!("{:.2}", summary.requests_per_sec),
+                    format_duration(summary.average_latency),
+                    format_duration(summary.p50),
+                    format_duration(summary.p99),
+                    summary.total_requests - summary.successful_requests
+                ]);
+                combined.merge(&aggregate);
+                combined_duration = combined_duration.max(actual_duration);
+                reporting_agents += 1;
+            }
+            Ok(AgentMessage::Error { message }) => {
+                table.add_row(row![address, H7 -> format!("failed: {message}")]);
+            }
+            Ok(other) => {
+                table.add_row(row![address, H7 -> format!("unexpected reply: {other:?}")]);
+            }
+            Err(e) => {
+                table.add_row(row![address, H7 -> format!("failed: {e}")]);
+            }
+        }
+    }
+
+    println!("[Per-Agent Results]");
+    table.printstd();
+    println!();
+    if combined.total_requests == 0 {
+        println!("No responses were recorded.");
+    } else {
+        let mut summary = combined.summarize(
+            cli.target(),
+            cli.concurrency as u64 * reporting_agents,
+            combined_duration,
+        );
+        summary.status_kind = StatusKind::of(&cli);
+        summary.paced = cli.think_time.is_some() || cli.pacing.is_some();
+        println!("{summary}");
+    }
+    Ok(())
+}
+
+/// Appends the default agent port unless the address already carries one.
+fn agent_address(agent: &str) -> String {
+    if agent.parse::<SocketAddr>().is_ok() {
+        return agent.to_string();
+    }
+    match agent.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => {
+            agent.to_string()
+        }
+        _ => format!("{agent}:{DEFAULT_AGENT_PORT}"),
+    }
+}
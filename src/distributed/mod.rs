@@ -0,0 +1,33 @@
+pub mod agent;
+pub mod controller;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::tcp::OwnedReadHalf;
+
+async fn send_message<W, T>(writer: &mut W, message: &T) -> Result<(), anyhow::Error>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_message<T>(reader: &mut BufReader<OwnedReadHalf>) -> Result<T, anyhow::Error>
+where
+    T: DeserializeOwned,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Err(anyhow!("Connection closed by peer"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
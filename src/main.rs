@@ -1,13 +1,24 @@
 use hyper::body::Incoming;
-use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::client::legacy::Client;
+use metrics::push::PushFormat;
+use metrics::push::PushSink;
+use mode::socket::Protocol;
+use output::report::Labels;
 use output::report::ResponseStatistic;
 use output::report::StatisticList;
 use std::sync::atomic::AtomicI64;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::Mutex;
+mod calendar;
+mod client;
+mod distributed;
+mod metrics;
+mod mode;
 mod output;
+mod scenario;
 mod vojo;
 #[macro_use]
 extern crate anyhow;
@@ -17,26 +28,65 @@ extern crate tracing;
 #[macro_use]
 extern crate prettytable;
 use clap::Parser;
+use http_body_util::BodyExt;
 use http_body_util::Full;
 use hyper::body::Bytes;
 use hyper::header::HeaderValue;
+use hyper::header::ACCEPT_ENCODING;
+use hyper::header::CONTENT_ENCODING;
 use hyper::header::CONTENT_LENGTH;
+use hyper::header::LOCATION;
+use hyper::header::PROXY_AUTHORIZATION;
 use hyper::Response;
+use hyper::StatusCode;
 use hyper_rustls::HttpsConnector;
-use rustls::crypto::ring::default_provider;
-use rustls::crypto::ring::DEFAULT_CIPHER_SUITES;
-use rustls::crypto::CryptoProvider;
-use rustls::ClientConfig;
-use rustls::RootCertStore;
 
+use crate::vojo::cli::App;
 use crate::vojo::cli::Cli;
+use crate::vojo::cli::Command;
+use crate::vojo::cli::CookieJarMode;
+use crate::vojo::cli::ExportArgs;
+use crate::vojo::cli::FromCurlArgs;
+use crate::vojo::cli::HarArgs;
+use crate::vojo::cli::JourneyArgs;
+use crate::vojo::cli::ReplayArgs;
+use crate::vojo::cli::SocketArgs;
+use client::compression;
+use client::connector::ConnectError;
+use client::connector::KtConnector;
+use client::connector::LABEL_REMOTE_IP;
+use client::connector::LABEL_SOURCE_ADDRESS;
+use client::cookie::CookieJar;
+use client::graphql;
+use client::grpc;
+use client::http3::Http3Client;
+use client::multipart::Form;
+use client::proxy::ProxyConfig;
+use client::redirect;
+use client::retry::RetryPolicy;
+use client::stream;
+use client::stream::Framing;
 use hyper::header::HeaderName;
 use hyper::header::CONTENT_TYPE;
 use hyper::HeaderMap;
 use hyper::Request;
+use hyper_util::client::legacy::connect::HttpInfo;
+use scenario::access_log;
+use scenario::har;
+use scenario::har::HostFilter;
+use scenario::journey;
+use scenario::journey::Journey;
+use scenario::Scenario;
+use scenario::SendAt;
+use scenario::ThinkTime;
+use scenario::ThinkTimeDistribution;
+use std::collections::HashMap;
+use std::iter::once;
 use std::str::FromStr;
 use tokio::sync::broadcast;
+use tokio::sync::oneshot;
 use tokio::task::JoinSet;
+use tokio::time::sleep_until;
 use tokio::time::timeout;
 use tokio::time::Instant;
 use tokio::time::{sleep, Duration};
@@ -55,48 +105,182 @@ async fn main() -> Result<(), anyhow::Error> {
         .with(console_layer)
         .with(tracing_subscriber::filter::LevelFilter::TRACE)
         .try_init();
-    let cli: Cli = Cli::parse();
+    let app: App = App::parse();
 
-    if let Err(e) = do_request(cli).await {
+    let result = match (app.command, app.cli) {
+        (Some(Command::Agent(args)), _) => distributed::agent::run(args).await,
+        (Some(Command::Controller(args)), _) => distributed::controller::run(args).await,
+        (Some(Command::Tcp(args)), _) => run_socket(args, Protocol::Tcp).await,
+        (Some(Command::Udp(args)), _) => run_socket(args, Protocol::Udp).await,
+        (Some(Command::Har(args)), _) => run_har(args).await,
+        (Some(Command::Journey(args)), _) => run_journey(args).await,
+        (Some(Command::Replay(args)), _) => run_replay(args).await,
+        (Some(Command::Export(args)), _) => run_export(args),
+        (Some(Command::FromCurl(args)), _) => run_from_curl(args),
+        (Some(Command::FindMax(args)), _) => mode::find_max::run(args).await,
+        (None, Some(cli)) => do_request(cli).await,
+        (None, None) => Err(anyhow!("A URL to benchmark is required")),
+    };
+    if let Err(e) = result {
         eprintln!("{e}");
     }
     Ok(())
 }
 async fn do_request(cli: Cli) -> Result<(), anyhow::Error> {
-    let mut root_store = RootCertStore::empty();
-    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-    let versions = rustls::DEFAULT_VERSIONS.to_vec();
-    let tls_config = ClientConfig::builder_with_provider(
-        CryptoProvider {
-            cipher_suites: DEFAULT_CIPHER_SUITES.to_vec(),
-            ..default_provider()
-        }
-        .into(),
-    )
-    .with_protocol_versions(&versions)?
-    .with_root_certificates(root_store)
-    .with_no_client_auth();
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_tls_config(tls_config)
-        .https_or_http()
-        .enable_http1()
-        .enable_http2()
-        .build();
-
-    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(https.clone());
-    let mut method = String::from("GET");
+    if cli.is_websocket() {
+        if cli.warmup.is_some() || cli.warmup_requests.is_some() {
+            return Err(anyhow!("--warmup is not supported for WebSocket URLs"));
+        }
+        if cli.think_time.is_some() || cli.pacing.is_some() {
+            return Err(anyhow!(
+                "--think-time and --pacing are not supported for WebSocket URLs"
+            ));
+        }
+        let summary = mode::websocket::run(cli).await?;
+        println!("{summary}");
+        return Ok(());
+    }
+    let (shared_list, actual_duration) = run_benchmark(cli).await?;
+    print_summary(&shared_list, actual_duration).await;
+    Ok(())
+}
+async fn run_socket(args: SocketArgs, protocol: Protocol) -> Result<(), anyhow::Error> {
+    let (shared_list, actual_duration) = mode::socket::run(args, protocol).await?;
+    print_summary(&shared_list, actual_duration).await;
+    Ok(())
+}
+async fn run_har(args: HarArgs) -> Result<(), anyhow::Error> {
+    let filter = HostFilter::new(&args.include_hosts, &args.exclude_hosts);
+    let mut scenario = har::load(&args.file, &filter, args.think_times)?;
+    // The plan has no URL of its own; the first entry stands in for it.
+    let url = scenario.requests[0].uri().to_string();
+    let cli = Cli::try_parse_from(once(String::from("kt")).chain(args.plan).chain(once(url)))?;
+    prepare_replay(&cli, &mut scenario)?;
+    let (shared_list, actual_duration) = run_scenario(cli, scenario).await?;
+    print_summary(&shared_list, actual_duration).await;
+    Ok(())
+}
+async fn run_journey(args: JourneyArgs) -> Result<(), anyhow::Error> {
+    let mut journey = journey::load(&args.file)?;
+    // The plan has no URL of its own; the first step stands in for it.
+    let url = journey.first_url()?.to_string();
+    let cli = Cli::try_parse_from(once(String::from("kt")).chain(args.plan).chain(once(url)))?;
+    if cli.stream {
+        return Err(anyhow!("--stream cannot be used with a journey"));
+    }
+    if let Some(accept_encoding) = cli.accept_encoding_header() {
+        journey.set_header(ACCEPT_ENCODING, accept_encoding.to_str()?);
+    }
+    for (key, value) in &cli.headers {
+        journey.set_header(HeaderName::from_str(key)?, value);
+    }
+    let mut scenario = journey.into_scenario();
+    prepare_replay(&cli, &mut scenario)?;
+    let (shared_list, actual_duration) = run_scenario(cli, scenario).await?;
+    print_summary(&shared_list, actual_duration).await;
+    Ok(())
+}
+async fn run_replay(args: ReplayArgs) -> Result<(), anyhow::Error> {
+    if args.speed <= 0.0 {
+        return Err(anyhow!("--speed must be greater than 0"));
+    }
+    let mut cli = Cli::try_parse_from(once(String::from("kt")).chain(args.plan))?;
+    let speed = (!args.max_speed).then_some(args.speed);
+    let mut scenario = access_log::load(&args.file, args.format, &cli.url, speed)?;
+    prepare_replay(&cli, &mut scenario)?;
+    if cli.duration.is_none() {
+        // Each logged request is sent once, and only the first --requests of them.
+        cli.requests = cli.requests.min(scenario.requests.len() as u64);
+        scenario.requests.truncate(cli.requests as usize);
+    }
+    let (shared_list, actual_duration) = run_scenario(cli, scenario).await?;
+    print_summary(&shared_list, actual_duration).await;
+    Ok(())
+}
+fn run_export(args: ExportArgs) -> Result<(), anyhow::Error> {
+    let cli = Cli::try_parse_from(once(String::from("kt")).chain(args.plan))?;
+    print!("{}", output::export::render(&cli, args.format)?);
+    Ok(())
+}
+fn run_from_curl(args: FromCurlArgs) -> Result<(), anyhow::Error> {
+    let (kt_args, ignored) = scenario::curl::to_kt_args(&args.command)?;
+    let quoted = kt_args
+        .iter()
+        .map(|arg| shlex::try_quote(arg).map_err(|e| anyhow!("Cannot quote {arg:?}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !ignored.is_empty() {
+        eprintln!("Options kt has no counterpart for: {}", ignored.join(", "));
+    }
+    println!("kt {}", quoted.join(" "));
+    Ok(())
+}
+/// Checks that `cli` suits a replayed scenario and adds its -H headers to every request.
+fn prepare_replay(cli: &Cli, scenario: &mut Scenario) -> Result<(), anyhow::Error> {
+    if cli.body.is_some()
+        || !cli.form.is_empty()
+        || cli.grpc.is_some()
+        || !cli.graphql.is_empty()
+        || cli.http3
+    {
+        return Err(anyhow!(
+            "--body, --form, --grpc, --graphql and --http3 cannot be used when replaying recorded requests"
+        ));
+    }
+    let accept_encoding = cli.accept_encoding_header();
+    for request in &mut scenario.requests {
+        if let Some(accept_encoding) = &accept_encoding {
+            request
+                .headers_mut()
+                .insert(ACCEPT_ENCODING, accept_encoding.clone());
+        }
+        for (key, value) in &cli.headers {
+            request
+                .headers_mut()
+                .insert(HeaderName::from_str(key)?, HeaderValue::from_str(value)?);
+        }
+    }
+    Ok(())
+}
+async fn print_summary(shared_list: &Mutex<StatisticList>, actual_duration: Duration) {
+    let stats = shared_list.lock().await;
+    if let Some(warmup) = stats.warmup_summary() {
+        println!("{warmup}");
+    }
+    if let Some(summary) = stats.analyze(actual_duration) {
+        println!("{summary}");
+    } else {
+        println!("No responses were recorded.");
+    }
+}
+/// Runs the test described by `cli` and returns the collected statistics with the elapsed time.
+pub async fn run_benchmark(
+    cli: Cli,
+) -> Result<(Arc<Mutex<StatisticList>>, Duration), anyhow::Error> {
+    if cli.is_websocket() {
+        return Err(anyhow!(
+            "WebSocket URLs can only be tested from the command line, not by an agent"
+        ));
+    }
+    let requests = build_requests(&cli).await?;
+    run_scenario(cli, Scenario::rotating(requests)).await
+}
+/// The requests described by the URL, headers and body of `cli`.
+async fn build_requests(cli: &Cli) -> Result<Vec<Request<Full<Bytes>>>, anyhow::Error> {
     let mut content_type_option = None;
     if cli.body.is_some() {
-        method = String::from("POST");
         content_type_option = Some(String::from("application/x-www-form-urlencoded"));
     }
-    let mut req_builder = Request::builder()
-        .method(method.as_str())
-        .uri(cli.url.clone());
+    let mut req_builder = Request::builder().method(cli.method()).uri(cli.url.clone());
     let mut header_map = HeaderMap::new();
     if let Some(content_type) = content_type_option {
         header_map.insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
     }
+    if let Some(accept_encoding) = cli.accept_encoding_header() {
+        header_map.insert(ACCEPT_ENCODING, accept_encoding);
+    }
+    if let Some(coding) = cli.compress_body {
+        header_map.insert(CONTENT_ENCODING, HeaderValue::from_static(coding.as_str()));
+    }
     for x in cli.headers.clone() {
         let key = x.0;
         let value = x.1;
@@ -116,40 +300,154 @@ async fn do_request(cli: Cli) -> Result<(), anyhow::Error> {
             body_bytes = Bytes::from(body_str.clone());
         }
     }
-    let req = req_builder.body(Full::new(body_bytes))?;
+    if let Some(coding) = cli.compress_body {
+        body_bytes = compression::compress(&body_bytes, coding)?.into();
+    }
+    let (parts, _) = req_builder.body(())?.into_parts();
+    let requests = if let Some(method) = &cli.grpc {
+        vec![grpc::call_request(
+            parts,
+            method,
+            cli.proto_set.as_deref(),
+            &body_bytes,
+        )?]
+    } else if !cli.graphql.is_empty() {
+        let variables = match &cli.graphql_variables {
+            Some(arg) => Some(graphql::read_variables(arg).await?),
+            None => None,
+        };
+        graphql::build_requests(&parts, &cli.graphql, variables.as_ref())?
+    } else {
+        vec![Request::from_parts(parts, Full::new(body_bytes))]
+    };
+
+    Ok(requests)
+}
+/// Runs `scenario` with the load shape and client settings of `cli`.
+pub async fn run_scenario(
+    cli: Cli,
+    mut scenario: Scenario,
+) -> Result<(Arc<Mutex<StatisticList>>, Duration), anyhow::Error> {
+    let tls_config = client::tls_config(cli.cacert.as_deref())?;
+    let shared_list: Arc<Mutex<StatisticList>> = Arc::new(Mutex::new(StatisticList::for_cli(&cli)));
+    if let Some(name) = &scenario.name {
+        shared_list.lock().await.target = name.clone();
+    }
+    let connector = KtConnector::new(&cli, shared_list.clone())?;
+    let proxy = connector.proxy();
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config)
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .wrap_connector(connector);
 
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new())
+        .http2_only(cli.http2_prior_knowledge || cli.grpc.is_some())
+        .build(https.clone());
     let mut task_list = JoinSet::new();
-    let shared_list: Arc<Mutex<StatisticList>> = Arc::new(Mutex::new(StatisticList {
-        cli: cli.clone(),
-        response_list: vec![],
-    }));
+    let in_flight = Arc::new(AtomicI64::new(0));
+    let metrics_task = match cli.metrics_listen {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!("Serving Prometheus metrics on http://{addr}/metrics");
+            Some(tokio::spawn(metrics::prometheus::serve(
+                listener,
+                shared_list.clone(),
+                in_flight.clone(),
+            )))
+        }
+        None => None,
+    };
+    let mut sinks = vec![];
+    if let Some(target) = &cli.statsd {
+        sinks.push(PushSink::connect(PushFormat::Statsd, target).await?);
+    }
+    if let Some(target) = &cli.influx_udp {
+        sinks.push(PushSink::connect(PushFormat::Influx, target).await?);
+    }
+    let push_task = if sinks.is_empty() {
+        None
+    } else {
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let task = tokio::spawn(metrics::push::run(
+            sinks,
+            shared_list.clone(),
+            cli.push_interval,
+            stop_receiver,
+        ));
+        Some((stop_sender, task))
+    };
+    let mut worker = Worker {
+        shared_list: shared_list.clone(),
+        client: client.clone(),
+        requests: Arc::new(std::mem::take(&mut scenario.requests)),
+        next_request: Arc::new(AtomicUsize::new(0)),
+        sequential: scenario.sequential,
+        in_flight: in_flight.clone(),
+        cookie_jar: cli.cookie_jar.map(|_| CookieJar::default()),
+        max_redirects: cli.follow_redirects.then_some(cli.max_redirects),
+        proxy,
+        http3: if cli.http3 {
+            Some(Http3Client::new(&cli, shared_list.clone())?)
+        } else {
+            None
+        },
+        grpc: cli.grpc.is_some(),
+        graphql: !cli.graphql.is_empty(),
+        stream: cli.stream,
+        max_stream_time: cli.max_stream_time,
+        deadline: None,
+        think_time: cli.think_time,
+        pacing: cli.pacing,
+        pace: Arc::default(),
+        variables: Arc::default(),
+        journey: scenario.journey.clone(),
+        retry: RetryPolicy::for_cli(&cli).map(Arc::new),
+        decompress: !cli.accept_encoding.is_empty(),
+        form: if cli.form.is_empty() {
+            None
+        } else {
+            Some(Arc::new(Form::load(&cli.form)?))
+        },
+    };
+    let shared_jar = cli.cookie_jar == Some(CookieJarMode::Shared);
     let now = Instant::now();
+    shared_list
+        .lock()
+        .await
+        .start_warmup(now.into_std(), cli.warmup, cli.warmup_requests);
+    // The warm-up comes on top of the measured duration or request count.
+    let warmup = cli.warmup.unwrap_or_default();
+    worker.deadline = cli.duration.map(|duration| now + warmup + duration);
 
     // Logic to handle either duration or request count
-    if let Some(duration) = cli.duration {
+    if scenario.timed {
+        if cli.think_time.is_some() || cli.pacing.is_some() {
+            return Err(anyhow!(
+                "--think-time and --pacing cannot be used with a timed replay; use --max-speed"
+            ));
+        }
+        // --- Timed replay, every request is sent once at its own offset ---
+        let worker = worker.for_next_worker(shared_jar);
+        task_list.spawn(async move { submit_task_timed(worker, now).await });
+    } else if let Some(duration) = cli.duration {
         // --- Duration based test ---
         let (sender, _) = broadcast::channel(16);
         for _ in 0..cli.concurrency {
             let rx2 = sender.subscribe();
-            let cloned_list = shared_list.clone();
-            let cloned_req = req.clone();
-            let clone_client = client.clone();
-            task_list.spawn(async move {
-                submit_task_duration(cloned_list, clone_client, cloned_req, rx2).await
-            });
+            let worker = worker.for_next_worker(shared_jar);
+            task_list.spawn(async move { submit_task_duration(worker, rx2).await });
         }
-        sleep(duration).await;
+        sleep(warmup + duration).await;
         sender.send(())?;
     } else {
-        let requests_counter = Arc::new(AtomicI64::new(cli.requests as i64));
+        let requests = cli.requests + cli.warmup_requests.unwrap_or(0);
+        let requests_counter = Arc::new(AtomicI64::new(requests as i64));
         for _ in 0..cli.concurrency {
             let counter_clone = requests_counter.clone();
-            let cloned_list = shared_list.clone();
-            let cloned_req = req.clone();
-            let clone_client = client.clone();
-            task_list.spawn(async move {
-                submit_task_requests(cloned_list, clone_client, cloned_req, counter_clone).await
-            });
+            let worker = worker.for_next_worker(shared_jar);
+            task_list.spawn(async move { submit_task_requests(worker, counter_clone).await });
         }
     }
 
@@ -160,47 +458,557 @@ async fn do_request(cli: Cli) -> Result<(), anyhow::Error> {
         }
     }
     drop(client);
-
-    let stats = shared_list.lock().await;
-    if let Some(summary) = stats.analyze(now.elapsed()) {
-        println!("{summary}");
-    } else {
-        println!("No responses were recorded.");
+    let elapsed = now.elapsed();
+    let actual_duration = elapsed.saturating_sub(shared_list.lock().await.end_warmup(elapsed));
+    if let Some(metrics_task) = metrics_task {
+        metrics_task.abort();
     }
-    Ok(())
+    if let Some((stop_sender, task)) = push_task {
+        let _ = stop_sender.send(());
+        task.await?;
+    }
+
+    Ok((shared_list, actual_duration))
 }
-async fn submit_task_duration(
+/// Everything a worker needs to send requests and record their outcome.
+#[derive(Clone)]
+struct Worker {
     shared_list: Arc<Mutex<StatisticList>>,
-    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
-    request: Request<Full<Bytes>>,
-    mut receiver: Receiver<()>,
-) -> Result<(), anyhow::Error> {
-    let clone_client = client.clone();
+    client: Client<HttpsConnector<KtConnector>, Full<Bytes>>,
+    // Sent in turn; each may carry extra report Labels in its extensions
+    requests: Arc<Vec<Request<Full<Bytes>>>>,
+    next_request: Arc<AtomicUsize>,
+    // Every worker has its own `next_request` and walks the requests in order
+    sequential: bool,
+    in_flight: Arc<AtomicI64>,
+    cookie_jar: Option<CookieJar>,
+    // None when redirects are reported as they are instead of followed
+    max_redirects: Option<u32>,
+    proxy: Arc<ProxyConfig>,
+    // Sends every request over HTTP/3 instead of `client`
+    http3: Option<Http3Client>,
+    // Read responses to their trailers and record the gRPC status
+    grpc: bool,
+    // Read responses to the end and fail those carrying GraphQL errors
+    graphql: bool,
+    // Read response bodies as event streams instead of dropping them
+    stream: bool,
+    max_stream_time: Option<Duration>,
+    // When a duration based test ends; streams still open are cut then
+    deadline: Option<Instant>,
+    // Pause between the requests of this worker
+    think_time: Option<ThinkTimeDistribution>,
+    // Least time between the starts of two iterations of this worker
+    pacing: Option<Duration>,
+    pace: Arc<std::sync::Mutex<Pace>>,
+    // Values this worker's journey steps extracted, used by later steps and iterations
+    variables: Arc<std::sync::Mutex<HashMap<String, String>>>,
+    // Run instead of sending `requests`
+    journey: Option<Arc<Journey>>,
+    retry: Option<Arc<RetryPolicy>>,
+    // Read responses to the end and decompress them under --accept-encoding
+    decompress: bool,
+    // Replaces the body of every request, uploading a random file for directory fields
+    form: Option<Arc<Form>>,
+}
 
-    loop {
+/// Where a worker is in its think time and pacing.
+#[derive(Default)]
+struct Pace {
+    // The worker has sent a request, so the next one follows a pause
+    started: bool,
+    next_iteration: Option<Instant>,
+}
+
+impl Worker {
+    /// A copy for the next spawned worker, with a fresh jar unless the jar is shared.
+    fn for_next_worker(&self, shared_jar: bool) -> Worker {
+        let mut worker = self.clone();
+        if !shared_jar && worker.cookie_jar.is_some() {
+            worker.cookie_jar = Some(CookieJar::default());
+        }
+        worker.http3 = self.http3.as_ref().map(Http3Client::for_next_worker);
+        if self.sequential {
+            worker.next_request = Arc::new(AtomicUsize::new(0));
+        }
+        worker.pace = Arc::default();
+        if let Some(journey) = &self.journey {
+            worker.variables = Arc::new(std::sync::Mutex::new(journey.variables.clone()));
+        }
+        worker
+    }
+
+    /// Sends the next request and records its outcome in the background.
+    async fn send(&self) {
+        if let Some(journey) = &self.journey {
+            self.run_journey(journey).await;
+            return;
+        }
+        let index = self.next_request.fetch_add(1, Ordering::Relaxed) % self.requests.len();
+        let mut request = self.requests[index].clone();
+        // An iteration is one pass over a sequential scenario, or else a single request.
+        let iteration_start = !self.sequential || index == 0;
+        if let Some(until) = self.pause_until(&request, iteration_start) {
+            if !self.sleep_before_deadline(until).await {
+                return;
+            }
+        }
+        if let Some(form) = &self.form {
+            let body = match form.body().await {
+                Ok(body) => body,
+                Err(e) => {
+                    let labels = request
+                        .extensions()
+                        .get::<Labels>()
+                        .cloned()
+                        .unwrap_or_default();
+                    self.shared_list.lock().await.push(Err(e), labels);
+                    return;
+                }
+            };
+            self.shared_list
+                .lock()
+                .await
+                .record_upload(body.len() as u64);
+            request
+                .headers_mut()
+                .insert(CONTENT_TYPE, form.content_type());
+            *request.body_mut() = Full::new(body);
+        }
+        self.send_request(request).await;
+    }
+
+    /// Runs the journey's steps in order and records each step and the journey as a whole.
+    /// A failed step, or one whose response lacks a value to extract, ends the journey.
+    async fn run_journey(&self, journey: &Journey) {
+        let mut variables = self.variables.lock().unwrap().clone();
+        // Think time and pacing are left out of the journey's duration.
+        let mut busy = Duration::ZERO;
+        let mut completed = true;
+        for (index, step) in journey.steps.iter().enumerate() {
+            let request = match step.render(&variables) {
+                Ok(request) => request,
+                Err(e) => {
+                    let labels = vec![(journey::LABEL_STEP, step.label().to_string())];
+                    self.shared_list.lock().await.push(Err(e), labels);
+                    completed = false;
+                    break;
+                }
+            };
+            if let Some(until) = self.pause_until(&request, index == 0) {
+                if !self.sleep_before_deadline(until).await {
+                    return;
+                }
+            }
+            let labels = request
+                .extensions()
+                .get::<Labels>()
+                .cloned()
+                .unwrap_or_default();
+            let now = Instant::now();
+            self.in_flight.fetch_add(1, Ordering::Relaxed);
+            let (result, retries) = self.execute_with_retries(request).await;
+            self.record_retries(retries, &result).await;
+            let result = match result {
+                Ok((res, redirects)) => {
+                    let (parts, body) = res.into_parts();
+                    match timeout(Duration::from_millis(500), body.collect()).await {
+                        Ok(Ok(body)) => Ok((parts, redirects, body.to_bytes())),
+                        Ok(Err(e)) => Err(e.into()),
+                        Err(_) => Err(anyhow!("Request timeout")),
+                    }
+                }
+                Err(e) => Err(e),
+            };
+            // Values are extracted from the decompressed body, the transferred size is recorded.
+            let result = match result {
+                Ok((parts, redirects, body)) if self.decompress => {
+                    match compression::decompress(&parts.headers, body.to_vec()) {
+                        Ok(decoded) => {
+                            let mut list = self.shared_list.lock().await;
+                            list.record_decoded(decoded.len() as u64);
+                            Ok((parts, redirects, body.len(), Bytes::from(decoded)))
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                result => {
+                    result.map(|(parts, redirects, body)| (parts, redirects, body.len(), body))
+                }
+            };
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            let time_cost_ns = now.elapsed().as_nanos() as u64;
+            busy += now.elapsed();
+            let result = result.and_then(|(parts, redirects, content_length, body)| {
+                if !parts.status.is_client_error() && !parts.status.is_server_error() {
+                    step.extract(&parts.headers, &body, &mut variables)?;
+                } else {
+                    completed = false;
+                }
+                Ok(ResponseStatistic {
+                    time_cost_ns,
+                    status_code: parts.status.as_u16(),
+                    content_length: content_length as u64,
+                    redirects,
+                })
+            });
+            completed &= result.is_ok();
+            self.shared_list.lock().await.push(result, labels);
+            if !completed {
+                break;
+            }
+        }
+        let time_cost_ns = completed.then_some(busy.as_nanos() as u64);
+        self.shared_list.lock().await.record_journey(time_cost_ns);
+        *self.variables.lock().unwrap() = variables;
+    }
+
+    /// When the next request may go out under --pacing and --think-time, or None to send now.
+    /// Recorded think times replace --think-time, and pacing replaces it between iterations.
+    fn pause_until(
+        &self,
+        request: &Request<Full<Bytes>>,
+        iteration_start: bool,
+    ) -> Option<Instant> {
+        if self.think_time.is_none() && self.pacing.is_none() {
+            return None;
+        }
+        let mut pace = self.pace.lock().unwrap();
         let now = Instant::now();
-        let cloned_client1 = clone_client.clone();
-        let result = timeout(
-            Duration::from_millis(500),
-            cloned_client1.request(request.clone()),
-        )
-        .await;
+        let first = !std::mem::replace(&mut pace.started, true);
+        match self.pacing {
+            Some(pacing) if iteration_start => {
+                let start = pace.next_iteration.map_or(now, |next| next.max(now));
+                pace.next_iteration = Some(start + pacing);
+                Some(start)
+            }
+            _ if first || request.extensions().get::<ThinkTime>().is_some() => None,
+            _ => self.think_time.map(|think_time| now + think_time.sample()),
+        }
+    }
+
+    /// Sleeps until `until`, or only to the deadline when that comes first and returns false.
+    async fn sleep_before_deadline(&self, until: Instant) -> bool {
+        match self.deadline {
+            Some(deadline) if until >= deadline => {
+                sleep_until(deadline).await;
+                false
+            }
+            _ => {
+                sleep_until(until).await;
+                true
+            }
+        }
+    }
+
+    /// Sends `request` and records its outcome in the background.
+    async fn send_request(&self, request: Request<Full<Bytes>>) {
+        let labels = request
+            .extensions()
+            .get::<Labels>()
+            .cloned()
+            .unwrap_or_default();
+        if let Some(ThinkTime(think_time)) = request.extensions().get::<ThinkTime>().copied() {
+            if !self
+                .sleep_before_deadline(Instant::now() + think_time)
+                .await
+            {
+                return;
+            }
+        }
+        let now = Instant::now();
+        if let Some(http3) = &self.http3 {
+            self.in_flight.fetch_add(1, Ordering::Relaxed);
+            let result = timeout(Duration::from_millis(500), http3.send(request, now))
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("Request timeout")));
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            self.shared_list.lock().await.push(result, labels);
+            return;
+        }
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        let (result, retries) = self.execute_with_retries(request).await;
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
         let elapsed = now.elapsed().as_nanos();
+        self.record_retries(retries, &result).await;
         match result {
-            Ok(Ok(res)) => {
-                tokio::spawn(statistic(shared_list.clone(), elapsed, Ok(res)));
+            Ok((res, redirects)) if self.grpc => {
+                self.finish_grpc_call(now, redirects, res, labels).await;
             }
-            Ok(Err(e)) => {
-                tokio::spawn(statistic(shared_list.clone(), elapsed, Err(anyhow!(e))));
+            Ok((res, redirects)) if self.graphql => {
+                self.finish_graphql(now, redirects, res, labels).await;
             }
-            Err(e) => {
-                tokio::spawn(statistic(
-                    shared_list.clone(),
+            Ok((res, redirects)) if self.decompress => {
+                self.finish_decompressed(now, redirects, res, labels).await;
+            }
+            // The worker holds the stream until it ends, so concurrency is the number of open streams.
+            Ok((res, redirects)) if self.stream => {
+                self.read_stream(now, elapsed, redirects, res, labels).await;
+            }
+            Ok((res, redirects)) => {
+                // Recorded before returning, so a test that ends right after still counts it.
+                statistic(
+                    self.shared_list.clone(),
                     elapsed,
-                    Err(anyhow!("Request timeout")),
-                ));
+                    redirects,
+                    Ok(res),
+                    labels,
+                )
+                .await;
+            }
+            Err(e) => {
+                statistic(self.shared_list.clone(), elapsed, 0, Err(e), labels).await;
             }
         }
+    }
+
+    /// Sends `request` within the request timeout, and again under --retries while it fails
+    /// with a --retry-on condition. Returns the last outcome and the number of retries sent.
+    async fn execute_with_retries(
+        &self,
+        request: Request<Full<Bytes>>,
+    ) -> (Result<(Response<Incoming>, u32), anyhow::Error>, u32) {
+        let send = |request| timeout(Duration::from_millis(500), self.execute(request));
+        let Some(policy) = self.retry.as_deref() else {
+            let result = send(request).await;
+            return (
+                result.unwrap_or_else(|_| Err(anyhow!("Request timeout"))),
+                0,
+            );
+        };
+        let mut retries = 0;
+        loop {
+            let result = send(request.clone()).await;
+            let retry = retries < policy.retries
+                && match &result {
+                    Ok(Ok((res, _))) => policy.retries_status(res.status()),
+                    Ok(Err(e)) => policy.retries_error(e),
+                    Err(_) => policy.retries_timeout(),
+                };
+            let result = result.unwrap_or_else(|_| Err(anyhow!("Request timeout")));
+            if !retry {
+                return (result, retries);
+            }
+            let headers = result.as_ref().ok().map(|(res, _)| res.headers());
+            let until = Instant::now() + policy.delay(retries, headers);
+            // A retry that cannot go out before the test ends is not waited for.
+            if self.deadline.is_some_and(|deadline| until >= deadline) {
+                return (result, retries);
+            }
+            drop(result);
+            sleep_until(until).await;
+            retries += 1;
+        }
+    }
+
+    /// Counts a request under --retries as succeeding on its first attempt, after retrying,
+    /// or failing despite retries.
+    async fn record_retries(
+        &self,
+        retries: u32,
+        result: &Result<(Response<Incoming>, u32), anyhow::Error>,
+    ) {
+        if self.retry.is_none() {
+            return;
+        }
+        let success = matches!(result, Ok((res, _)) if res.status().as_u16() < 400);
+        self.shared_list
+            .lock()
+            .await
+            .record_retries(retries, success);
+    }
+
+    /// Reads a gRPC response to its trailers and records the call under its gRPC status.
+    /// The latency covers the whole call, not just the response headers.
+    async fn finish_grpc_call(
+        &self,
+        started: Instant,
+        redirects: u32,
+        res: Response<Incoming>,
+        mut labels: Labels,
+    ) {
+        labels.extend(response_labels(&res));
+        let until = self.stream_until(started);
+        let (parts, body) = res.into_parts();
+        let (stream, trailers) = stream::read_events(body, Framing::Grpc, started, until).await;
+        let time_cost_ns = started.elapsed().as_nanos() as u64;
+        let cut = until.is_some_and(|until| Instant::now() >= until);
+        let result = if parts.status != StatusCode::OK {
+            Err(anyhow!(
+                "gRPC call failed with HTTP status {}",
+                parts.status
+            ))
+        } else {
+            match grpc::status(&parts.headers, trailers.as_ref()) {
+                Some(code) => Ok(code),
+                None if cut => Ok(grpc::STATUS_CANCELLED),
+                None => Err(anyhow!("gRPC call ended without a grpc-status")),
+            }
+        };
+        let mut list = self.shared_list.lock().await;
+        if self.stream {
+            list.record_stream(&stream);
+        }
+        let result = result.map(|status_code| ResponseStatistic {
+            time_cost_ns,
+            status_code,
+            content_length: stream.bytes,
+            redirects,
+        });
+        list.push(result, labels);
+    }
+
+    /// Reads a GraphQL response and records it, failing it when the body carries errors.
+    /// The latency covers the whole body, which has to be read to find the errors.
+    async fn finish_graphql(
+        &self,
+        started: Instant,
+        redirects: u32,
+        res: Response<Incoming>,
+        mut labels: Labels,
+    ) {
+        labels.extend(response_labels(&res));
+        let status_code = res.status().as_u16();
+        let body = timeout(Duration::from_millis(500), res.into_body().collect()).await;
+        let time_cost_ns = started.elapsed().as_nanos() as u64;
+        let result = match body {
+            Ok(Ok(body)) => {
+                let body = body.to_bytes();
+                graphql::check_response(&body).map(|_| ResponseStatistic {
+                    time_cost_ns,
+                    status_code,
+                    content_length: body.len() as u64,
+                    redirects,
+                })
+            }
+            Ok(Err(e)) => Err(anyhow!(e)),
+            Err(_) => Err(anyhow!("Request timeout")),
+        };
+        self.shared_list.lock().await.push(result, labels);
+    }
+
+    /// Reads a response to its end and decompresses it, recording the transferred size
+    /// along with the response and the decompressed size on its own.
+    async fn finish_decompressed(
+        &self,
+        started: Instant,
+        redirects: u32,
+        res: Response<Incoming>,
+        mut labels: Labels,
+    ) {
+        labels.extend(response_labels(&res));
+        let (parts, body) = res.into_parts();
+        let body = timeout(Duration::from_millis(500), body.collect()).await;
+        let time_cost_ns = started.elapsed().as_nanos() as u64;
+        let result = match body {
+            Ok(body) => body.map_err(|e| anyhow!(e)),
+            Err(_) => Err(anyhow!("Request timeout")),
+        };
+        let result = result.and_then(|body| {
+            let body = body.to_bytes();
+            let decoded = compression::decompress(&parts.headers, body.to_vec())?;
+            Ok((body.len(), decoded.len()))
+        });
+        let mut list = self.shared_list.lock().await;
+        match result {
+            Ok((content_length, decoded_length)) => {
+                list.record_decoded(decoded_length as u64);
+                let response_statistic = ResponseStatistic {
+                    time_cost_ns,
+                    status_code: parts.status.as_u16(),
+                    content_length: content_length as u64,
+                    redirects,
+                };
+                list.push(Ok(response_statistic), labels);
+            }
+            Err(e) => list.push(Err(e), labels),
+        }
+    }
+
+    /// When a stream started at `started` is cut, if ever.
+    fn stream_until(&self, started: Instant) -> Option<Instant> {
+        [self.deadline, self.max_stream_time.map(|max| started + max)]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Reads a streamed response to its end and records its events along with the response.
+    async fn read_stream(
+        &self,
+        started: Instant,
+        time_cost_ns: u128,
+        redirects: u32,
+        res: Response<Incoming>,
+        mut labels: Labels,
+    ) {
+        let sse = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let framing = if sse {
+            Framing::ServerSentEvents
+        } else {
+            Framing::Chunks
+        };
+        labels.extend(response_labels(&res));
+        let status_code = res.status().as_u16();
+        let until = self.stream_until(started);
+        let (stream, _) = stream::read_events(res.into_body(), framing, started, until).await;
+        let mut list = self.shared_list.lock().await;
+        list.record_stream(&stream);
+        let response_statistic = ResponseStatistic {
+            time_cost_ns: time_cost_ns as u64,
+            status_code,
+            content_length: stream.bytes,
+            redirects,
+        };
+        list.push(Ok(response_statistic), labels);
+    }
+
+    /// Sends `request`, following redirects when enabled, and returns the final response
+    /// with the number of hops it took.
+    async fn execute(
+        &self,
+        mut request: Request<Full<Bytes>>,
+    ) -> Result<(Response<Incoming>, u32), anyhow::Error> {
+        let mut redirects = 0;
+        loop {
+            let uri = request.uri().clone();
+            let mut outgoing = request.clone();
+            if let Some(jar) = &self.cookie_jar {
+                jar.apply(&uri, outgoing.headers_mut());
+            }
+            if let Some(value) = self.proxy.authorization_for(&uri) {
+                outgoing.headers_mut().insert(PROXY_AUTHORIZATION, value);
+            }
+            let res = self.client.request(outgoing).await?;
+            if let Some(jar) = &self.cookie_jar {
+                jar.store(&uri, res.headers());
+            }
+            let Some(max_redirects) = self.max_redirects else {
+                return Ok((res, redirects));
+            };
+            let location = res.headers().get(LOCATION).and_then(|v| v.to_str().ok());
+            let Some(next) = redirect::next_request(&request, res.status(), location)? else {
+                return Ok((res, redirects));
+            };
+            if redirects >= max_redirects {
+                return Err(anyhow!("Too many redirects (max {max_redirects})"));
+            }
+            redirect::drain(res.into_body()).await;
+            redirects += 1;
+            request = next;
+        }
+    }
+}
+async fn submit_task_duration(
+    worker: Worker,
+    mut receiver: Receiver<()>,
+) -> Result<(), anyhow::Error> {
+    loop {
+        worker.send().await;
         tokio::select! {
             biased;
             _ = receiver.recv() => {
@@ -209,49 +1017,42 @@ async fn submit_task_duration(
             _=async{}=>{}
         }
     }
-
+}
+async fn submit_task_timed(worker: Worker, started: Instant) -> Result<(), anyhow::Error> {
+    // Requests are not held back by slow responses, so the original traffic shape is kept.
+    let mut in_flight = JoinSet::new();
+    for request in worker.requests.iter() {
+        let offset = request
+            .extensions()
+            .get::<SendAt>()
+            .map_or(Duration::ZERO, |at| at.0);
+        let send_at = started + offset;
+        if worker.deadline.is_some_and(|deadline| send_at >= deadline) {
+            break;
+        }
+        sleep_until(send_at).await;
+        let worker = worker.clone();
+        let request = request.clone();
+        in_flight.spawn(async move { worker.send_request(request).await });
+    }
+    while in_flight.join_next().await.is_some() {}
     Ok(())
 }
 async fn submit_task_requests(
-    shared_list: Arc<Mutex<StatisticList>>,
-    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
-    request: Request<Full<Bytes>>,
+    worker: Worker,
     requests_counter: Arc<AtomicI64>,
 ) -> Result<(), anyhow::Error> {
-    let clone_client = client.clone();
-
     while requests_counter.fetch_sub(1, Ordering::Relaxed) > 0 {
-        let now = Instant::now();
-
-        let cloned_client1 = clone_client.clone();
-        let result = timeout(
-            Duration::from_millis(500),
-            cloned_client1.request(request.clone()),
-        )
-        .await;
-        let elapsed = now.elapsed().as_nanos();
-        match result {
-            Ok(Ok(res)) => {
-                tokio::spawn(statistic(shared_list.clone(), elapsed, Ok(res)));
-            }
-            Ok(Err(e)) => {
-                tokio::spawn(statistic(shared_list.clone(), elapsed, Err(anyhow!(e))));
-            }
-            Err(_) => {
-                tokio::spawn(statistic(
-                    shared_list.clone(),
-                    elapsed,
-                    Err(anyhow!("Request timeout")),
-                ));
-            }
-        }
+        worker.send().await;
     }
     Ok(())
 }
 async fn statistic(
     shared_list: Arc<Mutex<StatisticList>>,
     time_cost_ns: u128,
+    redirects: u32,
     result: Result<Response<Incoming>, anyhow::Error>,
+    mut labels: Labels,
 ) {
     match result {
         Ok(res) => {
@@ -265,18 +1066,37 @@ async fn statistic(
                 .unwrap_or("0")
                 .parse::<u64>()
                 .unwrap_or(0);
+            labels.extend(response_labels(&res));
             let mut list = shared_list.lock().await;
             let response_statistic = ResponseStatistic {
                 time_cost_ns: time_cost_ns as u64,
                 status_code: res.status().as_u16(),
                 content_length: content_len,
+                redirects,
             };
-            list.response_list.push(Ok(response_statistic));
+            list.push(Ok(response_statistic), labels);
         }
         Err(e) => {
+            let connect_error = e.chain().find_map(|c| c.downcast_ref::<ConnectError>());
+            if let Some(addr) = connect_error.and_then(|c| c.remote_addr) {
+                labels.push((LABEL_REMOTE_IP, addr.ip().to_string()));
+            }
+            if let Some(addr) = connect_error.and_then(|c| c.local_addr) {
+                labels.push((LABEL_SOURCE_ADDRESS, addr.to_string()));
+            }
             let mut list = shared_list.lock().await;
 
-            list.response_list.push(Err(anyhow!("{}", e)));
+            list.push(Err(anyhow!("{}", e)), labels);
         }
     };
 }
+
+/// The labels a response is grouped by in the report.
+fn response_labels(res: &Response<Incoming>) -> Labels {
+    let mut labels = Labels::new();
+    if let Some(info) = res.extensions().get::<HttpInfo>() {
+        labels.push((LABEL_REMOTE_IP, info.remote_addr().ip().to_string()));
+        labels.push((LABEL_SOURCE_ADDRESS, info.local_addr().ip().to_string()));
+    }
+    labels
+}
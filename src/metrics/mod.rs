@@ -0,0 +1,14 @@
+pub mod prometheus;
+pub mod push;
+
+/// Maps an error message to a small, fixed set of kinds so it can be used as a metric label.
+pub fn error_kind(message: &str) -> &'static str {
+    let lower = message.to_ascii_lowercase();
+    if lower.contains("timeout") || lower.contains("timed out") {
+        "timeout"
+    } else if lower.contains("connect") || lower.contains("refused") || lower.contains("dns") {
+        "connect"
+    } else {
+        "request"
+    }
+}
@@ -0,0 +1,159 @@
+use super::error_kind;
+use crate::output::report::StatisticList;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::body::Incoming;
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use hyper_util::rt::TokioIo;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+// Upper bounds of the latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 14] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Serves the live statistics in the Prometheus text format until the task is aborted.
+pub async fn serve(
+    listener: TcpListener,
+    shared_list: Arc<Mutex<StatisticList>>,
+    in_flight: Arc<AtomicI64>,
+) -> Result<(), anyhow::Error> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let shared_list = shared_list.clone();
+        let in_flight = in_flight.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req: Request<Incoming>| {
+                let shared_list = shared_list.clone();
+                let in_flight = in_flight.clone();
+                async move {
+                    if req.uri().path() != "/metrics" {
+                        let mut response = Response::new(Full::new(Bytes::from("Not Found")));
+                        *response.status_mut() = StatusCode::NOT_FOUND;
+                        return Ok::<_, Infallible>(response);
+                    }
+                    let text = render(&*shared_list.lock().await, &in_flight);
+                    let mut response = Response::new(Full::new(Bytes::from(text)));
+                    response.headers_mut().insert(
+                        CONTENT_TYPE,
+                        "text/plain; version=0.0.4; charset=utf-8".parse().unwrap(),
+                    );
+                    Ok(response)
+                }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Metrics connection closed with error: {e}");
+            }
+        });
+    }
+}
+
+fn render(list: &StatisticList, in_flight: &AtomicI64) -> String {
+    let totals = &list.totals;
+    let endpoint = escape_label(&list.target);
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# HELP kt_requests_total Completed requests by status code."
+    );
+    let _ = writeln!(out, "# TYPE kt_requests_total counter");
+    let status_codes: BTreeMap<_, _> = totals.status_code_dist.iter().collect();
+    for (code, count) in status_codes {
+        let _ = writeln!(
+            out,
+            "kt_requests_total{{endpoint=\"{endpoint}\",status_code=\"{code}\"}} {count}"
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP kt_errors_total Requests that failed without a response."
+    );
+    let _ = writeln!(out, "# TYPE kt_errors_total counter");
+    let mut errors: BTreeMap<&str, usize> = BTreeMap::new();
+    for (message, count) in &totals.error_dist {
+        *errors.entry(error_kind(message)).or_insert(0) += count;
+    }
+    for (kind, count) in errors {
+        let _ = writeln!(
+            out,
+            "kt_errors_total{{endpoint=\"{endpoint}\",error_kind=\"{kind}\"}} {count}"
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP kt_in_flight_requests Requests currently waiting for a response."
+    );
+    let _ = writeln!(out, "# TYPE kt_in_flight_requests gauge");
+    let _ = writeln!(
+        out,
+        "kt_in_flight_requests{{endpoint=\"{endpoint}\"}} {}",
+        in_flight.load(Ordering::Relaxed).max(0)
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP kt_response_bytes_total Response body bytes received."
+    );
+    let _ = writeln!(out, "# TYPE kt_response_bytes_total counter");
+    let _ = writeln!(
+        out,
+        "kt_response_bytes_total{{endpoint=\"{endpoint}\"}} {}",
+        totals.total_data
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP kt_request_duration_seconds Latency of requests that got a response."
+    );
+    let _ = writeln!(out, "# TYPE kt_request_duration_seconds histogram");
+    for bound in LATENCY_BUCKETS {
+        let count = totals
+            .histogram
+            .count_between(0, (bound * 1_000_000_000.0) as u64);
+        let _ = writeln!(
+            out,
+            "kt_request_duration_seconds_bucket{{endpoint=\"{endpoint}\",le=\"{bound}\"}} {count}"
+        );
+    }
+    let _ = writeln!(
+        out,
+        "kt_request_duration_seconds_bucket{{endpoint=\"{endpoint}\",le=\"+Inf\"}} {}",
+        totals.successful_requests
+    );
+    let _ = writeln!(
+        out,
+        "kt_request_duration_seconds_sum{{endpoint=\"{endpoint}\"}} {}",
+        totals.total_time_cost_ns as f64 / 1_000_000_000.0
+    );
+    let _ = writeln!(
+        out,
+        "kt_request_duration_seconds_count{{endpoint=\"{endpoint}\"}} {}",
+        totals.successful_requests
+    );
+    out
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
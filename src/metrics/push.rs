@@ -0,0 +1,195 @@
+use super::error_kind;
+use crate::output::report::BenchmarkSummary;
+use crate::output::report::StatisticAggregate;
+use crate::output::report::StatisticList;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tokio::time::Duration;
+use tokio::time::Instant;
+use tokio::time::MissedTickBehavior;
+
+// Keep each datagram below a typical MTU so nothing gets fragmented.
+const MAX_DATAGRAM_SIZE: usize = 1400;
+
+#[derive(Clone, Copy, Debug)]
+pub enum PushFormat {
+    Statsd,
+    Influx,
+}
+
+pub struct PushSink {
+    format: PushFormat,
+    socket: UdpSocket,
+}
+
+impl PushSink {
+    pub async fn connect(format: PushFormat, target: &str) -> Result<Self, anyhow::Error> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket
+            .connect(target)
+            .await
+            .map_err(|e| anyhow!("Failed to resolve push target {target}: {e}"))?;
+        Ok(PushSink { format, socket })
+    }
+
+    async fn send(&self, summary: &BenchmarkSummary) {
+        let lines = match self.format {
+            PushFormat::Statsd => statsd_lines(summary),
+            PushFormat::Influx => influx_lines(summary),
+        };
+        for datagram in pack_datagrams(&lines) {
+            // UDP sinks are best effort, a lost interval must not stop the test.
+            if let Err(e) = self.socket.send(datagram.as_bytes()).await {
+                debug!("Failed to push {:?} metrics: {e}", self.format);
+            }
+        }
+    }
+}
+
+/// Pushes the statistics of every interval to the sinks until `stop` fires,
+/// then flushes the last partial interval.
+pub async fn run(
+    sinks: Vec<PushSink>,
+    shared_list: Arc<Mutex<StatisticList>>,
+    push_interval: Duration,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let (url, concurrency) = {
+        let list = shared_list.lock().await;
+        (list.target.clone(), list.concurrency)
+    };
+    let mut previous = StatisticAggregate::default();
+    let mut previous_at = Instant::now();
+    let mut ticker = interval(push_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        let stopped = tokio::select! {
+            _ = ticker.tick() => false,
+            _ = &mut stop => true,
+        };
+        let current = shared_list.lock().await.totals.clone();
+        let now = Instant::now();
+        let delta = current.since(&previous);
+        if delta.total_requests > 0 {
+            let summary = delta.summarize(url.clone(), concurrency, now - previous_at);
+            for sink in &sinks {
+                sink.send(&summary).await;
+            }
+        }
+        previous = current;
+        previous_at = now;
+        if stopped {
+            return;
+        }
+    }
+}
+
+fn statsd_lines(summary: &BenchmarkSummary) -> Vec<String> {
+    let mut lines = vec![
+        format!("kt.requests:{}|c", summary.total_requests),
+        format!(
+            "kt.errors:{}|c",
+            summary.total_requests - summary.successful_requests
+        ),
+        format!("kt.requests_per_sec:{:.2}|g", summary.requests_per_sec),
+        format!("kt.bytes:{}|c", summary.total_data),
+    ];
+    if summary.successful_requests > 0 {
+        for (name, value) in latency_fields(summary) {
+            lines.push(format!("kt.latency.{name}:{value:.3}|g"));
+        }
+    }
+    for (code, count) in summary.status_code_dist.iter().collect::<BTreeMap<_, _>>() {
+        lines.push(format!("kt.status.{code}:{count}|c"));
+    }
+    for (kind, count) in error_kinds(summary) {
+        lines.push(format!("kt.errors.{kind}:{count}|c"));
+    }
+    lines
+}
+
+fn influx_lines(summary: &BenchmarkSummary) -> Vec<String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let endpoint = escape_tag(&summary.url);
+    let mut fields = vec![
+        format!("requests={}i", summary.total_requests),
+        format!(
+            "errors={}i",
+            summary.total_requests - summary.successful_requests
+        ),
+        format!("requests_per_sec={:.2}", summary.requests_per_sec),
+        format!("bytes={}i", summary.total_data),
+    ];
+    if summary.successful_requests > 0 {
+        for (name, value) in latency_fields(summary) {
+            fields.push(format!("{name}_ms={value:.3}"));
+        }
+    }
+    let mut lines = vec![format!(
+        "kt,endpoint={endpoint} {} {timestamp}",
+        fields.join(",")
+    )];
+    for (code, count) in summary.status_code_dist.iter().collect::<BTreeMap<_, _>>() {
+        lines.push(format!(
+            "kt_status,endpoint={endpoint},status_code={code} count={count}i {timestamp}"
+        ));
+    }
+    for (kind, count) in error_kinds(summary) {
+        lines.push(format!(
+            "kt_errors,endpoint={endpoint},error_kind={kind} count={count}i {timestamp}"
+        ));
+    }
+    lines
+}
+
+// Latency values in milliseconds.
+fn latency_fields(summary: &BenchmarkSummary) -> [(&'static str, f64); 6] {
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    [
+        ("avg", ms(summary.average_latency)),
+        ("p50", ms(summary.p50)),
+        ("p90", ms(summary.p90)),
+        ("p95", ms(summary.p95)),
+        ("p99", ms(summary.p99)),
+        ("max", ms(summary.slowest)),
+    ]
+}
+
+fn error_kinds(summary: &BenchmarkSummary) -> BTreeMap<&'static str, usize> {
+    let mut kinds = BTreeMap::new();
+    for (message, count) in &summary.error_dist {
+        *kinds.entry(error_kind(message)).or_insert(0) += count;
+    }
+    kinds
+}
+
+fn escape_tag(value: &str) -> String {
+    value
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+fn pack_datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams: Vec<String> = vec![];
+    for line in lines {
+        match datagrams.last_mut() {
+            Some(last) if last.len() + 1 + line.len() <= MAX_DATAGRAM_SIZE => {
+                last.push('\n');
+                last.push_str(line);
+            }
+            _ => datagrams.push(line.clone()),
+        }
+    }
+    datagrams
+}
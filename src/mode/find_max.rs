@@ -0,0 +1,188 @@
+use crate::output::report::format_duration;
+use crate::output::report::StatusKind;
+use crate::vojo::cli::Cli;
+use crate::vojo::cli::FindMaxArgs;
+use crate::vojo::cli::SearchStrategy;
+use clap::Parser;
+use prettytable::Table;
+use std::iter::once;
+use std::time::Duration;
+
+/// What one step measured and whether it met the SLO.
+struct Step {
+    concurrency: u16,
+    requests_per_sec: f64,
+    p50: Duration,
+    p99: Duration,
+    error_rate: f64,
+    // Why the step failed the SLO
+    breach: Option<String>,
+}
+
+/// Runs the plan at rising concurrency until a step breaches the SLO, then prints every step
+/// and the highest concurrency that passed.
+pub async fn run(args: FindMaxArgs) -> Result<(), anyhow::Error> {
+    if args.start == 0 || args.step == 0 {
+        return Err(anyhow!("--start and --step must be at least 1"));
+    }
+    if args.max < args.start {
+        return Err(anyhow!("--max must not be below --start"));
+    }
+    let cli = Cli::try_parse_from(once(String::from("kt")).chain(args.plan.iter().cloned()))?;
+
+    let mut steps = vec![];
+    match args.search {
+        SearchStrategy::Step => {
+            let mut concurrency = args.start;
+            loop {
+                let step = measure(&cli, concurrency, &args).await?;
+                let passed = step.breach.is_none();
+                steps.push(step);
+                if !passed || concurrency == args.max {
+                    break;
+                }
+                concurrency = concurrency.saturating_add(args.step).min(args.max);
+            }
+        }
+        SearchStrategy::Binary => {
+            // Double until a step fails, then bisect between the last pass and that failure.
+            let mut passed = None;
+            let mut failed = None;
+            let mut concurrency = args.start;
+            loop {
+                let step = measure(&cli, concurrency, &args).await?;
+                let pass = step.breach.is_none();
+                steps.push(step);
+                if !pass {
+                    failed = Some(concurrency);
+                    break;
+                }
+                passed = Some(concurrency);
+                if concurrency == args.max {
+                    break;
+                }
+                concurrency = concurrency.saturating_mul(2).min(args.max);
+            }
+            if let (Some(mut low), Some(mut high)) = (passed, failed) {
+                while high - low > args.step {
+                    let middle = low + (high - low) / 2;
+                    let step = measure(&cli, middle, &args).await?;
+                    if step.breach.is_none() {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                    steps.push(step);
+                }
+            }
+        }
+    }
+    print_steps(steps, args.max);
+    Ok(())
+}
+
+/// Runs one step of the plan at `concurrency` for --step-duration.
+async fn measure(cli: &Cli, concurrency: u16, args: &FindMaxArgs) -> Result<Step, anyhow::Error> {
+    let mut cli = cli.clone();
+    cli.concurrency = concurrency;
+    cli.duration = Some(args.step_duration);
+    let (shared_list, actual_duration) = crate::run_benchmark(cli).await?;
+    let list = shared_list.lock().await;
+    let totals = &list.totals;
+    let summary = totals.summarize(list.target.clone(), concurrency as u64, actual_duration);
+
+    let server_errors: usize = match list.status_kind {
+        StatusKind::Http => totals
+            .status_code_dist
+            .iter()
+            .filter(|(status, _)| **status >= 500)
+            .map(|(_, count)| count)
+            .sum(),
+        _ => 0,
+    };
+    let failed = totals.total_requests - totals.successful_requests + server_errors;
+    let error_rate = if totals.total_requests == 0 {
+        100.0
+    } else {
+        failed as f64 * 100.0 / totals.total_requests as f64
+    };
+    let breach = if totals.successful_requests == 0 {
+        Some(String::from("no successful requests"))
+    } else if error_rate > args.max_error_rate {
+        Some(format!(
+            "error rate {error_rate:.2}% above {}%",
+            args.max_error_rate
+        ))
+    } else {
+        args.max_p99
+            .filter(|max_p99| summary.p99 > *max_p99)
+            .map(|max_p99| {
+                format!(
+                    "p99 {} above {}",
+                    format_duration(summary.p99),
+                    format_duration(max_p99)
+                )
+            })
+    };
+    info!(
+        "Concurrency {concurrency}: {:.2} requests/sec, p99 {}, {error_rate:.2}% errors{}",
+        summary.requests_per_sec,
+        format_duration(summary.p99),
+        breach
+            .as_ref()
+            .map_or(String::new(), |breach| format!(", {breach}"))
+    );
+    Ok(Step {
+        concurrency,
+        requests_per_sec: summary.requests_per_sec,
+        p50: summary.p50,
+        p99: summary.p99,
+        error_rate,
+        breach,
+    })
+}
+
+fn print_steps(mut steps: Vec<Step>, max: u16) {
+    steps.sort_by_key(|step| step.concurrency);
+    let mut table = Table::new();
+    table.set_titles(row![
+        "Concurrency",
+        "Requests/sec",
+        "P50",
+        "P99",
+        "Errors",
+        "Result"
+    ]);
+    for step in &steps {
+        table.add_row(row![
+            step.concurrency,
+            format!("{:.2}", step.requests_per_sec),
+            format_duration(step.p50),
+            format_duration(step.p99),
+            format!("{:.2}%", step.error_rate),
+            step.breach.as_deref().unwrap_or("ok")
+        ]);
+    }
+    println!("[Capacity Search]");
+    table.printstd();
+    println!();
+
+    let best = steps
+        .iter()
+        .filter(|step| step.breach.is_none())
+        .max_by_key(|step| step.concurrency);
+    match best {
+        Some(best) => {
+            println!(
+                "Highest sustainable concurrency: {} ({:.2} requests/sec, p99 {})",
+                best.concurrency,
+                best.requests_per_sec,
+                format_duration(best.p99)
+            );
+            if best.concurrency == max {
+                println!("The SLO held up to --max {max}; raise it to search further.");
+            }
+        }
+        None => println!("No tested concurrency met the SLO."),
+    }
+}
@@ -0,0 +1,3 @@
+pub mod find_max;
+pub mod socket;
+pub mod websocket;
@@ -0,0 +1,335 @@
+use crate::client::connector::PHASE_TCP_CONNECT;
+use crate::output::report::ResponseStatistic;
+use crate::output::report::StatisticList;
+use crate::output::report::StatusKind;
+use crate::vojo::cli::SocketArgs;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::lookup_host;
+use tokio::net::TcpStream;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+use tokio::time::Instant;
+
+// The largest payload a UDP datagram can carry.
+const MAX_DATAGRAM: usize = 65536;
+
+/// The transport `kt tcp` and `kt udp` send payloads over.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// Sends the payload from `--concurrency` sockets and times every response, returning the
+/// collected statistics with the elapsed time like an HTTP test does.
+pub async fn run(
+    args: SocketArgs,
+    protocol: Protocol,
+) -> Result<(Arc<Mutex<StatisticList>>, Duration), anyhow::Error> {
+    let payload = Payload::from_args(&args).await?;
+    let delimiter = args.delimiter.as_deref().map(unescape).transpose()?;
+    if delimiter
+        .as_ref()
+        .is_some_and(|delimiter| delimiter.is_empty())
+    {
+        return Err(anyhow!("--delimiter must not be empty"));
+    }
+    let addr = lookup_host(&args.target)
+        .await
+        .map_err(|e| anyhow!("Failed to resolve {}: {e}", args.target))?
+        .next()
+        .ok_or_else(|| anyhow!("{} did not resolve to any address", args.target))?;
+    let scheme = match protocol {
+        Protocol::Tcp => "tcp",
+        Protocol::Udp => "udp",
+    };
+    let shared_list = Arc::new(Mutex::new(StatisticList::new(
+        format!("{scheme}://{}", args.target),
+        args.concurrency as u64,
+        StatusKind::None,
+    )));
+    let now = Instant::now();
+    let connection = Connection {
+        protocol,
+        addr,
+        payload: Arc::new(payload),
+        delimiter,
+        expect_length: args.expect_length,
+        timeout: args.timeout,
+        shared_list: shared_list.clone(),
+        deadline: args.duration.map(|duration| now + duration),
+        remaining: Arc::new(AtomicI64::new(args.requests as i64)),
+    };
+
+    let mut task_list = JoinSet::new();
+    for index in 0..args.concurrency {
+        let connection = connection.clone();
+        task_list.spawn(async move { connection.run(index).await });
+    }
+    while task_list.join_next().await.is_some() {}
+    Ok((shared_list, now.elapsed()))
+}
+
+/// Everything one socket loop needs.
+#[derive(Clone)]
+struct Connection {
+    protocol: Protocol,
+    addr: SocketAddr,
+    payload: Arc<Payload>,
+    delimiter: Option<Vec<u8>>,
+    expect_length: Option<usize>,
+    timeout: Duration,
+    shared_list: Arc<Mutex<StatisticList>>,
+    // Set for a duration based test, otherwise `remaining` counts the payloads left to send
+    deadline: Option<Instant>,
+    remaining: Arc<AtomicI64>,
+}
+
+impl Connection {
+    /// Sends payloads one after another on one socket, opening a new socket after a failure
+    /// because a late or partial response would be taken for the next one.
+    async fn run(self, index: u16) {
+        let mut transport = None;
+        let mut received = vec![];
+        let mut seq = 0;
+        loop {
+            match self.deadline {
+                Some(deadline) if Instant::now() >= deadline => return,
+                Some(_) => {}
+                None if self.remaining.fetch_sub(1, Ordering::Relaxed) <= 0 => return,
+                None => {}
+            }
+            let payload = self.payload.render(index, seq);
+            seq += 1;
+            let result = self.exchange(&mut transport, &mut received, &payload).await;
+            if result.is_err() {
+                transport = None;
+                received.clear();
+            }
+            self.shared_list.lock().await.push(result, vec![]);
+        }
+    }
+
+    /// Sends one payload and waits for its response, connecting first when needed.
+    async fn exchange(
+        &self,
+        transport: &mut Option<Transport>,
+        received: &mut Vec<u8>,
+        payload: &[u8],
+    ) -> Result<ResponseStatistic, anyhow::Error> {
+        let transport = match transport {
+            Some(transport) => transport,
+            None => transport.insert(
+                timeout(self.timeout, self.connect())
+                    .await
+                    .map_err(|_| anyhow!("Connect timeout"))??,
+            ),
+        };
+        let now = Instant::now();
+        let response_len = timeout(self.timeout, async {
+            transport.send(payload).await?;
+            loop {
+                if let Some(len) = self.response_len(received) {
+                    return Ok::<_, anyhow::Error>(len);
+                }
+                transport.read(received).await?;
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("Response timeout"))??;
+        received.drain(..response_len);
+        Ok(ResponseStatistic {
+            time_cost_ns: now.elapsed().as_nanos() as u64,
+            status_code: 0,
+            content_length: response_len as u64,
+            redirects: 0,
+        })
+    }
+
+    async fn connect(&self) -> Result<Transport, anyhow::Error> {
+        match self.protocol {
+            Protocol::Tcp => {
+                let now = Instant::now();
+                let stream = TcpStream::connect(self.addr).await?;
+                stream.set_nodelay(true)?;
+                self.shared_list
+                    .lock()
+                    .await
+                    .record_phase(PHASE_TCP_CONNECT, now.elapsed().as_nanos() as u64);
+                Ok(Transport::Tcp(stream))
+            }
+            Protocol::Udp => {
+                let local: SocketAddr = if self.addr.is_ipv4() {
+                    "0.0.0.0:0".parse()?
+                } else {
+                    "[::]:0".parse()?
+                };
+                let socket = UdpSocket::bind(local).await?;
+                socket.connect(self.addr).await?;
+                Ok(Transport::Udp(socket))
+            }
+        }
+    }
+
+    /// The length of the response at the start of `received`, once all of it arrived.
+    fn response_len(&self, received: &[u8]) -> Option<usize> {
+        if let Some(delimiter) = &self.delimiter {
+            return received
+                .windows(delimiter.len())
+                .position(|window| window == delimiter.as_slice())
+                .map(|position| position + delimiter.len());
+        }
+        match self.expect_length {
+            Some(len) => (received.len() >= len).then_some(len),
+            None => (!received.is_empty()).then_some(received.len()),
+        }
+    }
+}
+
+enum Transport {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+impl Transport {
+    async fn send(&mut self, payload: &[u8]) -> Result<(), anyhow::Error> {
+        match self {
+            Transport::Tcp(stream) => stream.write_all(payload).await?,
+            Transport::Udp(socket) => {
+                socket.send(payload).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends the next read, or the next datagram, to `received`.
+    async fn read(&mut self, received: &mut Vec<u8>) -> Result<(), anyhow::Error> {
+        match self {
+            Transport::Tcp(stream) => {
+                if stream.read_buf(received).await? == 0 {
+                    return Err(anyhow!("Connection closed by server"));
+                }
+            }
+            Transport::Udp(socket) => {
+                received.reserve(MAX_DATAGRAM);
+                socket.recv_buf(received).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A payload with the {{conn}} and {{seq}} placeholders of a text template split out.
+struct Payload(Vec<Segment>);
+
+enum Segment {
+    Bytes(Vec<u8>),
+    Conn,
+    Seq,
+}
+
+impl Payload {
+    async fn from_args(args: &SocketArgs) -> Result<Payload, anyhow::Error> {
+        if let Some(text) = &args.payload {
+            return Payload::template(text);
+        }
+        let bytes = if let Some(hex) = &args.payload_hex {
+            decode_hex(hex)?
+        } else if let Some(path) = &args.payload_file {
+            tokio::fs::read(path)
+                .await
+                .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?
+        } else {
+            return Err(anyhow!(
+                "A payload is required: --payload, --payload-hex or --payload-file"
+            ));
+        };
+        Ok(Payload(vec![Segment::Bytes(bytes)]))
+    }
+
+    fn template(text: &str) -> Result<Payload, anyhow::Error> {
+        let mut segments = vec![];
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            let (placeholder, len) = if rest[start..].starts_with("{{conn}}") {
+                (Segment::Conn, "{{conn}}".len())
+            } else if rest[start..].starts_with("{{seq}}") {
+                (Segment::Seq, "{{seq}}".len())
+            } else {
+                segments.push(Segment::Bytes(unescape(&rest[..start + 2])?));
+                rest = &rest[start + 2..];
+                continue;
+            };
+            segments.push(Segment::Bytes(unescape(&rest[..start])?));
+            segments.push(placeholder);
+            rest = &rest[start + len..];
+        }
+        segments.push(Segment::Bytes(unescape(rest)?));
+        Ok(Payload(segments))
+    }
+
+    fn render(&self, connection: u16, seq: u64) -> Vec<u8> {
+        let mut bytes = vec![];
+        for segment in &self.0 {
+            match segment {
+                Segment::Bytes(literal) => bytes.extend_from_slice(literal),
+                Segment::Conn => bytes.extend_from_slice(connection.to_string().as_bytes()),
+                Segment::Seq => bytes.extend_from_slice(seq.to_string().as_bytes()),
+            }
+        }
+        bytes
+    }
+}
+
+/// Decodes the \r, \n, \t, \0, \\ and \xHH escapes of a text payload or delimiter.
+fn unescape(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let mut bytes = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| anyhow!("Invalid escape \\x{hex} in {text:?}"))?;
+                bytes.push(byte);
+            }
+            Some(other) => return Err(anyhow!("Unknown escape \\{other} in {text:?}")),
+            None => return Err(anyhow!("{text:?} ends with a lone backslash")),
+        }
+    }
+    Ok(bytes)
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(anyhow!("The hex payload has an odd number of digits"));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| anyhow!("The hex payload is not valid hex"))
+        })
+        .collect()
+}
@@ -0,0 +1,242 @@
+use crate::client;
+use crate::client::connector::KtConnector;
+use crate::client::proxy::ProxyConfig;
+use crate::client::websocket::add_handshake_headers;
+use crate::client::websocket::handshake_uri;
+use crate::client::websocket::verify_handshake;
+use crate::client::websocket::Message;
+use crate::client::websocket::WebSocketReader;
+use crate::client::websocket::WebSocketWriter;
+use crate::output::report::StatisticList;
+use crate::output::report::WebSocketStatistic;
+use crate::output::report::WebSocketSummary;
+use crate::vojo::cli::Cli;
+use http::header::PROXY_AUTHORIZATION;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Request;
+use http::Uri;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::upgrade::Upgraded;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::TokioIo;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::interval;
+use tokio::time::sleep_until;
+use tokio::time::timeout_at;
+use tokio::time::Instant;
+
+// Pause before reconnecting, so a refusing server is not hammered in a tight loop.
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+// The bounds of the pause between two messages, whatever --ws-rate asks for.
+const MIN_SEND_INTERVAL: Duration = Duration::from_micros(10);
+const MAX_SEND_INTERVAL: Duration = Duration::from_secs(3600);
+// Sent messages still waiting for an echo on one connection; older ones are given up on.
+const MAX_PENDING: usize = 1024;
+
+/// Holds `--concurrency` WebSocket connections open for `--duration`, sending the scripted
+/// messages at `--ws-rate`, and summarizes handshakes, echoes and disconnects.
+pub async fn run(cli: Cli) -> Result<WebSocketSummary, anyhow::Error> {
+    let duration = cli
+        .duration
+        .ok_or_else(|| anyhow!("WebSocket mode needs --duration, e.g., -d 30s"))?;
+    let shared_list = Arc::new(Mutex::new(StatisticList::for_cli(&cli)));
+    let connector = KtConnector::new(&cli, shared_list.clone())?;
+    let proxy = connector.proxy();
+    // The upgrade only exists in HTTP/1.1, so h2 must not be offered over TLS.
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(client::tls_config(cli.cacert.as_deref())?)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(connector);
+    let mut headers = HeaderMap::new();
+    for (key, value) in &cli.headers {
+        headers.insert(HeaderName::from_str(key)?, HeaderValue::from_str(value)?);
+    }
+    let stats = Arc::new(Mutex::new(WebSocketStatistic::default()));
+    let now = Instant::now();
+    let connection = Connection {
+        client: Client::builder(TokioExecutor::new()).build(https),
+        uri: handshake_uri(&cli.url)?,
+        headers,
+        proxy,
+        messages: Arc::new(cli.ws_message.clone()),
+        send_interval: Duration::from_secs_f64((1.0 / cli.ws_rate).clamp(
+            MIN_SEND_INTERVAL.as_secs_f64(),
+            MAX_SEND_INTERVAL.as_secs_f64(),
+        )),
+        stats: stats.clone(),
+        deadline: now + duration,
+    };
+
+    let mut task_list = JoinSet::new();
+    for index in 0..cli.concurrency {
+        let connection = connection.clone();
+        task_list.spawn(async move { connection.run(index).await });
+    }
+    while task_list.join_next().await.is_some() {}
+    let actual_duration = now.elapsed();
+
+    let phase_summaries = shared_list.lock().await.totals.phase_summaries();
+    let summary = stats.lock().await.summarize(
+        cli.target(),
+        cli.concurrency as u64,
+        actual_duration,
+        phase_summaries,
+    );
+    Ok(summary)
+}
+
+/// Everything one connection loop needs.
+#[derive(Clone)]
+struct Connection {
+    client: Client<HttpsConnector<KtConnector>, Full<Bytes>>,
+    uri: Uri,
+    headers: HeaderMap,
+    proxy: Arc<ProxyConfig>,
+    messages: Arc<Vec<String>>,
+    send_interval: Duration,
+    stats: Arc<Mutex<WebSocketStatistic>>,
+    deadline: Instant,
+}
+
+impl Connection {
+    /// Keeps one connection open until the deadline, reconnecting after failures.
+    async fn run(self, index: u16) {
+        let mut seq = 0;
+        while Instant::now() < self.deadline {
+            if let Err(e) = self.session(index, &mut seq).await {
+                self.stats.lock().await.record_error(&e);
+                sleep_until((Instant::now() + RECONNECT_DELAY).min(self.deadline)).await;
+            }
+        }
+    }
+
+    async fn handshake(&self) -> Result<Upgraded, anyhow::Error> {
+        let mut request = Request::get(self.uri.clone()).body(Full::new(Bytes::new()))?;
+        request.headers_mut().extend(self.headers.clone());
+        if let Some(value) = self.proxy.authorization_for(&self.uri) {
+            request.headers_mut().insert(PROXY_AUTHORIZATION, value);
+        }
+        let key = add_handshake_headers(request.headers_mut())?;
+        let response = self.client.request(request).await?;
+        verify_handshake(response.status(), response.headers(), &key)?;
+        Ok(hyper::upgrade::on(response).await?)
+    }
+
+    /// Runs one connection from the handshake until the deadline or a disconnect.
+    async fn session(&self, index: u16, seq: &mut u64) -> Result<(), anyhow::Error> {
+        let now = Instant::now();
+        let Ok(upgraded) = timeout_at(self.deadline, self.handshake()).await else {
+            return Ok(());
+        };
+        let upgraded = upgraded?;
+        self.stats
+            .lock()
+            .await
+            .record_handshake(now.elapsed().as_nanos() as u64);
+
+        let (read_half, write_half) = tokio::io::split(TokioIo::new(upgraded));
+        let mut writer = WebSocketWriter::new(write_half);
+        // Frames are read in their own task because a half-read frame cannot be resumed.
+        let (sender, mut receiver) = mpsc::channel(64);
+        let reader_task = tokio::spawn(async move {
+            let mut reader = WebSocketReader::new(read_half);
+            loop {
+                let message = reader.read_message().await;
+                let last = matches!(message, Ok(Message::Close) | Err(_));
+                if sender.send(message).await.is_err() || last {
+                    return;
+                }
+            }
+        });
+
+        let mut ticker = interval(self.send_interval);
+        let mut pending = VecDeque::new();
+        let result = loop {
+            tokio::select! {
+                _ = sleep_until(self.deadline) => {
+                    let _ = writer.send_close().await;
+                    break Ok(());
+                }
+                _ = ticker.tick(), if !self.messages.is_empty() => {
+                    let template = &self.messages[*seq as usize % self.messages.len()];
+                    let (text, echo_key) = render(template, index, *seq);
+                    *seq += 1;
+                    if let Err(e) = writer.send_text(&text).await {
+                        break Err(e);
+                    }
+                    pending.push_back((echo_key, Instant::now()));
+                    if pending.len() > MAX_PENDING {
+                        pending.pop_front();
+                    }
+                    self.stats.lock().await.messages_sent += 1;
+                }
+                message = receiver.recv() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        self.received(&text, text.len(), &mut pending).await;
+                    }
+                    Some(Ok(Message::Binary(data))) => {
+                        let text = String::from_utf8_lossy(&data);
+                        self.received(&text, data.len(), &mut pending).await;
+                    }
+                    Some(Ok(Message::Ping(payload))) => {
+                        if let Err(e) = writer.send_pong(&payload).await {
+                            break Err(e);
+                        }
+                    }
+                    Some(Ok(Message::Pong)) => {}
+                    Some(Ok(Message::Close)) | None => {
+                        break Err(anyhow!("Connection closed by server"));
+                    }
+                    Some(Err(e)) => break Err(e),
+                }
+            }
+        };
+        reader_task.abort();
+        if result.is_err() {
+            self.stats.lock().await.disconnects += 1;
+        }
+        result
+    }
+
+    /// Counts a received message and matches it to the oldest sent message it echoes.
+    async fn received(&self, text: &str, len: usize, pending: &mut VecDeque<(String, Instant)>) {
+        let mut stats = self.stats.lock().await;
+        stats.messages_received += 1;
+        stats.bytes_received += len as u64;
+        let echoed = pending
+            .iter()
+            .position(|(key, _)| !key.is_empty() && text.contains(key.as_str()));
+        if let Some((_, sent)) = echoed.and_then(|position| pending.remove(position)) {
+            stats.record_round_trip(sent.elapsed().as_nanos() as u64);
+        }
+    }
+}
+
+/// Fills in a message template and returns it with the text its echo is recognized by:
+/// the unique id when the template has one, otherwise the whole message.
+fn render(template: &str, connection: u16, seq: u64) -> (String, String) {
+    // Fixed width, so one id is never a prefix of another.
+    let id = format!("kt-{connection:05}-{seq:010}");
+    let text = template
+        .replace("{{conn}}", &connection.to_string())
+        .replace("{{seq}}", &seq.to_string())
+        .replace("{{id}}", &id);
+    let echo_key = if template.contains("{{id}}") {
+        id
+    } else {
+        text.clone()
+    };
+    (text, echo_key)
+}
@@ -0,0 +1,377 @@
+use crate::vojo::cli::Cli;
+use crate::vojo::cli::ExportFormat;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+// wrk needs a duration, so plans with a request count get this one.
+const WRK_DEFAULT_SECONDS: u64 = 30;
+const WRK_MAX_THREADS: u16 = 4;
+
+/// The body of the plan, with `@path` read from a file like kt does.
+enum Body<'a> {
+    None,
+    Text(&'a str),
+    File(&'a str),
+}
+
+/// Writes a script sending the request of `cli` with the same concurrency and duration or
+/// request count. Client options the tool has no counterpart for are listed in its header.
+pub fn render(cli: &Cli, format: ExportFormat) -> Result<String, anyhow::Error> {
+    if cli.is_websocket() || cli.grpc.is_some() || !cli.graphql.is_empty() {
+        return Err(anyhow!(
+            "WebSocket, gRPC and GraphQL plans cannot be exported"
+        ));
+    }
+    match format {
+        ExportFormat::Curl => curl_script(cli),
+        ExportFormat::K6 => k6_script(cli),
+        ExportFormat::Wrk => wrk_script(cli),
+    }
+}
+
+fn curl_script(cli: &Cli) -> Result<String, anyhow::Error> {
+    let mut args: Vec<String> = ["curl", "-s", "-o", "/dev/null", "-w"]
+        .map(String::from)
+        .to_vec();
+    args.push(String::from("%{http_code} %{time_total}\\n"));
+    if let Some(method) = &cli.method {
+        args.extend([String::from("-X"), method.clone()]);
+    }
+    for (key, value) in headers(cli) {
+        args.extend([String::from("-H"), format!("{key}: {value}")]);
+    }
+    match body(cli) {
+        Body::None => {}
+        Body::Text(text) => args.extend([String::from("--data-binary"), text.to_string()]),
+        Body::File(path) => args.extend([String::from("--data-binary"), format!("@{path}")]),
+    }
+    let mut translated = vec![];
+    // curl takes the same field syntax, but cannot pick a file from a directory.
+    let picks_file = cli.form.iter().any(|field| {
+        field
+            .split_once("=@")
+            .is_some_and(|(_, file)| Path::new(file.split(';').next().unwrap_or_default()).is_dir())
+    });
+    if !cli.form.is_empty() && !picks_file {
+        for field in &cli.form {
+            args.extend([String::from("-F"), field.clone()]);
+        }
+        translated.push("--form");
+    }
+    if cli.follow_redirects {
+        args.extend([
+            String::from("-L"),
+            String::from("--max-redirs"),
+            cli.max_redirects.to_string(),
+        ]);
+        translated.push("--follow-redirects");
+    }
+    if let Some(proxy) = &cli.proxy {
+        args.extend([String::from("--proxy"), proxy.clone()]);
+        translated.push("--proxy");
+    }
+    if let Some(path) = &cli.unix_socket {
+        args.extend([String::from("--unix-socket"), path.display().to_string()]);
+        translated.push("--unix-socket");
+    }
+    if !cli.accept_encoding.is_empty() {
+        args.push(String::from("--compressed"));
+        translated.push("--accept-encoding");
+    }
+    if cli.http2_prior_knowledge {
+        args.push(String::from("--http2-prior-knowledge"));
+        translated.push("--http2-prior-knowledge");
+    }
+    if cli.http3 {
+        args.push(String::from("--http3-only"));
+        translated.push("--http3");
+    }
+    if let Some(path) = &cli.cacert {
+        args.extend([String::from("--cacert"), path.display().to_string()]);
+        translated.push("--cacert");
+    }
+    for resolve in &cli.resolve {
+        let addrs: Vec<String> = resolve
+            .addrs
+            .iter()
+            .map(|addr| match addr {
+                std::net::IpAddr::V4(addr) => addr.to_string(),
+                std::net::IpAddr::V6(addr) => format!("[{addr}]"),
+            })
+            .collect();
+        args.extend([
+            String::from("--resolve"),
+            format!("{}:{}:{}", resolve.host, resolve.port, addrs.join(",")),
+        ]);
+        translated.push("--resolve");
+    }
+    if let Some(interface) = &cli.interface {
+        args.extend([String::from("--interface"), interface.clone()]);
+        translated.push("--interface");
+    }
+    args.push(cli.url.to_string());
+    let command = shell_join(&args)?;
+
+    let mut script = String::from("#!/bin/sh\n");
+    writeln!(script, "# Exported from kt: {}.", load_shape(cli))?;
+    writeln!(
+        script,
+        "# Prints the status code and total time of every request."
+    )?;
+    if let Some(options) = untranslated(cli, &translated) {
+        writeln!(script, "# Not translated: {options}")?;
+    }
+    match cli.duration {
+        Some(duration) => {
+            writeln!(script, "end=$(($(date +%s) + {}))", whole_seconds(duration))?;
+            writeln!(script, "for worker in $(seq {}); do", cli.concurrency)?;
+            writeln!(script, "  while [ \"$(date +%s)\" -lt \"$end\" ]; do")?;
+        }
+        None => {
+            // Worker w sends (requests + w - 1) / concurrency, which adds up to the total.
+            writeln!(script, "for worker in $(seq {}); do", cli.concurrency)?;
+            writeln!(
+                script,
+                "  for i in $(seq $((({} + worker - 1) / {}))); do",
+                cli.requests, cli.concurrency
+            )?;
+        }
+    }
+    writeln!(script, "    {command}")?;
+    writeln!(script, "  done &")?;
+    writeln!(script, "done")?;
+    writeln!(script, "wait")?;
+    Ok(script)
+}
+
+fn k6_script(cli: &Cli) -> Result<String, anyhow::Error> {
+    let mut translated = vec!["--follow-redirects"];
+    let mut script = String::from("import http from 'k6/http';\n\n");
+    writeln!(script, "// Exported from kt: {}.", load_shape(cli))?;
+    let mut options = String::new();
+    writeln!(options, "export const options = {{")?;
+    writeln!(options, "  vus: {},", cli.concurrency)?;
+    match cli.duration {
+        Some(duration) => writeln!(options, "  duration: '{}',", k6_duration(duration))?,
+        None => writeln!(options, "  iterations: {},", cli.requests)?,
+    }
+    // k6 follows redirects unless told otherwise, kt only with --follow-redirects.
+    let max_redirects = if cli.follow_redirects {
+        cli.max_redirects
+    } else {
+        0
+    };
+    writeln!(options, "  maxRedirects: {max_redirects},")?;
+    if !cli.resolve.is_empty() {
+        writeln!(options, "  hosts: {{")?;
+        for resolve in &cli.resolve {
+            let key = format!("{}:{}", resolve.host, resolve.port);
+            let addr = std::net::SocketAddr::new(resolve.addrs[0], resolve.port).to_string();
+            writeln!(options, "    {}: {},", js_string(&key)?, js_string(&addr)?)?;
+        }
+        writeln!(options, "  }},")?;
+        translated.push("--resolve");
+    }
+    writeln!(options, "}};")?;
+    if let Some(options) = untranslated(cli, &translated) {
+        writeln!(script, "// Not translated: {options}")?;
+    }
+    writeln!(script, "{options}")?;
+
+    match body(cli) {
+        Body::None => writeln!(script, "const body = null;")?,
+        Body::Text(text) => writeln!(script, "const body = {};", js_string(text)?)?,
+        Body::File(path) => {
+            writeln!(script, "// open() reads paths relative to this script.")?;
+            writeln!(script, "const body = open({}, 'b');", js_string(path)?)?;
+        }
+    }
+    writeln!(script, "const params = {{")?;
+    writeln!(script, "  headers: {{")?;
+    for (key, value) in headers(cli) {
+        writeln!(script, "    {}: {},", js_string(&key)?, js_string(&value)?)?;
+    }
+    writeln!(script, "  }},")?;
+    writeln!(script, "}};\n")?;
+    writeln!(script, "export default function () {{")?;
+    writeln!(
+        script,
+        "  http.request({}, {}, body, params);",
+        js_string(cli.method())?,
+        js_string(&cli.url.to_string())?
+    )?;
+    writeln!(script, "}}")?;
+    Ok(script)
+}
+
+fn wrk_script(cli: &Cli) -> Result<String, anyhow::Error> {
+    let seconds = cli.duration.map_or(WRK_DEFAULT_SECONDS, whole_seconds);
+    let threads = cli.concurrency.clamp(1, WRK_MAX_THREADS);
+    let command = shell_join(&[
+        String::from("wrk"),
+        format!("-t{threads}"),
+        format!("-c{}", cli.concurrency),
+        format!("-d{seconds}s"),
+        String::from("-s"),
+        String::from("kt.lua"),
+        cli.url.to_string(),
+    ])?;
+    let mut script = String::new();
+    writeln!(script, "-- Exported from kt: {}.", load_shape(cli))?;
+    writeln!(script, "-- Save as kt.lua and run:")?;
+    writeln!(script, "--   {command}")?;
+    if cli.duration.is_none() {
+        writeln!(
+            script,
+            "-- wrk has no request count, so -d{seconds}s stands in for -r {}.",
+            cli.requests
+        )?;
+    }
+    if let Some(options) = untranslated(cli, &[]) {
+        writeln!(script, "-- Not translated: {options}")?;
+    }
+    writeln!(script, "wrk.method = {}", lua_string(cli.method()))?;
+    match body(cli) {
+        Body::None => {}
+        Body::Text(text) => writeln!(script, "wrk.body = {}", lua_string(text))?,
+        Body::File(path) => {
+            writeln!(
+                script,
+                "local file = assert(io.open({}, \"rb\"))",
+                lua_string(path)
+            )?;
+            writeln!(script, "wrk.body = file:read(\"*a\")")?;
+            writeln!(script, "file:close()")?;
+        }
+    }
+    for (key, value) in headers(cli) {
+        writeln!(
+            script,
+            "wrk.headers[{}] = {}",
+            lua_string(&key),
+            lua_string(&value)
+        )?;
+    }
+    Ok(script)
+}
+
+/// The headers kt sends besides its own, including the form content type it adds to bodies.
+fn headers(cli: &Cli) -> Vec<(String, String)> {
+    let mut headers = cli.headers.clone();
+    if cli.body.is_some()
+        && !headers
+            .iter()
+            .any(|(key, _)| key.eq_ignore_ascii_case("content-type"))
+    {
+        headers.insert(
+            0,
+            (
+                String::from("Content-Type"),
+                String::from("application/x-www-form-urlencoded"),
+            ),
+        );
+    }
+    headers
+}
+
+fn body(cli: &Cli) -> Body<'_> {
+    match cli.body.as_deref() {
+        None => Body::None,
+        Some(body) => match body.strip_prefix('@') {
+            Some(path) => Body::File(path),
+            None => Body::Text(body),
+        },
+    }
+}
+
+/// The client options set in `cli` that are not among `translated`, comma separated.
+fn untranslated(cli: &Cli, translated: &[&str]) -> Option<String> {
+    let options: Vec<&str> = [
+        ("--warmup", cli.warmup.is_some()),
+        ("--warmup-requests", cli.warmup_requests.is_some()),
+        ("--think-time", cli.think_time.is_some()),
+        ("--pacing", cli.pacing.is_some()),
+        ("--retries", cli.retries > 0),
+        ("--form", !cli.form.is_empty()),
+        ("--compress-body", cli.compress_body.is_some()),
+        ("--accept-encoding", !cli.accept_encoding.is_empty()),
+        ("--cookie-jar", cli.cookie_jar.is_some()),
+        ("--follow-redirects", cli.follow_redirects),
+        ("--proxy", cli.proxy.is_some()),
+        ("--unix-socket", cli.unix_socket.is_some()),
+        ("--http2-prior-knowledge", cli.http2_prior_knowledge),
+        ("--http3", cli.http3),
+        ("--zero-rtt", cli.zero_rtt),
+        (
+            "--http3-reconnect-after",
+            cli.http3_reconnect_after.is_some(),
+        ),
+        ("--cacert", cli.cacert.is_some()),
+        ("--resolve", !cli.resolve.is_empty()),
+        ("--spread-ips", cli.spread_ips),
+        ("--local-address", !cli.local_address.is_empty()),
+        ("--interface", cli.interface.is_some()),
+        ("--stream", cli.stream),
+    ]
+    .into_iter()
+    .filter(|(option, set)| *set && !translated.contains(option))
+    .map(|(option, _)| option)
+    .collect();
+    (!options.is_empty()).then(|| options.join(", "))
+}
+
+fn load_shape(cli: &Cli) -> String {
+    match cli.duration {
+        Some(duration) => format!(
+            "{} workers for {}",
+            cli.concurrency,
+            humantime::format_duration(duration)
+        ),
+        None => format!(
+            "{} workers sending {} requests",
+            cli.concurrency, cli.requests
+        ),
+    }
+}
+
+fn whole_seconds(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+fn k6_duration(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+fn shell_join(args: &[String]) -> Result<String, anyhow::Error> {
+    let quoted = args
+        .iter()
+        .map(|arg| shlex::try_quote(arg).map_err(|e| anyhow!("Cannot quote {arg:?}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(quoted.join(" "))
+}
+
+/// A JavaScript string literal; JSON strings are valid ones.
+fn js_string(text: &str) -> Result<String, anyhow::Error> {
+    Ok(serde_json::to_string(text)?)
+}
+
+/// A Lua string literal, with bytes outside printable ASCII written as decimal escapes.
+fn lua_string(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in text.bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{byte:03}")),
+        }
+    }
+    literal.push('"');
+    literal
+}
@@ -1,145 +1,909 @@
+use crate::client::grpc;
 use crate::vojo::cli::Cli;
 use hdrhistogram::Histogram;
 use itertools::Itertools;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::hash::Hash;
 use std::time::Duration;
+use std::time::Instant;
+
+// 预热摘要里按秒的平均延迟最多展示这么多段，更长的预热合并相邻的秒
+const MAX_WARMUP_SLICES: usize = 20;
+
 pub struct StatisticList {
     pub response_list: Vec<Result<ResponseStatistic, anyhow::Error>>,
-    pub cli: Cli,
+    // 报告和指标里显示的压测目标
+    pub target: String,
+    pub concurrency: u64,
+    pub status_kind: StatusKind,
+    // 边压测边累计的统计，供实时指标和 agent 上报使用
+    pub totals: StatisticAggregate,
+    // 开启 --warmup 或 --warmup-requests 时，预热期间的结果记在这里而不是 totals
+    pub warmup: Option<Warmup>,
+    // 开启 --think-time 或 --pacing 时报告里加上每个 worker 的实际速率
+    pub paced: bool,
+}
+
+/// 预热阶段的统计。预热按时间或按请求数结束，结束后的结果才计入正式报告。
+pub struct Warmup {
+    started: Instant,
+    until: Option<Instant>,
+    remaining: u64,
+    // 预热结束时记下实际耗时
+    elapsed: Option<Duration>,
+    totals: StatisticAggregate,
+    // 每秒成功请求的 (数量, 总耗时)，用来看冷启动持续了多久
+    per_second: Vec<(u64, u64)>,
 }
+
+impl Warmup {
+    fn is_running(&mut self) -> bool {
+        if self.elapsed.is_some() {
+            return false;
+        }
+        match self.until {
+            Some(until) if Instant::now() >= until => {
+                self.elapsed = Some(until.duration_since(self.started));
+                false
+            }
+            Some(_) => true,
+            None => self.remaining > 0,
+        }
+    }
+
+    fn record(&mut self, result: &Result<ResponseStatistic, anyhow::Error>, labels: &Labels) {
+        self.totals.record(result, labels);
+        if let Ok(item) = result {
+            let second = self.started.elapsed().as_secs() as usize;
+            if self.per_second.len() <= second {
+                self.per_second.resize(second + 1, (0, 0));
+            }
+            self.per_second[second].0 += 1;
+            self.per_second[second].1 += item.time_cost_ns;
+        }
+        if self.until.is_none() {
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                self.elapsed = Some(self.started.elapsed());
+            }
+        }
+    }
+}
+/// 结果里的状态码是什么含义，决定报告怎么展示状态码分布。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+    Http,
+    Grpc,
+    // 原始 TCP/UDP 没有状态码，不展示状态码分布
+    None,
+}
+
+impl StatusKind {
+    pub fn of(cli: &Cli) -> Self {
+        if cli.grpc.is_some() {
+            StatusKind::Grpc
+        } else {
+            StatusKind::Http
+        }
+    }
+}
+
+/// 附加在每个结果上的分组标签，例如 ("Remote IP", "10.0.0.1")，报告按维度分别统计。
+pub type Labels = Vec<(&'static str, String)>;
+
 pub struct ResponseStatistic {
     pub time_cost_ns: u64,
     pub status_code: u16,
     pub content_length: u64,
+    // 跟随重定向的次数，未开启 --follow-redirects 时为 0
+    pub redirects: u32,
+}
+
+/// 一个流式响应的事件时间线，时间都从发出请求开始算。
+pub struct StreamResult {
+    pub first_event_ns: Option<u64>,
+    pub event_gaps_ns: Vec<u64>,
+    pub lifetime_ns: u64,
+    pub events: u64,
+    pub bytes: u64,
+    // 流在服务端正常结束或测试截止之前因错误中断
+    pub interrupted: bool,
 }
 // 【优化】用于存放所有计算后结果的结构体
 #[derive(Debug)]
 pub struct BenchmarkSummary {
     // 会话信息
-    url: String, // 新增：测试的目标 URL
-    concurrency: u64,
-    actual_duration: Duration, // 改为实际测试时长
+    pub url: String, // 新增：测试的目标 URL
+    pub concurrency: u64,
+    pub actual_duration: Duration, // 改为实际测试时长
 
     // 吞吐量
-    requests_per_sec: f64,
-    data_transfer_rate_mbps: f64,
+    pub requests_per_sec: f64,
+    pub data_transfer_rate_mbps: f64,
 
     // 延迟 (使用 Duration 类型)
-    average_latency: Duration,
-    latency_std_dev: Duration,
-    slowest: Duration,
-    fastest: Duration,
+    pub average_latency: Duration,
+    pub latency_std_dev: Duration,
+    pub slowest: Duration,
+    pub fastest: Duration,
 
     // 延迟百分位数
-    p50: Duration,
-    p90: Duration,
-    p95: Duration,
-    p99: Duration,
-    p99_9: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub p99_9: Duration,
 
     // 数据传输
-    total_data: u64,
-    avg_size_per_request: f64,
+    pub total_data: u64,
+    pub avg_size_per_request: f64,
+    // 开启 --accept-encoding 时才有
+    pub decoded_data: Option<u64>,
 
     // 结果分布
-    total_requests: usize,
-    successful_requests: usize,
-    status_code_dist: HashMap<u16, usize>,
-    error_dist: HashMap<String, usize>,
+    pub total_requests: usize,
+    pub successful_requests: usize,
+    pub status_code_dist: HashMap<u16, usize>,
+    pub error_dist: HashMap<String, usize>,
+    pub redirect_dist: HashMap<u32, usize>,
+
+    // 建立连接各阶段的耗时
+    pub phase_summaries: BTreeMap<String, PhaseSummary>,
+
+    // 按标签维度拆分的统计，只保留有多个取值的维度
+    pub breakdowns: BTreeMap<String, Vec<(String, BreakdownSummary)>>,
+
+    // 开启 --stream 且有流结束时才有
+    pub stream: Option<StreamSummary>,
+
+    // 运行多步骤旅程时才有
+    pub journey: Option<BreakdownSummary>,
+
+    // 开启 --retries 时才有
+    pub retry: Option<RetryStatistic>,
+
+    // 开启 --form 时才有
+    pub upload: Option<UploadSummary>,
+
+    // gRPC 模式下 status_code_dist 里是 gRPC 状态码而不是 HTTP 状态码
+    pub status_kind: StatusKind,
+
+    // 为 true 时吞吐部分显示每个 worker 的速率
+    pub paced: bool,
+}
+
+#[derive(Debug)]
+pub struct BreakdownSummary {
+    pub total_requests: u64,
+    pub errors: u64,
+    pub average: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+}
+
+#[derive(Debug)]
+pub struct StreamSummary {
+    pub streams: u64,
+    pub events: u64,
+    pub interrupted: u64,
+    pub events_per_sec_per_stream: f64,
+    pub first_event: PhaseSummary,
+    pub event_gap: PhaseSummary,
+    pub lifetime: PhaseSummary,
+}
+
+#[derive(Debug)]
+pub struct PhaseSummary {
+    pub count: u64,
+    pub average: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub slowest: Duration,
 }
 
 impl StatisticList {
+    pub fn new(target: String, concurrency: u64, status_kind: StatusKind) -> Self {
+        StatisticList {
+            response_list: vec![],
+            target,
+            concurrency,
+            status_kind,
+            totals: StatisticAggregate::default(),
+            warmup: None,
+            paced: false,
+        }
+    }
+
+    /// 从 `started` 开始预热，持续 `duration` 或直到记录了 `requests` 个结果。
+    pub fn start_warmup(
+        &mut self,
+        started: Instant,
+        duration: Option<Duration>,
+        requests: Option<u64>,
+    ) {
+        if duration.is_none() && requests.is_none() {
+            return;
+        }
+        self.warmup = Some(Warmup {
+            started,
+            until: duration.map(|duration| started + duration),
+            remaining: requests.unwrap_or(0),
+            elapsed: None,
+            totals: StatisticAggregate::default(),
+            per_second: vec![],
+        });
+    }
+
+    /// 压测结束时调用，返回预热用掉的时间，计算吞吐时要从总时长里扣掉。
+    /// 预热到压测结束都没完成时，整个 `elapsed` 都算预热。
+    pub fn end_warmup(&mut self, elapsed: Duration) -> Duration {
+        match &mut self.warmup {
+            Some(warmup) => *warmup.elapsed.get_or_insert(elapsed),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// 仍在预热时返回预热统计，结果应记到这里。
+    fn running_warmup(&mut self) -> Option<&mut Warmup> {
+        let warmup = self.warmup.as_mut()?;
+        warmup.is_running().then_some(warmup)
+    }
+
+    pub fn for_cli(cli: &Cli) -> Self {
+        let mut list = Self::new(cli.target(), cli.concurrency as u64, StatusKind::of(cli));
+        list.paced = cli.think_time.is_some() || cli.pacing.is_some();
+        list
+    }
+
+    /// 记录一次新建连接某个阶段的耗时，例如 TCP 建连或代理握手。
+    pub fn record_phase(&mut self, phase: &str, time_cost_ns: u64) {
+        let totals = match self.running_warmup() {
+            Some(warmup) => &mut warmup.totals,
+            None => &mut self.totals,
+        };
+        totals
+            .phase_dist
+            .entry(phase.to_string())
+            .or_default()
+            .record(time_cost_ns);
+    }
+
+    pub fn push(&mut self, result: Result<ResponseStatistic, anyhow::Error>, labels: Labels) {
+        if let Some(warmup) = self.running_warmup() {
+            warmup.record(&result, &labels);
+            return;
+        }
+        self.totals.record(&result, &labels);
+        self.response_list.push(result);
+    }
+
+    pub fn record_stream(&mut self, stream: &StreamResult) {
+        match self.running_warmup() {
+            Some(warmup) => warmup.totals.stream.record(stream),
+            None => self.totals.stream.record(stream),
+        }
+    }
+
+    /// 开启 --retries 时每个请求记录一次：重试了几次，最终是否成功。
+    pub fn record_retries(&mut self, retries: u32, success: bool) {
+        match self.running_warmup() {
+            Some(warmup) => warmup.totals.retry.record(retries, success),
+            None => self.totals.retry.record(retries, success),
+        }
+    }
+
+    /// 开启 --accept-encoding 时记录一个响应体解压后的大小。
+    pub fn record_decoded(&mut self, bytes: u64) {
+        match self.running_warmup() {
+            Some(warmup) => warmup.totals.decoded_data += bytes,
+            None => self.totals.decoded_data += bytes,
+        }
+    }
+
+    /// 开启 --form 时记录一个请求体的大小。
+    pub fn record_upload(&mut self, bytes: u64) {
+        match self.running_warmup() {
+            Some(warmup) => warmup.totals.upload.record(bytes),
+            None => self.totals.upload.record(bytes),
+        }
+    }
+
+    /// 记录一次完整的旅程，失败时 `time_cost_ns` 为 None。
+    pub fn record_journey(&mut self, time_cost_ns: Option<u64>) {
+        match self.running_warmup() {
+            Some(warmup) => warmup.totals.journey.record(time_cost_ns),
+            None => self.totals.journey.record(time_cost_ns),
+        }
+    }
+
+    /// 预热阶段的摘要，没开启预热或预热没有结果时为 None。
+    pub fn warmup_summary(&self) -> Option<WarmupSummary> {
+        let warmup = self.warmup.as_ref()?;
+        let totals = &warmup.totals;
+        if totals.total_requests == 0 {
+            return None;
+        }
+        let successful = totals.successful_requests.max(1) as u64;
+        // 合并相邻的秒，让展示的段数不超过 MAX_WARMUP_SLICES
+        let seconds_per_slice = warmup.per_second.len().div_ceil(MAX_WARMUP_SLICES).max(1);
+        let slice_averages = warmup
+            .per_second
+            .chunks(seconds_per_slice)
+            .map(|chunk| {
+                let (count, total_ns) = chunk
+                    .iter()
+                    .fold((0, 0), |(count, total), (c, t)| (count + c, total + t));
+                (count > 0).then(|| Duration::from_nanos(total_ns / count))
+            })
+            .collect();
+        Some(WarmupSummary {
+            duration: warmup.elapsed.unwrap_or_default(),
+            total_requests: totals.total_requests,
+            errors: totals.total_requests - totals.successful_requests,
+            average: Duration::from_nanos(totals.total_time_cost_ns / successful),
+            p50: Duration::from_nanos(totals.histogram.value_at_quantile(0.50)),
+            p99: Duration::from_nanos(totals.histogram.value_at_quantile(0.99)),
+            seconds_per_slice,
+            slice_averages,
+        })
+    }
+
     /// 分析压测结果。
     /// 【重要】传入实际的测试总耗时，以获得最精确的 RPS 计算。
     pub fn analyze(&self, actual_duration: Duration) -> Option<BenchmarkSummary> {
         if self.response_list.is_empty() {
             return None;
         }
+        let mut summary =
+            self.totals
+                .summarize(self.target.clone(), self.concurrency, actual_duration);
+        summary.status_kind = self.status_kind;
+        summary.paced = self.paced;
+        Some(summary)
+    }
+}
+
+/// 可合并的统计结果，分布式压测时由各个 agent 上报给 controller。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatisticAggregate {
+    #[serde(with = "histogram_serde")]
+    pub histogram: Histogram<u64>,
+    pub total_requests: usize,
+    pub successful_requests: usize,
+    pub total_data: u64,
+    pub total_time_cost_ns: u64,
+    pub total_time_cost_ns_squared: f64,
+    pub slowest_ns: u64,
+    pub fastest_ns: u64,
+    pub status_code_dist: HashMap<u16, usize>,
+    pub error_dist: HashMap<String, usize>,
+    pub redirect_dist: HashMap<u32, usize>,
+    pub phase_dist: BTreeMap<String, PhaseStatistic>,
+    // 维度 -> 标签值 -> 统计
+    pub breakdowns: BTreeMap<String, BTreeMap<String, BreakdownStatistic>>,
+    pub stream: StreamStatistic,
+    // 多步骤旅程的整体耗时，失败的旅程只计入 errors
+    pub journey: BreakdownStatistic,
+    pub retry: RetryStatistic,
+    // 开启 --accept-encoding 时解压后的响应体字节数，total_data 是传输的字节数
+    pub decoded_data: u64,
+    pub upload: UploadStatistic,
+}
+
+/// 开启 --retries 时的重试计数，区分首次就成功和重试后才成功的请求。
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RetryStatistic {
+    // 额外发出的重试次数
+    pub retries: u64,
+    pub first_attempt_successes: u64,
+    pub retried_successes: u64,
+    // 重试过但最终仍然失败的请求
+    pub retried_failures: u64,
+}
+
+impl RetryStatistic {
+    fn record(&mut self, retries: u32, success: bool) {
+        self.retries += retries as u64;
+        match (retries, success) {
+            (0, true) => self.first_attempt_successes += 1,
+            (_, true) => self.retried_successes += 1,
+            (0, false) => {}
+            (_, false) => self.retried_failures += 1,
+        }
+    }
 
-        let mut status_code_dist = HashMap::new();
-        let mut error_dist = HashMap::new();
-        let mut hist = Histogram::<u64>::new(3).unwrap();
+    fn add(&mut self, other: &RetryStatistic) {
+        self.retries += other.retries;
+        self.first_attempt_successes += other.first_attempt_successes;
+        self.retried_successes += other.retried_successes;
+        self.retried_failures += other.retried_failures;
+    }
 
-        let mut total_data = 0;
-        let mut successful_times_ns = Vec::new();
+    fn subtract(&mut self, earlier: &RetryStatistic) {
+        self.retries -= earlier.retries;
+        self.first_attempt_successes -= earlier.first_attempt_successes;
+        self.retried_successes -= earlier.retried_successes;
+        self.retried_failures -= earlier.retried_failures;
+    }
+}
 
-        for result in &self.response_list {
-            match result {
-                Ok(item) => {
-                    successful_times_ns.push(item.time_cost_ns);
-                    hist.record(item.time_cost_ns).unwrap();
-                    total_data += item.content_length;
-                    *status_code_dist.entry(item.status_code).or_insert(0) += 1;
-                }
-                Err(e) => {
-                    *error_dist.entry(e.to_string()).or_insert(0) += 1;
-                }
+/// 开启 --form 时每个请求上传的请求体大小。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UploadStatistic {
+    #[serde(with = "histogram_serde")]
+    pub histogram: Histogram<u64>,
+    pub total_bytes: u64,
+}
+
+impl Default for UploadStatistic {
+    fn default() -> Self {
+        Self {
+            histogram: Histogram::<u64>::new(3).unwrap(),
+            total_bytes: 0,
+        }
+    }
+}
+
+impl UploadStatistic {
+    fn record(&mut self, bytes: u64) {
+        self.histogram.record(bytes).unwrap();
+        self.total_bytes += bytes;
+    }
+
+    fn add(&mut self, other: &UploadStatistic) {
+        self.histogram.add(&other.histogram).unwrap();
+        self.total_bytes += other.total_bytes;
+    }
+
+    fn subtract(&mut self, earlier: &UploadStatistic) {
+        self.histogram.subtract(&earlier.histogram).unwrap();
+        self.total_bytes -= earlier.total_bytes;
+    }
+
+    fn summarize(&self) -> Option<UploadSummary> {
+        let uploads = self.histogram.len();
+        if uploads == 0 {
+            return None;
+        }
+        Some(UploadSummary {
+            uploads,
+            total_bytes: self.total_bytes,
+            average: self.total_bytes / uploads,
+            smallest: self.histogram.min(),
+            p50: self.histogram.value_at_quantile(0.50),
+            p99: self.histogram.value_at_quantile(0.99),
+            largest: self.histogram.max(),
+        })
+    }
+}
+
+/// 上传大小的分布，单位是字节。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UploadSummary {
+    pub uploads: u64,
+    pub total_bytes: u64,
+    pub average: u64,
+    pub smallest: u64,
+    pub p50: u64,
+    pub p99: u64,
+    pub largest: u64,
+}
+
+/// 某个标签值下的请求统计，错误只计数不计入延迟。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BreakdownStatistic {
+    #[serde(with = "histogram_serde")]
+    pub histogram: Histogram<u64>,
+    pub total_time_cost_ns: u64,
+    pub total_requests: u64,
+    pub errors: u64,
+}
+
+impl Default for BreakdownStatistic {
+    fn default() -> Self {
+        Self {
+            histogram: Histogram::<u64>::new(3).unwrap(),
+            total_time_cost_ns: 0,
+            total_requests: 0,
+            errors: 0,
+        }
+    }
+}
+
+impl BreakdownStatistic {
+    fn record(&mut self, time_cost_ns: Option<u64>) {
+        self.total_requests += 1;
+        match time_cost_ns {
+            Some(time_cost_ns) => {
+                self.histogram.record(time_cost_ns).unwrap();
+                self.total_time_cost_ns += time_cost_ns;
+            }
+            None => self.errors += 1,
+        }
+    }
+
+    fn add(&mut self, other: &BreakdownStatistic) {
+        self.histogram.add(&other.histogram).unwrap();
+        self.total_time_cost_ns += other.total_time_cost_ns;
+        self.total_requests += other.total_requests;
+        self.errors += other.errors;
+    }
+
+    fn subtract(&mut self, earlier: &BreakdownStatistic) {
+        self.histogram.subtract(&earlier.histogram).unwrap();
+        self.total_time_cost_ns -= earlier.total_time_cost_ns;
+        self.total_requests -= earlier.total_requests;
+        self.errors -= earlier.errors;
+    }
+
+    fn summarize(&self) -> BreakdownSummary {
+        let successful = self.histogram.len();
+        BreakdownSummary {
+            total_requests: self.total_requests,
+            errors: self.errors,
+            average: Duration::from_nanos(self.total_time_cost_ns / successful.max(1)),
+            p50: Duration::from_nanos(self.histogram.value_at_quantile(0.50)),
+            p99: Duration::from_nanos(self.histogram.value_at_quantile(0.99)),
+        }
+    }
+}
+
+/// 连接阶段耗时的直方图，每个新建连接记录一次。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhaseStatistic {
+    #[serde(with = "histogram_serde")]
+    pub histogram: Histogram<u64>,
+    pub total_time_cost_ns: u64,
+}
+
+impl Default for PhaseStatistic {
+    fn default() -> Self {
+        Self {
+            histogram: Histogram::<u64>::new(3).unwrap(),
+            total_time_cost_ns: 0,
+        }
+    }
+}
+
+impl PhaseStatistic {
+    fn record(&mut self, time_cost_ns: u64) {
+        self.histogram.record(time_cost_ns).unwrap();
+        self.total_time_cost_ns += time_cost_ns;
+    }
+
+    fn add(&mut self, other: &PhaseStatistic) {
+        self.histogram.add(&other.histogram).unwrap();
+        self.total_time_cost_ns += other.total_time_cost_ns;
+    }
+
+    fn subtract(&mut self, earlier: &PhaseStatistic) {
+        self.histogram.subtract(&earlier.histogram).unwrap();
+        self.total_time_cost_ns -= earlier.total_time_cost_ns;
+    }
+
+    fn summarize(&self) -> PhaseSummary {
+        let count = self.histogram.len();
+        PhaseSummary {
+            count,
+            average: Duration::from_nanos(self.total_time_cost_ns / count.max(1)),
+            p50: Duration::from_nanos(self.histogram.value_at_quantile(0.50)),
+            p99: Duration::from_nanos(self.histogram.value_at_quantile(0.99)),
+            slowest: Duration::from_nanos(self.histogram.max()),
+        }
+    }
+}
+
+/// 流式响应的统计，每个流结束时记录一次。
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StreamStatistic {
+    pub first_event: PhaseStatistic,
+    pub event_gap: PhaseStatistic,
+    pub lifetime: PhaseStatistic,
+    pub streams: u64,
+    pub events: u64,
+    pub interrupted: u64,
+    // 各个流事件速率之和，除以流数得到单流平均速率
+    pub events_per_sec_sum: f64,
+}
+
+impl StreamStatistic {
+    fn record(&mut self, stream: &StreamResult) {
+        self.streams += 1;
+        self.events += stream.events;
+        if stream.interrupted {
+            self.interrupted += 1;
+        }
+        if let Some(first_event_ns) = stream.first_event_ns {
+            self.first_event.record(first_event_ns);
+        }
+        for gap in &stream.event_gaps_ns {
+            self.event_gap.record(*gap);
+        }
+        self.lifetime.record(stream.lifetime_ns);
+        if stream.lifetime_ns > 0 {
+            self.events_per_sec_sum += stream.events as f64 / (stream.lifetime_ns as f64 / 1e9);
+        }
+    }
+
+    fn add(&mut self, other: &StreamStatistic) {
+        self.first_event.add(&other.first_event);
+        self.event_gap.add(&other.event_gap);
+        self.lifetime.add(&other.lifetime);
+        self.streams += other.streams;
+        self.events += other.events;
+        self.interrupted += other.interrupted;
+        self.events_per_sec_sum += other.events_per_sec_sum;
+    }
+
+    fn subtract(&mut self, earlier: &StreamStatistic) {
+        self.first_event.subtract(&earlier.first_event);
+        self.event_gap.subtract(&earlier.event_gap);
+        self.lifetime.subtract(&earlier.lifetime);
+        self.streams -= earlier.streams;
+        self.events -= earlier.events;
+        self.interrupted -= earlier.interrupted;
+        self.events_per_sec_sum -= earlier.events_per_sec_sum;
+    }
+
+    fn summarize(&self) -> Option<StreamSummary> {
+        if self.streams == 0 {
+            return None;
+        }
+        Some(StreamSummary {
+            streams: self.streams,
+            events: self.events,
+            interrupted: self.interrupted,
+            events_per_sec_per_stream: self.events_per_sec_sum / self.streams as f64,
+            first_event: self.first_event.summarize(),
+            event_gap: self.event_gap.summarize(),
+            lifetime: self.lifetime.summarize(),
+        })
+    }
+}
+
+impl Default for StatisticAggregate {
+    fn default() -> Self {
+        Self {
+            histogram: Histogram::<u64>::new(3).unwrap(),
+            total_requests: 0,
+            successful_requests: 0,
+            total_data: 0,
+            total_time_cost_ns: 0,
+            total_time_cost_ns_squared: 0.0,
+            slowest_ns: 0,
+            fastest_ns: u64::MAX,
+            status_code_dist: HashMap::new(),
+            error_dist: HashMap::new(),
+            redirect_dist: HashMap::new(),
+            phase_dist: BTreeMap::new(),
+            breakdowns: BTreeMap::new(),
+            stream: StreamStatistic::default(),
+            journey: BreakdownStatistic::default(),
+            retry: RetryStatistic::default(),
+            decoded_data: 0,
+            upload: UploadStatistic::default(),
+        }
+    }
+}
+
+impl StatisticAggregate {
+    pub fn record(&mut self, result: &Result<ResponseStatistic, anyhow::Error>, labels: &Labels) {
+        self.total_requests += 1;
+        let time_cost_ns = result.as_ref().ok().map(|item| item.time_cost_ns);
+        for (dimension, value) in labels {
+            self.breakdowns
+                .entry(dimension.to_string())
+                .or_default()
+                .entry(value.clone())
+                .or_default()
+                .record(time_cost_ns);
+        }
+        match result {
+            Ok(item) => {
+                self.successful_requests += 1;
+                self.histogram.record(item.time_cost_ns).unwrap();
+                self.total_data += item.content_length;
+                self.total_time_cost_ns += item.time_cost_ns;
+                self.total_time_cost_ns_squared += (item.time_cost_ns as f64).powi(2);
+                self.slowest_ns = self.slowest_ns.max(item.time_cost_ns);
+                self.fastest_ns = self.fastest_ns.min(item.time_cost_ns);
+                *self.status_code_dist.entry(item.status_code).or_insert(0) += 1;
+                *self.redirect_dist.entry(item.redirects).or_insert(0) += 1;
+            }
+            Err(e) => {
+                *self.error_dist.entry(e.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// 计算从 `earlier` 快照到现在的增量，用于按时间间隔上报。
+    pub fn since(&self, earlier: &StatisticAggregate) -> StatisticAggregate {
+        let mut histogram = self.histogram.clone();
+        histogram.subtract(&earlier.histogram).unwrap();
+        StatisticAggregate {
+            // 增量里无法精确还原极值，用直方图的近似值代替
+            slowest_ns: histogram.max(),
+            fastest_ns: if histogram.is_empty() {
+                u64::MAX
+            } else {
+                histogram.min()
+            },
+            histogram,
+            total_requests: self.total_requests - earlier.total_requests,
+            successful_requests: self.successful_requests - earlier.successful_requests,
+            total_data: self.total_data - earlier.total_data,
+            total_time_cost_ns: self.total_time_cost_ns - earlier.total_time_cost_ns,
+            total_time_cost_ns_squared: self.total_time_cost_ns_squared
+                - earlier.total_time_cost_ns_squared,
+            status_code_dist: subtract_dist(&self.status_code_dist, &earlier.status_code_dist),
+            error_dist: subtract_dist(&self.error_dist, &earlier.error_dist),
+            redirect_dist: subtract_dist(&self.redirect_dist, &earlier.redirect_dist),
+            phase_dist: self
+                .phase_dist
+                .iter()
+                .map(|(phase, current)| {
+                    let mut delta = current.clone();
+                    if let Some(earlier) = earlier.phase_dist.get(phase) {
+                        delta.subtract(earlier);
+                    }
+                    (phase.clone(), delta)
+                })
+                .collect(),
+            breakdowns: self
+                .breakdowns
+                .iter()
+                .map(|(dimension, groups)| {
+                    let groups = groups
+                        .iter()
+                        .map(|(value, current)| {
+                            let mut delta = current.clone();
+                            if let Some(earlier) = earlier
+                                .breakdowns
+                                .get(dimension)
+                                .and_then(|groups| groups.get(value))
+                            {
+                                delta.subtract(earlier);
+                            }
+                            (value.clone(), delta)
+                        })
+                        .collect();
+                    (dimension.clone(), groups)
+                })
+                .collect(),
+            stream: {
+                let mut delta = self.stream.clone();
+                delta.subtract(&earlier.stream);
+                delta
+            },
+            journey: {
+                let mut delta = self.journey.clone();
+                delta.subtract(&earlier.journey);
+                delta
+            },
+            retry: {
+                let mut delta = self.retry.clone();
+                delta.subtract(&earlier.retry);
+                delta
+            },
+            decoded_data: self.decoded_data - earlier.decoded_data,
+            upload: {
+                let mut delta = self.upload.clone();
+                delta.subtract(&earlier.upload);
+                delta
+            },
+        }
+    }
+
+    pub fn merge(&mut self, other: &StatisticAggregate) {
+        self.histogram.add(&other.histogram).unwrap();
+        self.total_requests += other.total_requests;
+        self.successful_requests += other.successful_requests;
+        self.total_data += other.total_data;
+        self.total_time_cost_ns += other.total_time_cost_ns;
+        self.total_time_cost_ns_squared += other.total_time_cost_ns_squared;
+        self.slowest_ns = self.slowest_ns.max(other.slowest_ns);
+        self.fastest_ns = self.fastest_ns.min(other.fastest_ns);
+        merge_dist(&mut self.status_code_dist, &other.status_code_dist);
+        merge_dist(&mut self.error_dist, &other.error_dist);
+        merge_dist(&mut self.redirect_dist, &other.redirect_dist);
+        for (phase, other) in &other.phase_dist {
+            self.phase_dist.entry(phase.clone()).or_default().add(other);
+        }
+        for (dimension, groups) in &other.breakdowns {
+            let current = self.breakdowns.entry(dimension.clone()).or_default();
+            for (value, other) in groups {
+                current.entry(value.clone()).or_default().add(other);
             }
         }
+        self.stream.add(&other.stream);
+        self.journey.add(&other.journey);
+        self.retry.add(&other.retry);
+        self.decoded_data += other.decoded_data;
+        self.upload.add(&other.upload);
+    }
 
-        let successful_requests = successful_times_ns.len();
+    pub fn summarize(
+        &self,
+        url: String,
+        concurrency: u64,
+        actual_duration: Duration,
+    ) -> BenchmarkSummary {
+        let successful_requests = self.successful_requests;
         if successful_requests == 0 {
             // 所有请求都失败的场景
-            return Some(self.build_error_summary(actual_duration, error_dist));
+            return self.build_error_summary(url, concurrency, actual_duration);
         }
 
         // --- 开始计算 ---
         let total_duration_sec = actual_duration.as_secs_f64();
-        let requests_per_sec = self.response_list.len() as f64 / total_duration_sec;
-        let data_transfer_rate_mbps = (total_data as f64 / (1024.0 * 1024.0)) / total_duration_sec;
+        let requests_per_sec = self.total_requests as f64 / total_duration_sec;
+        let data_transfer_rate_mbps =
+            (self.total_data as f64 / (1024.0 * 1024.0)) / total_duration_sec;
 
-        let total_time_cost_ns: u64 = successful_times_ns.iter().sum();
-        let average_ns = total_time_cost_ns as f64 / successful_requests as f64;
-
-        let variance = successful_times_ns
-            .iter()
-            .map(|&time| {
-                let diff = time as f64 - average_ns;
-                diff * diff
-            })
-            .sum::<f64>()
-            / successful_requests as f64;
+        let average_ns = self.total_time_cost_ns as f64 / successful_requests as f64;
+        let variance = (self.total_time_cost_ns_squared / successful_requests as f64
+            - average_ns * average_ns)
+            .max(0.0);
         let std_dev_ns = variance.sqrt();
 
-        let avg_size_per_request = total_data as f64 / successful_requests as f64;
+        let avg_size_per_request = self.total_data as f64 / successful_requests as f64;
+        let hist = &self.histogram;
 
-        Some(BenchmarkSummary {
-            url: self.cli.url.to_string().clone(),
-            concurrency: self.cli.concurrency as u64, // 类型转换 u16 -> u64
+        BenchmarkSummary {
+            url,
+            concurrency,
             actual_duration,
             requests_per_sec,
             data_transfer_rate_mbps,
             average_latency: Duration::from_nanos(average_ns as u64),
             latency_std_dev: Duration::from_nanos(std_dev_ns as u64),
-            slowest: Duration::from_nanos(*successful_times_ns.iter().max().unwrap_or(&0)),
-            fastest: Duration::from_nanos(*successful_times_ns.iter().min().unwrap_or(&0)),
+            slowest: Duration::from_nanos(self.slowest_ns),
+            fastest: Duration::from_nanos(self.fastest_ns),
             p50: Duration::from_nanos(hist.value_at_quantile(0.50)),
             p90: Duration::from_nanos(hist.value_at_quantile(0.90)),
             p95: Duration::from_nanos(hist.value_at_quantile(0.95)),
             p99: Duration::from_nanos(hist.value_at_quantile(0.99)),
             p99_9: Duration::from_nanos(hist.value_at_quantile(0.999)),
-            total_data,
+            total_data: self.total_data,
             avg_size_per_request,
-            total_requests: self.response_list.len(),
+            decoded_data: (self.decoded_data > 0).then_some(self.decoded_data),
+            total_requests: self.total_requests,
             successful_requests,
-            status_code_dist,
-            error_dist,
-        })
+            status_code_dist: self.status_code_dist.clone(),
+            error_dist: self.error_dist.clone(),
+            redirect_dist: self.redirect_dist.clone(),
+            phase_summaries: self.phase_summaries(),
+            breakdowns: self.breakdown_summaries(),
+            stream: self.stream.summarize(),
+            journey: (self.journey.total_requests > 0).then(|| self.journey.summarize()),
+            retry: (self.retry.first_attempt_successes
+                + self.retry.retried_successes
+                + self.retry.retried_failures
+                > 0)
+            .then(|| self.retry.clone()),
+            upload: self.upload.summarize(),
+            status_kind: StatusKind::Http,
+            paced: false,
+        }
     }
 
     // 辅助函数，用于构建只有错误的摘要
     fn build_error_summary(
         &self,
+        url: String,
+        concurrency: u64,
         actual_duration: Duration,
-        error_dist: HashMap<String, usize>,
     ) -> BenchmarkSummary {
         BenchmarkSummary {
-            url: self.cli.url.to_string().clone(),
-            concurrency: self.cli.concurrency as u64,
+            url,
+            concurrency,
             actual_duration,
-            requests_per_sec: self.response_list.len() as f64 / actual_duration.as_secs_f64(),
+            requests_per_sec: self.total_requests as f64 / actual_duration.as_secs_f64(),
             data_transfer_rate_mbps: 0.0,
             average_latency: Duration::default(),
             latency_std_dev: Duration::default(),
@@ -152,36 +916,163 @@ impl StatisticList {
             p99_9: Duration::default(),
             total_data: 0,
             avg_size_per_request: 0.0,
-            total_requests: self.response_list.len(),
+            decoded_data: None,
+            total_requests: self.total_requests,
             successful_requests: 0,
             status_code_dist: HashMap::new(),
-            error_dist,
+            error_dist: self.error_dist.clone(),
+            redirect_dist: HashMap::new(),
+            phase_summaries: self.phase_summaries(),
+            breakdowns: self.breakdown_summaries(),
+            stream: self.stream.summarize(),
+            journey: (self.journey.total_requests > 0).then(|| self.journey.summarize()),
+            retry: (self.retry.first_attempt_successes
+                + self.retry.retried_successes
+                + self.retry.retried_failures
+                > 0)
+            .then(|| self.retry.clone()),
+            upload: self.upload.summarize(),
+            status_kind: StatusKind::Http,
+            paced: false,
         }
     }
+
+    fn breakdown_summaries(&self) -> BTreeMap<String, Vec<(String, BreakdownSummary)>> {
+        self.breakdowns
+            .iter()
+            .filter(|(_, groups)| groups.len() > 1)
+            .map(|(dimension, groups)| {
+                let rows = groups
+                    .iter()
+                    .map(|(value, group)| (value.clone(), group.summarize()))
+                    .collect();
+                (dimension.clone(), rows)
+            })
+            .collect()
+    }
+
+    pub fn phase_summaries(&self) -> BTreeMap<String, PhaseSummary> {
+        self.phase_dist
+            .iter()
+            .filter(|(_, phase)| !phase.histogram.is_empty())
+            .map(|(name, phase)| (name.clone(), phase.summarize()))
+            .collect()
+    }
 }
 
-// 为 BenchmarkSummary 实现 Display trait，专门用于格式化输出
-impl Display for BenchmarkSummary {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        // --- 辅助函数，用于格式化 ---
-        fn format_duration(d: Duration) -> String {
-            if d.as_secs_f64() >= 1.0 {
-                format!("{:.2} s", d.as_secs_f64())
-            } else if d.as_millis() >= 1 {
-                format!("{} ms", d.as_millis())
-            } else if d.as_micros() >= 1 {
-                format!("{} µs", d.as_micros())
-            } else {
-                format!("{} ns", d.as_nanos())
-            }
-        }
+fn merge_dist<K: Hash + Eq + Clone>(into: &mut HashMap<K, usize>, other: &HashMap<K, usize>) {
+    for (key, count) in other {
+        *into.entry(key.clone()).or_insert(0) += count;
+    }
+}
+
+fn subtract_dist<K: Hash + Eq + Clone>(
+    current: &HashMap<K, usize>,
+    earlier: &HashMap<K, usize>,
+) -> HashMap<K, usize> {
+    current
+        .iter()
+        .map(|(key, count)| (key.clone(), count - earlier.get(key).unwrap_or(&0)))
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+// 直方图按 (值, 计数) 列表序列化，反序列化时重新记录到同一个桶里。
+mod histogram_serde {
+    use hdrhistogram::Histogram;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(hist: &Histogram<u64>, s: S) -> Result<S::Ok, S::Error> {
+        hist.iter_recorded()
+            .map(|v| (v.value_iterated_to(), v.count_at_value()))
+            .collect::<Vec<(u64, u64)>>()
+            .serialize(s)
+    }
 
-        fn format_bytes(b: u64) -> String {
-            byte_unit::Byte::from_u64(b)
-                .get_appropriate_unit(byte_unit::UnitType::Binary)
-                .to_string()
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Histogram<u64>, D::Error> {
+        let buckets = Vec::<(u64, u64)>::deserialize(d)?;
+        let mut hist = Histogram::<u64>::new(3).map_err(serde::de::Error::custom)?;
+        for (value, count) in buckets {
+            hist.record_n(value, count)
+                .map_err(serde::de::Error::custom)?;
         }
+        Ok(hist)
+    }
+}
 
+// --- 辅助函数，用于格式化 ---
+pub fn format_duration(d: Duration) -> String {
+    if d.as_secs_f64() >= 1.0 {
+        format!("{:.2} s", d.as_secs_f64())
+    } else if d.as_millis() >= 1 {
+        format!("{} ms", d.as_millis())
+    } else if d.as_micros() >= 1 {
+        format!("{} µs", d.as_micros())
+    } else {
+        format!("{} ns", d.as_nanos())
+    }
+}
+
+pub fn format_bytes(b: u64) -> String {
+    byte_unit::Byte::from_u64(b)
+        .get_appropriate_unit(byte_unit::UnitType::Binary)
+        .to_string()
+}
+
+/// 预热阶段的简要统计，和正式报告分开展示。
+#[derive(Debug)]
+pub struct WarmupSummary {
+    pub duration: Duration,
+    pub total_requests: usize,
+    pub errors: usize,
+    pub average: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    // 每段 seconds_per_slice 秒的平均延迟，没有成功请求的段为 None
+    pub seconds_per_slice: usize,
+    pub slice_averages: Vec<Option<Duration>>,
+}
+
+impl Display for WarmupSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[Warm-up] (not included in the summary below)")?;
+        writeln!(
+            f,
+            "  Duration:         {:.2} s",
+            self.duration.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "  Requests:         {}, {} errors",
+            self.total_requests, self.errors
+        )?;
+        writeln!(
+            f,
+            "  Latency:          avg {}, p50 {}, p99 {}",
+            format_duration(self.average),
+            format_duration(self.p50),
+            format_duration(self.p99)
+        )?;
+        let label = if self.seconds_per_slice == 1 {
+            String::from("Avg per second:")
+        } else {
+            format!("Avg per {} s:", self.seconds_per_slice)
+        };
+        let averages = self
+            .slice_averages
+            .iter()
+            .map(|average| average.map_or(String::from("-"), format_duration))
+            .join(", ");
+        writeln!(f, "  {label:<18}{averages}")
+    }
+}
+
+// 为 BenchmarkSummary 实现 Display trait，专门用于格式化输出
+impl Display for BenchmarkSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Http Stress Test Summary")?;
         writeln!(f, "====================================")?;
 
@@ -198,6 +1089,16 @@ impl Display for BenchmarkSummary {
         // --- Throughput Section ---
         writeln!(f, "\n[Throughput]")?;
         writeln!(f, "  Requests/sec:     {:.2}", self.requests_per_sec)?;
+        if self.paced && self.concurrency > 0 && self.requests_per_sec > 0.0 {
+            // 模拟用户时更关心每个用户的节奏，而不是总吞吐
+            let per_worker = self.requests_per_sec / self.concurrency as f64;
+            writeln!(
+                f,
+                "  Per worker:       {:.2} requests/sec (one every {})",
+                per_worker,
+                format_duration(Duration::from_secs_f64(1.0 / per_worker))
+            )?;
+        }
         writeln!(
             f,
             "  Transfer Rate:    {:.2} MB/s",
@@ -234,19 +1135,358 @@ impl Display for BenchmarkSummary {
                 "  Size/request:     {:.2} bytes",
                 self.avg_size_per_request
             )?;
+            if let Some(decoded_data) = self.decoded_data {
+                writeln!(f, "  Decompressed:     {}", format_bytes(decoded_data))?;
+                if self.total_data > 0 {
+                    writeln!(
+                        f,
+                        "  Compression:      {:.2}x",
+                        decoded_data as f64 / self.total_data as f64
+                    )?;
+                }
+            }
+        }
+
+        if let Some(upload) = &self.upload {
+            writeln!(f, "\n[Uploads]")?;
+            writeln!(
+                f,
+                "  Total Uploaded:   {} in {} requests",
+                format_bytes(upload.total_bytes),
+                upload.uploads
+            )?;
+            writeln!(
+                f,
+                "  Size/request:     avg {}, p50 {}, p99 {}",
+                format_bytes(upload.average),
+                format_bytes(upload.p50),
+                format_bytes(upload.p99)
+            )?;
+            writeln!(
+                f,
+                "  Smallest/Largest: {} / {}",
+                format_bytes(upload.smallest),
+                format_bytes(upload.largest)
+            )?;
+        }
+
+        if !self.phase_summaries.is_empty() {
+            writeln!(f, "\n[Connection Phases]")?;
+            for (name, phase) in &self.phase_summaries {
+                writeln!(
+                    f,
+                    "  {:<18}{} connections, avg {}, p50 {}, p99 {}, slowest {}",
+                    format!("{name}:"),
+                    phase.count,
+                    format_duration(phase.average),
+                    format_duration(phase.p50),
+                    format_duration(phase.p99),
+                    format_duration(phase.slowest)
+                )?;
+            }
+        }
+
+        if let Some(retry) = &self.retry {
+            writeln!(f, "\n[Retries]")?;
+            writeln!(f, "  Retries sent:     {}", retry.retries)?;
+            writeln!(
+                f,
+                "  First attempt:    {} succeeded",
+                retry.first_attempt_successes
+            )?;
+            writeln!(
+                f,
+                "  After retrying:   {} succeeded",
+                retry.retried_successes
+            )?;
+            writeln!(
+                f,
+                "  Still failing:    {} after retrying",
+                retry.retried_failures
+            )?;
+        }
+
+        if let Some(journey) = &self.journey {
+            writeln!(f, "\n[Journey]")?;
+            writeln!(
+                f,
+                "  Journeys:         {} ({} failed)",
+                journey.total_requests, journey.errors
+            )?;
+            if journey.total_requests > journey.errors {
+                writeln!(
+                    f,
+                    "  Duration:         avg {}, p50 {}, p99 {}",
+                    format_duration(journey.average),
+                    format_duration(journey.p50),
+                    format_duration(journey.p99)
+                )?;
+            }
+        }
+
+        if let Some(stream) = &self.stream {
+            writeln!(f, "\n[Streaming]")?;
+            writeln!(
+                f,
+                "  Streams:          {} ({} interrupted)",
+                stream.streams, stream.interrupted
+            )?;
+            writeln!(f, "  Events:           {}", stream.events)?;
+            writeln!(
+                f,
+                "  Events/sec:       {:.2} per stream",
+                stream.events_per_sec_per_stream
+            )?;
+            for (name, phase) in [
+                ("First Event", &stream.first_event),
+                ("Event Gap", &stream.event_gap),
+                ("Lifetime", &stream.lifetime),
+            ] {
+                if phase.count == 0 {
+                    continue;
+                }
+                writeln!(
+                    f,
+                    "  {:<18}avg {}, p50 {}, p99 {}, slowest {}",
+                    format!("{name}:"),
+                    format_duration(phase.average),
+                    format_duration(phase.p50),
+                    format_duration(phase.p99),
+                    format_duration(phase.slowest)
+                )?;
+            }
         }
 
         // --- Results Section ---
-        writeln!(f, "\n[Status Code Distribution]")?;
-        if self.status_code_dist.is_empty() {
-            writeln!(f, "  (No successful requests)")?;
-        } else {
+        let status_title = match self.status_kind {
+            StatusKind::Http => Some("Status Code Distribution"),
+            StatusKind::Grpc => Some("gRPC Status Distribution"),
+            StatusKind::None => None,
+        };
+        if let Some(title) = status_title {
+            writeln!(f, "\n[{title}]")?;
+            if self.status_code_dist.is_empty() {
+                writeln!(f, "  (No successful requests)")?;
+            }
             for (code, count) in self.status_code_dist.iter().sorted_by_key(|&(&c, _)| c) {
                 let percent = (*count as f64 / self.total_requests as f64) * 100.0;
-                writeln!(f, "  [{code}] {count} responses ({percent:.2}%)")?;
+                if self.status_kind == StatusKind::Grpc {
+                    let name = grpc::status_name(*code);
+                    writeln!(f, "  [{code} {name}] {count} responses ({percent:.2}%)")?;
+                } else {
+                    writeln!(f, "  [{code}] {count} responses ({percent:.2}%)")?;
+                }
+            }
+        }
+
+        for (dimension, rows) in &self.breakdowns {
+            writeln!(f, "\n[By {dimension}]")?;
+            let width = rows.iter().map(|(value, _)| value.len()).max().unwrap_or(0);
+            for (value, row) in rows {
+                writeln!(
+                    f,
+                    "  {value:<width$}  {} requests, {} errors, avg {}, p50 {}, p99 {}",
+                    row.total_requests,
+                    row.errors,
+                    format_duration(row.average),
+                    format_duration(row.p50),
+                    format_duration(row.p99)
+                )?;
+            }
+        }
+
+        // 只有真正发生过重定向时才输出
+        if self.redirect_dist.keys().any(|&hops| hops > 0) {
+            writeln!(f, "\n[Redirect Chains]")?;
+            for (hops, count) in self.redirect_dist.iter().sorted_by_key(|&(&h, _)| h) {
+                let percent = (*count as f64 / self.successful_requests as f64) * 100.0;
+                writeln!(f, "  {hops} hops: {count} responses ({percent:.2}%)")?;
+            }
+        }
+
+        writeln!(f, "\n[Error Distribution]")?;
+        if self.error_dist.is_empty() {
+            writeln!(f, "  (No errors)")?;
+        } else {
+            for (error, count) in &self.error_dist {
+                writeln!(f, "  - \"{error}\": {count} occurrences")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// WebSocket 模式的统计：握手耗时、回显往返延迟、消息计数和断线次数。
+#[derive(Default)]
+pub struct WebSocketStatistic {
+    pub handshake: PhaseStatistic,
+    pub round_trip: PhaseStatistic,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    // 测试结束前连接被服务端关闭或出错断开的次数
+    pub disconnects: u64,
+    pub error_dist: HashMap<String, usize>,
+}
+
+impl WebSocketStatistic {
+    pub fn record_handshake(&mut self, time_cost_ns: u64) {
+        self.handshake.record(time_cost_ns);
+    }
+
+    pub fn record_round_trip(&mut self, time_cost_ns: u64) {
+        self.round_trip.record(time_cost_ns);
+    }
+
+    pub fn record_error(&mut self, error: &anyhow::Error) {
+        *self.error_dist.entry(error.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn summarize(
+        &self,
+        url: String,
+        connections: u64,
+        actual_duration: Duration,
+        phase_summaries: BTreeMap<String, PhaseSummary>,
+    ) -> WebSocketSummary {
+        let secs = actual_duration.as_secs_f64();
+        WebSocketSummary {
+            url,
+            connections,
+            actual_duration,
+            handshake: self.handshake.summarize(),
+            round_trip: self.round_trip.summarize(),
+            messages_sent: self.messages_sent,
+            messages_received: self.messages_received,
+            sent_per_sec: self.messages_sent as f64 / secs,
+            received_per_sec: self.messages_received as f64 / secs,
+            bytes_received: self.bytes_received,
+            disconnects: self.disconnects,
+            error_dist: self.error_dist.clone(),
+            phase_summaries,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WebSocketSummary {
+    pub url: String,
+    pub connections: u64,
+    pub actual_duration: Duration,
+    pub handshake: PhaseSummary,
+    pub round_trip: PhaseSummary,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub sent_per_sec: f64,
+    pub received_per_sec: f64,
+    pub bytes_received: u64,
+    pub disconnects: u64,
+    pub error_dist: HashMap<String, usize>,
+    pub phase_summaries: BTreeMap<String, PhaseSummary>,
+}
+
+impl Display for WebSocketSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "WebSocket Stress Test Summary")?;
+        writeln!(f, "====================================")?;
+
+        writeln!(f, "\n[Session]")?;
+        writeln!(f, "  URL:              {}", self.url)?;
+        writeln!(f, "  Connections:      {}", self.connections)?;
+        writeln!(
+            f,
+            "  Test Duration:    {:.2} s",
+            self.actual_duration.as_secs_f64()
+        )?;
+
+        writeln!(f, "\n[Handshake]")?;
+        writeln!(f, "  Successful:       {}", self.handshake.count)?;
+        if self.handshake.count > 0 {
+            writeln!(
+                f,
+                "  Average:          {}",
+                format_duration(self.handshake.average)
+            )?;
+            writeln!(
+                f,
+                "  P50 (Median):     {}",
+                format_duration(self.handshake.p50)
+            )?;
+            writeln!(
+                f,
+                "  P99:              {}",
+                format_duration(self.handshake.p99)
+            )?;
+            writeln!(
+                f,
+                "  Slowest:          {}",
+                format_duration(self.handshake.slowest)
+            )?;
+        }
+
+        writeln!(f, "\n[Messages]")?;
+        writeln!(
+            f,
+            "  Sent:             {} ({:.2}/s)",
+            self.messages_sent, self.sent_per_sec
+        )?;
+        writeln!(
+            f,
+            "  Received:         {} ({:.2}/s)",
+            self.messages_received, self.received_per_sec
+        )?;
+        writeln!(
+            f,
+            "  Data Received:    {}",
+            format_bytes(self.bytes_received)
+        )?;
+
+        // 只有收到过回显时才有往返延迟
+        if self.round_trip.count > 0 {
+            writeln!(f, "\n[Round Trip]")?;
+            writeln!(f, "  Echoes:           {}", self.round_trip.count)?;
+            writeln!(
+                f,
+                "  Average:          {}",
+                format_duration(self.round_trip.average)
+            )?;
+            writeln!(
+                f,
+                "  P50 (Median):     {}",
+                format_duration(self.round_trip.p50)
+            )?;
+            writeln!(
+                f,
+                "  P99:              {}",
+                format_duration(self.round_trip.p99)
+            )?;
+            writeln!(
+                f,
+                "  Slowest:          {}",
+                format_duration(self.round_trip.slowest)
+            )?;
+        }
+
+        if !self.phase_summaries.is_empty() {
+            writeln!(f, "\n[Connection Phases]")?;
+            for (name, phase) in &self.phase_summaries {
+                writeln!(
+                    f,
+                    "  {:<18}{} connections, avg {}, p50 {}, p99 {}, slowest {}",
+                    format!("{name}:"),
+                    phase.count,
+                    format_duration(phase.average),
+                    format_duration(phase.p50),
+                    format_duration(phase.p99),
+                    format_duration(phase.slowest)
+                )?;
             }
         }
 
+        writeln!(f, "\n[Disconnects]")?;
+        writeln!(f, "  Before end:       {}", self.disconnects)?;
+
         writeln!(f, "\n[Error Distribution]")?;
         if self.error_dist.is_empty() {
             writeln!(f, "  (No errors)")?;
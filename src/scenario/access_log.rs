@@ -0,0 +1,454 @@
+use super::epoch_ms;
+use super::har::SKIPPED_HEADERS;
+use super::parse_timestamp_ms;
+use super::Scenario;
+use super::SendAt;
+use crate::output::report::Labels;
+use crate::vojo::cli::LogFormat;
+use http::header::REFERER;
+use http::header::USER_AGENT;
+use http::uri::Authority;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Request;
+use http::Uri;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+pub const LABEL_PATH_PATTERN: &str = "Path Pattern";
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// One request read from a log line.
+struct LogEntry {
+    time_ms: Option<f64>,
+    method: String,
+    // Path and query
+    target: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// Reads a log into a scenario sending its requests to the scheme and host of `base`,
+/// labelled by path pattern. With a `speed` every request is sent at its recorded offset
+/// divided by the speed, otherwise the requests are sent as fast as the workers can.
+pub fn load(
+    path: &Path,
+    format: Option<LogFormat>,
+    base: &Uri,
+    speed: Option<f64>,
+) -> Result<Scenario, anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    let format = format.unwrap_or_else(|| match lines.first() {
+        Some(line) if line.trim_start().starts_with('{') => LogFormat::Jsonl,
+        _ => LogFormat::Combined,
+    });
+    let mut entries: Vec<LogEntry> = lines
+        .iter()
+        .filter_map(|line| match format {
+            LogFormat::Combined => parse_combined(line),
+            LogFormat::Jsonl => parse_jsonl(line),
+        })
+        .collect();
+    let unreadable = lines.len() - entries.len();
+
+    // Lines without a time keep the time of the line before, then requests go out in time order.
+    let mut last_time = None;
+    for entry in &mut entries {
+        last_time = entry.time_ms.or(last_time);
+        entry.time_ms = last_time;
+    }
+    entries.sort_by(|a, b| {
+        a.time_ms
+            .unwrap_or(0.0)
+            .total_cmp(&b.time_ms.unwrap_or(0.0))
+    });
+    let first_time = entries.iter().find_map(|entry| entry.time_ms);
+
+    let scheme = base.scheme_str().unwrap_or("http");
+    let authority = base
+        .authority()
+        .ok_or_else(|| anyhow!("The URL must include a host"))?;
+    let mut requests = vec![];
+    // Lines with a URL, method or header that is not valid in a request are dropped as well.
+    let mut invalid = 0;
+    for entry in entries {
+        let label = format!("{} {}", entry.method, path_pattern(&entry.target));
+        let Ok(mut request) = build_request(&entry, scheme, authority) else {
+            invalid += 1;
+            continue;
+        };
+        if let (Some(speed), Some(time), Some(first)) = (speed, entry.time_ms, first_time) {
+            let offset = Duration::from_secs_f64((time - first).max(0.0) / 1000.0 / speed);
+            request.extensions_mut().insert(SendAt(offset));
+        }
+        let labels: Labels = vec![(LABEL_PATH_PATTERN, label)];
+        request.extensions_mut().insert(labels);
+        requests.push(request);
+    }
+    if requests.is_empty() {
+        return Err(anyhow!("No requests could be read from {}", path.display()));
+    }
+    info!(
+        "Replaying {} requests from {} ({} lines skipped)",
+        requests.len(),
+        path.display(),
+        unreadable + invalid
+    );
+    Ok(Scenario {
+        name: Some(format!("{} ({} requests)", path.display(), requests.len())),
+        requests,
+        sequential: false,
+        timed: speed.is_some(),
+        journey: None,
+    })
+}
+
+fn build_request(
+    entry: &LogEntry,
+    scheme: &str,
+    authority: &Authority,
+) -> Result<Request<Full<Bytes>>, anyhow::Error> {
+    let uri: Uri = format!("{scheme}://{authority}{}", entry.target).parse()?;
+    let mut request = Request::builder()
+        .method(entry.method.as_str())
+        .uri(uri)
+        .body(Full::new(Bytes::from(entry.body.clone())))?;
+    for (name, value) in &entry.headers {
+        let name = name.to_lowercase();
+        if SKIPPED_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        request.headers_mut().append(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+    }
+    Ok(request)
+}
+
+/// Parses a line of the combined format nginx and Apache share:
+/// `1.2.3.4 - user [10/Oct/2000:13:55:36 -0700] "GET /a HTTP/1.1" 200 2326 "referer" "agent"`.
+/// The common format, without referer and agent, is read too.
+fn parse_combined(line: &str) -> Option<LogEntry> {
+    let open = line.find('[')?;
+    let close = open + line[open..].find(']')?;
+    let time_ms = parse_clf_time(&line[open + 1..close]);
+    let (request_line, rest) = quoted(&line[close + 1..])?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+    if !target.starts_with('/') || !method.bytes().all(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    let mut headers = vec![];
+    // After the status and size come the referer and the user agent.
+    if let Some((referer, rest)) = quoted(rest) {
+        if referer != "-" {
+            headers.push((REFERER.to_string(), referer));
+        }
+        if let Some((agent, _)) = quoted(rest) {
+            if agent != "-" {
+                headers.push((USER_AGENT.to_string(), agent));
+            }
+        }
+    }
+    Some(LogEntry {
+        time_ms,
+        method,
+        target,
+        headers,
+        body: String::new(),
+    })
+}
+
+/// The next double-quoted string in `text` and the text after it, unescaped. Apache escapes
+/// quotes and backslashes with a backslash, and both servers write other bytes as \xHH.
+fn quoted(text: &str) -> Option<(String, &str)> {
+    let start = text.find('"')? + 1;
+    let bytes = &text.as_bytes()[start..];
+    let mut value = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => {
+                let escaped = *bytes.get(i + 1)?;
+                let hex = bytes
+                    .get(i + 2..i + 4)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match (escaped, hex) {
+                    (b'x', Some(byte)) => {
+                        value.push(byte);
+                        i += 4;
+                    }
+                    (b'"' | b'\\', _) => {
+                        value.push(escaped);
+                        i += 2;
+                    }
+                    (b'n', _) => {
+                        value.push(b'\n');
+                        i += 2;
+                    }
+                    (b't', _) => {
+                        value.push(b'\t');
+                        i += 2;
+                    }
+                    _ => {
+                        value.push(b'\\');
+                        i += 1;
+                    }
+                }
+            }
+            b'"' => {
+                let value = String::from_utf8_lossy(&value).into_owned();
+                return Some((value, &text[start + i + 1..]));
+            }
+            byte => {
+                value.push(byte);
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+/// Milliseconds since the epoch of a log time such as 10/Oct/2000:13:55:36 -0700.
+fn parse_clf_time(text: &str) -> Option<f64> {
+    let number = |range: std::ops::Range<usize>| text.get(range)?.parse::<i64>().ok();
+    let day = number(0..2)?;
+    let month = MONTHS.iter().position(|m| text.get(3..6) == Some(*m))? as i64 + 1;
+    let year = number(7..11)?;
+    let (hour, minute, second) = (number(12..14)?, number(15..17)?, number(18..20)?);
+    let offset_minutes = match text.get(21..26) {
+        Some(zone) => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            sign * (zone.get(1..3)?.parse::<i64>().ok()? * 60
+                + zone.get(3..5)?.parse::<i64>().ok()?)
+        }
+        None => 0,
+    };
+    let seconds = (hour * 60 + minute - offset_minutes) * 60 + second;
+    Some(epoch_ms(year, month, day) + seconds as f64 * 1000.0)
+}
+
+/// Parses a JSON request log line such as
+/// `{"time": "2024-05-01T10:00:00Z", "method": "POST", "path": "/a", "headers": {}, "body": ""}`.
+/// `url` may stand in for `path`, and the time may be an ISO 8601 string or epoch seconds.
+fn parse_jsonl(line: &str) -> Option<LogEntry> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    let field = |names: &[&str]| names.iter().find_map(|name| value.get(*name));
+    let target = field(&["path", "url", "uri", "request_uri"])?.as_str()?;
+    let target = if target.starts_with('/') {
+        target.to_string()
+    } else {
+        // An absolute URL only lends its path; the host comes from the target URL.
+        let uri: Uri = target.parse().ok()?;
+        uri.path_and_query()?.as_str().to_string()
+    };
+    let time_ms = field(&["time", "timestamp", "@timestamp"]).and_then(|time| match time {
+        Value::Number(seconds) => seconds.as_f64().map(|seconds| seconds * 1000.0),
+        Value::String(text) => parse_timestamp_ms(text),
+        _ => None,
+    });
+    let headers = field(&["headers"])
+        .and_then(Value::as_object)
+        .map(|headers| {
+            headers
+                .iter()
+                .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(LogEntry {
+        time_ms,
+        method: field(&["method"])
+            .and_then(Value::as_str)
+            .unwrap_or("GET")
+            .to_uppercase(),
+        target,
+        headers,
+        body: field(&["body"])
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+/// The path with its query dropped and ids replaced, e.g., /users/42/orders → /users/:id/orders.
+fn path_pattern(target: &str) -> String {
+    let path = target.split('?').next().unwrap_or(target);
+    path.split('/')
+        .map(|segment| if is_id(segment) { ":id" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Numbers, UUIDs and long hex strings such as hashes.
+fn is_id(segment: &str) -> bool {
+    let bytes = segment.as_bytes();
+    !bytes.is_empty()
+        && (bytes.iter().all(u8::is_ascii_digit)
+            || (bytes.len() >= 16
+                && bytes.iter().all(|b| b.is_ascii_hexdigit() || *b == b'-')
+                && bytes.iter().any(u8::is_ascii_digit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_lines(
+        name: &str,
+        content: &str,
+        speed: Option<f64>,
+    ) -> Result<Scenario, anyhow::Error> {
+        let path =
+            std::env::temp_dir().join(format!("kt-access-log-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let base: Uri = "https://example.com:8443/ignored".parse().unwrap();
+        let scenario = load(&path, None, &base, speed);
+        std::fs::remove_file(&path).unwrap();
+        scenario
+    }
+
+    #[test]
+    fn unescapes_quoted_strings() {
+        assert_eq!(
+            quoted(r#" "say \"hi\" \\ now" rest"#),
+            Some((String::from(r#"say "hi" \ now"#), " rest"))
+        );
+        assert_eq!(
+            quoted(r#""Mozilla \x22quoted\x22 caf\xC3\xA9" x"#),
+            Some((String::from("Mozilla \"quoted\" café"), " x"))
+        );
+        assert_eq!(quoted(r#""a\qb""#), Some((String::from(r"a\qb"), "")));
+        assert_eq!(quoted(r#""unclosed"#), None);
+        assert_eq!(quoted("no quotes"), None);
+    }
+
+    #[test]
+    fn parses_clf_times() {
+        assert_eq!(parse_clf_time("01/Jan/1970:00:00:01 +0000"), Some(1000.0));
+        assert_eq!(
+            parse_clf_time("10/Oct/2000:13:55:36 -0700"),
+            Some(971_211_336_000.0)
+        );
+        assert_eq!(
+            parse_clf_time("01/Jan/1970:02:00:00 +0130"),
+            Some(1_800_000.0)
+        );
+        assert_eq!(parse_clf_time("01/Foo/1970:00:00:00 +0000"), None);
+        assert_eq!(parse_clf_time("garbage"), None);
+    }
+
+    #[test]
+    fn parses_combined_lines() {
+        let entry = parse_combined(
+            r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET /a?b=1 HTTP/1.1" 200 2326 "https://r.example/\"x\"" "agent \x22y\x22""#,
+        )
+        .unwrap();
+        assert_eq!(entry.method, "GET");
+        assert_eq!(entry.target, "/a?b=1");
+        assert_eq!(entry.time_ms, Some(971_211_336_000.0));
+        assert_eq!(
+            entry.headers,
+            [
+                (REFERER.to_string(), String::from("https://r.example/\"x\"")),
+                (USER_AGENT.to_string(), String::from("agent \"y\"")),
+            ]
+        );
+        // The common format has neither referer nor agent.
+        let entry =
+            parse_combined(r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "POST /b HTTP/1.0" 201 -"#)
+                .unwrap();
+        assert_eq!(entry.method, "POST");
+        assert!(entry.headers.is_empty());
+        assert!(parse_combined(r#"1.2.3.4 - - [x] "\x16\x03\x01" 400 0"#).is_none());
+    }
+
+    #[test]
+    fn parses_jsonl_lines() {
+        let entry = parse_jsonl(
+            r#"{"time": 1.5, "method": "post", "url": "http://other/x?y=1", "headers": {"A": "b", "N": 1}, "body": "{}"}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.time_ms, Some(1500.0));
+        assert_eq!(entry.method, "POST");
+        assert_eq!(entry.target, "/x?y=1");
+        assert_eq!(entry.headers, [(String::from("A"), String::from("b"))]);
+        assert_eq!(entry.body, "{}");
+        assert!(parse_jsonl(r#"{"method": "GET"}"#).is_none());
+        assert!(parse_jsonl("not json").is_none());
+    }
+
+    #[test]
+    fn replaces_ids_in_path_patterns() {
+        assert_eq!(path_pattern("/users/42/orders?x=1"), "/users/:id/orders");
+        assert_eq!(
+            path_pattern("/o/123e4567-e89b-12d3-a456-426614174000"),
+            "/o/:id"
+        );
+        assert_eq!(path_pattern("/static/app.js"), "/static/app.js");
+    }
+
+    #[test]
+    fn skips_invalid_lines() {
+        let scenario = load_lines(
+            "invalid",
+            concat!(
+                "1.2.3.4 - - [01/Jan/2024:00:00:00 +0000] \"GET /ok HTTP/1.1\" 200 1 \"-\" \"a\\x01b\"\n",
+                "1.2.3.4 - - [01/Jan/2024:00:00:01 +0000] \"GET /bad\\x01path HTTP/1.1\" 200 1\n",
+                "not a log line\n",
+                "1.2.3.4 - - [01/Jan/2024:00:00:02 +0000] \"GET /next HTTP/1.1\" 200 1 \"-\" \"ok\"\n",
+            ),
+            None,
+        )
+        .unwrap();
+        let uris: Vec<String> = scenario
+            .requests
+            .iter()
+            .map(|request| request.uri().to_string())
+            .collect();
+        assert_eq!(uris, ["https://example.com:8443/next"]);
+        assert_eq!(scenario.requests[0].headers()[USER_AGENT], "ok");
+        assert!(!scenario.timed);
+        assert!(load_lines("empty", "nothing to read\n", None).is_err());
+    }
+
+    #[test]
+    fn offsets_timed_requests_by_speed() {
+        let scenario = load_lines(
+            "timed",
+            concat!(
+                "1.2.3.4 - - [01/Jan/2024:00:00:04 +0000] \"GET /b HTTP/1.1\" 200 1\n",
+                "1.2.3.4 - - [01/Jan/2024:00:00:00 +0000] \"GET /a HTTP/1.1\" 200 1\n",
+            ),
+            Some(2.0),
+        )
+        .unwrap();
+        assert!(scenario.timed);
+        let offsets: Vec<(String, Duration)> = scenario
+            .requests
+            .iter()
+            .map(|request| {
+                let at = request.extensions().get::<SendAt>().unwrap().0;
+                (request.uri().path().to_string(), at)
+            })
+            .collect();
+        assert_eq!(
+            offsets,
+            [
+                (String::from("/a"), Duration::ZERO),
+                (String::from("/b"), Duration::from_secs(2)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,504 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use percent_encoding::utf8_percent_encode;
+use percent_encoding::AsciiSet;
+use percent_encoding::NON_ALPHANUMERIC;
+
+// What --data-urlencode leaves as is.
+const URLENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// Options kt has no counterpart for, reported as dropped.
+const KNOWN_IGNORED: [&str; 6] = [
+    "insecure",
+    "cookie-jar",
+    "max-time",
+    "connect-timeout",
+    "retry",
+    "tcp-nodelay",
+];
+
+// Options that only change what curl prints.
+const OUTPUT_OPTIONS: [&str; 10] = [
+    "silent",
+    "show-error",
+    "verbose",
+    "include",
+    "fail",
+    "output",
+    "write-out",
+    "remote-name",
+    "no-progress-meter",
+    "progress-bar",
+];
+
+/// A request body part from -d, --data-binary and the like.
+struct Data {
+    text: String,
+    // The text is a path to read the data from.
+    file: bool,
+}
+
+/// The options of a curl command that kt can express.
+#[derive(Default)]
+struct CurlCommand {
+    url: Option<String>,
+    method: Option<String>,
+    headers: Vec<String>,
+    data: Vec<Data>,
+    // -F fields, which kt takes in the same syntax
+    form: Vec<String>,
+    get: bool,
+    json: bool,
+    follow_redirects: bool,
+    max_redirects: Option<String>,
+    // kt options with the same meaning as their curl option
+    options: Vec<String>,
+    ignored: Vec<String>,
+}
+
+/// Turns a curl command line, as one string or already split into words, into the arguments
+/// of a kt command sending the same request. Also returns the curl options that were dropped.
+pub fn to_kt_args(command: &[String]) -> Result<(Vec<String>, Vec<String>), anyhow::Error> {
+    let words = match command {
+        // Commands copied from browsers and docs are often split with backslash-newlines.
+        [line] => shlex::split(&line.replace("\\\r\n", " ").replace("\\\n", " "))
+            .ok_or_else(|| anyhow!("The curl command has an unclosed quote"))?,
+        words => words.to_vec(),
+    };
+    let mut words = words.into_iter().peekable();
+    if words
+        .peek()
+        .is_some_and(|word| word == "curl" || word.ends_with("/curl"))
+    {
+        words.next();
+    }
+
+    let mut curl = CurlCommand::default();
+    while let Some(word) = words.next() {
+        if let Some(name) = word.strip_prefix("--") {
+            let value = if takes_value(name) {
+                Some(
+                    words
+                        .next()
+                        .ok_or_else(|| anyhow!("{word} needs a value"))?,
+                )
+            } else {
+                None
+            };
+            curl.apply(name, value)?;
+        } else if word.len() > 1 && word.starts_with('-') {
+            // Short options can be combined, e.g., -sSL, and take their value attached or
+            // as the next word, e.g., -XPUT or -X PUT.
+            for (index, short) in word.char_indices().skip(1) {
+                let name =
+                    long_name(short).ok_or_else(|| anyhow!("Unsupported curl option -{short}"))?;
+                if !takes_value(name) {
+                    curl.apply(name, None)?;
+                    continue;
+                }
+                let attached = &word[index + short.len_utf8()..];
+                let value = if attached.is_empty() {
+                    words
+                        .next()
+                        .ok_or_else(|| anyhow!("-{short} needs a value"))?
+                } else {
+                    attached.to_string()
+                };
+                curl.apply(name, Some(value))?;
+                break;
+            }
+        } else {
+            curl.url = Some(word);
+        }
+    }
+    let ignored = std::mem::take(&mut curl.ignored);
+    Ok((curl.into_kt_args()?, ignored))
+}
+
+/// The long name of a short curl option.
+fn long_name(short: char) -> Option<&'static str> {
+    let name = match short {
+        'X' => "request",
+        'H' => "header",
+        'd' => "data",
+        'F' => "form",
+        'u' => "user",
+        'A' => "user-agent",
+        'e' => "referer",
+        'b' => "cookie",
+        'c' => "cookie-jar",
+        'x' => "proxy",
+        'L' => "location",
+        'G' => "get",
+        'I' => "head",
+        'k' => "insecure",
+        'm' => "max-time",
+        's' => "silent",
+        'S' => "show-error",
+        'v' => "verbose",
+        'i' => "include",
+        'f' => "fail",
+        'o' => "output",
+        'O' => "remote-name",
+        'w' => "write-out",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn takes_value(name: &str) -> bool {
+    matches!(
+        name,
+        "url"
+            | "request"
+            | "header"
+            | "form"
+            | "user-agent"
+            | "referer"
+            | "cookie"
+            | "cookie-jar"
+            | "user"
+            | "data"
+            | "data-ascii"
+            | "data-binary"
+            | "data-raw"
+            | "data-urlencode"
+            | "json"
+            | "max-redirs"
+            | "proxy"
+            | "unix-socket"
+            | "cacert"
+            | "resolve"
+            | "interface"
+            | "output"
+            | "write-out"
+            | "max-time"
+            | "connect-timeout"
+            | "retry"
+    )
+}
+
+impl CurlCommand {
+    fn apply(&mut self, name: &str, value: Option<String>) -> Result<(), anyhow::Error> {
+        let value = value.unwrap_or_default();
+        match name {
+            "url" => self.url = Some(value),
+            "request" => self.method = Some(value),
+            "head" => self.method = Some(String::from("HEAD")),
+            "get" => self.get = true,
+            "header" => self.headers.push(match value.strip_suffix(';') {
+                // `Name;` sends the header without a value.
+                Some(name) if !value.contains(':') => format!("{name}:"),
+                _ => value,
+            }),
+            "user-agent" => self.headers.push(format!("User-Agent: {value}")),
+            "referer" => self.headers.push(format!("Referer: {value}")),
+            "cookie" if value.contains('=') => self.headers.push(format!("Cookie: {value}")),
+            "user" => {
+                let credentials = if value.contains(':') {
+                    value
+                } else {
+                    format!("{value}:")
+                };
+                self.headers.push(format!(
+                    "Authorization: Basic {}",
+                    STANDARD.encode(credentials)
+                ));
+            }
+            "data" | "data-ascii" | "data-binary" | "json" => {
+                self.json |= name == "json";
+                self.data.push(match value.strip_prefix('@') {
+                    Some(path) => Data {
+                        text: path.to_string(),
+                        file: true,
+                    },
+                    None => Data {
+                        text: value,
+                        file: false,
+                    },
+                });
+            }
+            "data-raw" => self.data.push(Data {
+                text: value,
+                file: false,
+            }),
+            "data-urlencode" => {
+                let text = match value.split_once('=') {
+                    Some(("", content)) => utf8_percent_encode(content, URLENCODE).to_string(),
+                    Some((key, content)) => {
+                        format!("{key}={}", utf8_percent_encode(content, URLENCODE))
+                    }
+                    None if value.contains('@') => {
+                        return Err(anyhow!(
+                            "--data-urlencode reading a file is not supported: {value}"
+                        ))
+                    }
+                    None => utf8_percent_encode(&value, URLENCODE).to_string(),
+                };
+                self.data.push(Data { text, file: false });
+            }
+            "form" => {
+                if value
+                    .split_once('=')
+                    .is_some_and(|(_, value)| value.starts_with('<'))
+                {
+                    return Err(anyhow!(
+                        "-F reading a field from a file is not supported: {value}"
+                    ));
+                }
+                self.form.push(value);
+            }
+            "location" => self.follow_redirects = true,
+            "max-redirs" => self.max_redirects = Some(value),
+            "proxy" | "unix-socket" | "cacert" | "resolve" | "interface" => {
+                self.options.extend([format!("--{name}"), value]);
+            }
+            "http2-prior-knowledge" => self.options.push(format!("--{name}")),
+            "compressed" => self.options.extend([
+                String::from("--accept-encoding"),
+                String::from("gzip,deflate,br,zstd"),
+            ]),
+            "http3" | "http3-only" => self.options.push(String::from("--http3")),
+            // kt negotiates HTTP/2 over TLS and uses HTTP/1.1 otherwise, like curl.
+            "http1.1" | "http2" => {}
+            _ if OUTPUT_OPTIONS.contains(&name) => {}
+            _ if KNOWN_IGNORED.contains(&name) => self.ignored.push(format!("--{name}")),
+            "cookie" => self.ignored.push(String::from("--cookie (file)")),
+            _ => return Err(anyhow!("Unsupported curl option --{name}")),
+        }
+        Ok(())
+    }
+
+    fn into_kt_args(self) -> Result<Vec<String>, anyhow::Error> {
+        let url = self
+            .url
+            .ok_or_else(|| anyhow!("The curl command has no URL"))?;
+        // curl assumes http:// for a bare host.
+        let mut url = if url.contains("://") {
+            url
+        } else {
+            format!("http://{url}")
+        };
+        let body = match self.data.as_slice() {
+            [] => None,
+            [Data { text, file: true }] => Some(format!("@{text}")),
+            data if data.iter().any(|data| data.file) => {
+                return Err(anyhow!(
+                    "kt cannot send data from a file together with other data"
+                ))
+            }
+            data => {
+                let text = data
+                    .iter()
+                    .map(|data| data.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("&");
+                if text.starts_with('@') {
+                    return Err(anyhow!("kt reads a body starting with @ as a file: {text}"));
+                }
+                Some(text)
+            }
+        };
+        // -G sends the data as the query string of a GET request.
+        let body = match body {
+            Some(query) if self.get => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                url = format!("{url}{separator}{query}");
+                None
+            }
+            body => body,
+        };
+
+        if body.is_some() && !self.form.is_empty() {
+            return Err(anyhow!("kt cannot send -F fields together with data"));
+        }
+
+        let mut args = vec![];
+        let default_method = if body.is_some() || !self.form.is_empty() {
+            "POST"
+        } else {
+            "GET"
+        };
+        if let Some(method) = self.method.filter(|method| method != default_method) {
+            args.extend([String::from("-X"), method]);
+        }
+        let mut headers = self.headers;
+        if self.json {
+            for (name, value) in [
+                ("Content-Type", "application/json"),
+                ("Accept", "application/json"),
+            ] {
+                let prefix = format!("{}:", name.to_lowercase());
+                if !headers
+                    .iter()
+                    .any(|header| header.to_lowercase().starts_with(&prefix))
+                {
+                    headers.push(format!("{name}: {value}"));
+                }
+            }
+        }
+        for header in headers {
+            args.extend([String::from("-H"), header]);
+        }
+        if let Some(body) = body {
+            args.extend([String::from("-b"), body]);
+        }
+        for field in self.form {
+            args.extend([String::from("-F"), field]);
+        }
+        if self.follow_redirects {
+            args.push(String::from("-L"));
+            if let Some(max_redirects) = self.max_redirects {
+                args.extend([String::from("--max-redirects"), max_redirects]);
+            }
+        }
+        args.extend(self.options);
+        args.push(url);
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(line: &str) -> Result<(Vec<String>, Vec<String>), anyhow::Error> {
+        to_kt_args(&[line.to_string()])
+    }
+
+    fn args(line: &str) -> Vec<String> {
+        convert(line).unwrap().0
+    }
+
+    #[test]
+    fn converts_requests_with_bodies() {
+        let (args, ignored) = convert(
+            "curl -sSL -X POST 'https://api.example.com/users' -H 'Content-Type: application/json' \
+             --data-raw '{\"a\":1}' --max-redirs 3 -k",
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            [
+                "-H",
+                "Content-Type: application/json",
+                "-b",
+                "{\"a\":1}",
+                "-L",
+                "--max-redirects",
+                "3",
+                "https://api.example.com/users",
+            ]
+        );
+        assert_eq!(ignored, ["--insecure"]);
+    }
+
+    #[test]
+    fn converts_short_options_and_headers() {
+        assert_eq!(
+            args("curl -XPUT -u user -A kt/1 -H 'X-Empty;' http://h/"),
+            [
+                "-X",
+                "PUT",
+                "-H",
+                "Authorization: Basic dXNlcjo=",
+                "-H",
+                "User-Agent: kt/1",
+                "-H",
+                "X-Empty:",
+                "http://h/",
+            ]
+        );
+        // Already split into words, as given after `kt from-curl --`
+        let words = ["curl", "-H", "X-Id: 1", "https://h/"].map(String::from);
+        assert_eq!(
+            to_kt_args(&words).unwrap().0,
+            ["-H", "X-Id: 1", "https://h/"]
+        );
+    }
+
+    #[test]
+    fn converts_json_and_encoded_data() {
+        assert_eq!(
+            args("curl --json '{\"x\":1}' -H 'accept: text/plain' https://h/"),
+            [
+                "-H",
+                "accept: text/plain",
+                "-H",
+                "Content-Type: application/json",
+                "-b",
+                "{\"x\":1}",
+                "https://h/",
+            ]
+        );
+        assert_eq!(
+            args("curl --data-urlencode 'q=a b&c' -d x=1 https://h/"),
+            ["-b", "q=a%20b%26c&x=1", "https://h/"]
+        );
+        assert_eq!(
+            args("curl -d @body.json h"),
+            ["-b", "@body.json", "http://h"]
+        );
+    }
+
+    #[test]
+    fn moves_data_into_the_query_with_get() {
+        assert_eq!(
+            args("curl example.com/search \\\n  -G -d q=rust \\\n  -d page=2"),
+            ["http://example.com/search?q=rust&page=2"]
+        );
+        assert_eq!(
+            args("curl -G -d b=2 'http://h/?a=1'"),
+            ["http://h/?a=1&b=2"]
+        );
+    }
+
+    #[test]
+    fn converts_form_fields() {
+        assert_eq!(
+            args("curl -F 'file=@a.png;type=image/png' -F name=x https://h/upload"),
+            [
+                "-F",
+                "file=@a.png;type=image/png",
+                "-F",
+                "name=x",
+                "https://h/upload",
+            ]
+        );
+        assert_eq!(
+            args("curl -X POST -F a=1 https://h/"),
+            ["-F", "a=1", "https://h/"]
+        );
+    }
+
+    #[test]
+    fn rejects_what_kt_cannot_send() {
+        for (line, message) in [
+            (
+                "curl -F a=1 -d b=2 https://h/",
+                "-F fields together with data",
+            ),
+            (
+                "curl -F 'a=<notes.txt' https://h/",
+                "reading a field from a file",
+            ),
+            ("curl -d @a.json -d b=2 https://h/", "from a file together"),
+            ("curl --data-urlencode @a.txt https://h/", "reading a file"),
+            (
+                "curl --digest https://h/",
+                "Unsupported curl option --digest",
+            ),
+            ("curl -Z https://h/", "Unsupported curl option -Z"),
+            ("curl -H", "-H needs a value"),
+            ("curl -H 'X: 1", "unclosed quote"),
+            ("curl -s", "has no URL"),
+        ] {
+            let error = convert(line).unwrap_err().to_string();
+            assert!(error.contains(message), "{line}: {error}");
+        }
+    }
+}
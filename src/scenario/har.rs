@@ -0,0 +1,334 @@
+use super::parse_timestamp_ms;
+use super::Scenario;
+use super::ThinkTime;
+use crate::output::report::Labels;
+use http::header::CONTENT_TYPE;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Request;
+use http::Uri;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+pub const LABEL_ENTRY: &str = "Entry";
+
+// Set by the client for every request, or not allowed in HTTP/2 and HTTP/3, which many
+// recordings use. HTTP/2 pseudo-headers starting with ':' are dropped as well.
+pub const SKIPPED_HEADERS: [&str; 7] = [
+    "host",
+    "content-length",
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+// Long URLs are cut in the per-entry report.
+const MAX_LABEL_PATH: usize = 60;
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: String,
+    // Milliseconds the whole exchange took
+    #[serde(default)]
+    time: f64,
+    request: HarRequest,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarNameValue>,
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Deserialize)]
+struct HarNameValue {
+    name: String,
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarPostData {
+    #[serde(default)]
+    mime_type: String,
+    text: Option<String>,
+    #[serde(default)]
+    params: Vec<HarNameValue>,
+}
+
+/// Which hosts of a recording are replayed. A pattern is a host name, or `*.example.com` for
+/// example.com and all its subdomains.
+pub struct HostFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl HostFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        let lowercase = |patterns: &[String]| patterns.iter().map(|p| p.to_lowercase()).collect();
+        HostFilter {
+            include: lowercase(include),
+            exclude: lowercase(exclude),
+        }
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        let matches = |pattern: &String| match pattern.strip_prefix("*.") {
+            Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
+            None => host == *pattern,
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Reads a HAR file into a scenario that replays its entries in the recorded order, each
+/// labelled for the per-entry report. With `think_times` every request first waits as long
+/// as the recorded session was idle before it.
+pub fn load(
+    path: &Path,
+    filter: &HostFilter,
+    think_times: bool,
+) -> Result<Scenario, anyhow::Error> {
+    let content =
+        std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let har: Har = serde_json::from_slice(&content)
+        .map_err(|e| anyhow!("{} is not a valid HAR file: {e}", path.display()))?;
+    let total = har.log.entries.len();
+    // Entries that cannot be sent are skipped and counted, unlike the ones left out on purpose.
+    let mut invalid = 0;
+    let mut built = vec![];
+    for entry in har.log.entries {
+        let Ok(uri) = entry.request.url.parse::<Uri>() else {
+            invalid += 1;
+            continue;
+        };
+        // data:, blob: and WebSocket entries cannot be replayed as HTTP requests.
+        if !matches!(uri.scheme_str(), Some("http") | Some("https"))
+            || !uri.host().is_some_and(|host| filter.allows(host))
+        {
+            continue;
+        }
+        let method = entry.request.method.clone();
+        match build_request(entry.request, uri.clone()) {
+            Ok(request) => built.push((request, method, uri, entry.started_date_time, entry.time)),
+            Err(e) => {
+                debug!("Skipping HAR entry {uri}: {e}");
+                invalid += 1;
+            }
+        }
+    }
+    if built.is_empty() {
+        return Err(anyhow!(
+            "None of the {total} entries in {} can be replayed ({invalid} invalid)",
+            path.display()
+        ));
+    }
+    info!(
+        "Replaying {} of {total} entries from {} ({invalid} invalid entries skipped)",
+        built.len(),
+        path.display()
+    );
+
+    let width = built.len().to_string().len();
+    let mut previous_end: Option<f64> = None;
+    let mut requests = vec![];
+    for (index, (mut request, method, uri, started_date_time, time)) in
+        built.into_iter().enumerate()
+    {
+        let label = entry_label(index + 1, width, &method, &uri);
+        let started = parse_timestamp_ms(&started_date_time);
+        if think_times {
+            if let (Some(started), Some(previous_end)) = (started, previous_end) {
+                let idle = Duration::from_secs_f64((started - previous_end).max(0.0) / 1000.0);
+                request.extensions_mut().insert(ThinkTime(idle));
+            }
+        }
+        if let Some(started) = started {
+            let end = started + time.max(0.0);
+            previous_end = Some(previous_end.map_or(end, |previous| previous.max(end)));
+        }
+        let labels: Labels = vec![(LABEL_ENTRY, label)];
+        request.extensions_mut().insert(labels);
+        requests.push(request);
+    }
+    Ok(Scenario {
+        name: Some(format!("{} ({} requests)", path.display(), requests.len())),
+        requests,
+        sequential: true,
+        timed: false,
+        journey: None,
+    })
+}
+
+fn build_request(har: HarRequest, uri: Uri) -> Result<Request<Full<Bytes>>, anyhow::Error> {
+    let mut request = Request::builder()
+        .method(har.method.as_str())
+        .uri(uri)
+        .body(())?;
+    for header in &har.headers {
+        let name = header.name.to_lowercase();
+        if name.starts_with(':') || SKIPPED_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        request.headers_mut().append(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(&header.value)?,
+        );
+    }
+    let body = match har.post_data {
+        Some(post_data) => {
+            if !post_data.mime_type.is_empty() && !request.headers().contains_key(CONTENT_TYPE) {
+                request
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, HeaderValue::from_str(&post_data.mime_type)?);
+            }
+            match post_data.text {
+                Some(text) => Bytes::from(text),
+                // Form posts may be recorded as params only.
+                None => url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(post_data.params.iter().map(|p| (&p.name, &p.value)))
+                    .finish()
+                    .into(),
+            }
+        }
+        None => Bytes::new(),
+    };
+    let (parts, _) = request.into_parts();
+    Ok(Request::from_parts(parts, Full::new(body)))
+}
+
+/// `#03 GET api.example.com/users`, numbered so the report lists entries in recorded order.
+fn entry_label(number: usize, width: usize, method: &str, uri: &Uri) -> String {
+    let path = uri.path();
+    let path = match path.char_indices().nth(MAX_LABEL_PATH) {
+        Some((cut, _)) => format!("{}...", &path[..cut]),
+        None => path.to_string(),
+    };
+    format!(
+        "#{number:0width$} {method} {}{path}",
+        uri.authority().map_or("", |a| a.as_str())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_entries(
+        name: &str,
+        entries: serde_json::Value,
+        filter: &HostFilter,
+    ) -> Result<Scenario, anyhow::Error> {
+        let path = std::env::temp_dir().join(format!("kt-har-{}-{name}", std::process::id()));
+        let har = serde_json::json!({ "log": { "entries": entries } });
+        std::fs::write(&path, har.to_string()).unwrap();
+        let scenario = load(&path, filter, true);
+        std::fs::remove_file(&path).unwrap();
+        scenario
+    }
+
+    fn entry(method: &str, url: &str, started: &str) -> serde_json::Value {
+        serde_json::json!({
+            "startedDateTime": started,
+            "time": 100.0,
+            "request": { "method": method, "url": url, "headers": [] }
+        })
+    }
+
+    fn label(request: &Request<Full<Bytes>>) -> &str {
+        &request.extensions().get::<Labels>().unwrap()[0].1
+    }
+
+    #[test]
+    fn builds_requests_in_recorded_order() {
+        let mut post = entry(
+            "POST",
+            "https://api.example.com/users",
+            "2024-05-01T10:00:00.500Z",
+        );
+        post["request"]["headers"] = serde_json::json!([
+            { "name": ":authority", "value": "api.example.com" },
+            { "name": "Content-Length", "value": "7" },
+            { "name": "X-Trace", "value": "abc" }
+        ]);
+        post["request"]["postData"] = serde_json::json!({
+            "mimeType": "application/x-www-form-urlencoded",
+            "params": [{ "name": "a b", "value": "1" }]
+        });
+        let entries = serde_json::json!([
+            entry("GET", "https://example.com/", "2024-05-01T10:00:00Z"),
+            post,
+            entry("GET", "data:text/plain,hi", "2024-05-01T10:00:01Z"),
+        ]);
+        let scenario = load_entries("order", entries, &HostFilter::new(&[], &[])).unwrap();
+        assert_eq!(scenario.requests.len(), 2);
+        assert_eq!(label(&scenario.requests[0]), "#1 GET example.com/");
+        let request = &scenario.requests[1];
+        assert_eq!(label(request), "#2 POST api.example.com/users");
+        assert_eq!(request.headers().len(), 2);
+        assert_eq!(request.headers()["x-trace"], "abc");
+        assert_eq!(
+            request.headers()[CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        // The first entry ended at 10:00:00.100.
+        let think_time = request.extensions().get::<ThinkTime>().unwrap();
+        assert_eq!(think_time.0, Duration::from_millis(400));
+    }
+
+    #[test]
+    fn filters_hosts() {
+        let filter = HostFilter::new(&["*.example.com".to_string()], &["ads.example.com".into()]);
+        assert!(filter.allows("example.com"));
+        assert!(filter.allows("API.Example.com"));
+        assert!(!filter.allows("ads.example.com"));
+        assert!(!filter.allows("example.org"));
+        assert!(!filter.allows("badexample.com"));
+    }
+
+    #[test]
+    fn skips_invalid_entries() {
+        let mut bad_header = entry("GET", "https://example.com/a", "2024-05-01T10:00:00Z");
+        bad_header["request"]["headers"] = serde_json::json!([{ "name": "X", "value": "a\nb" }]);
+        let mut bad_mime = entry("POST", "https://example.com/b", "2024-05-01T10:00:01Z");
+        bad_mime["request"]["postData"] =
+            serde_json::json!({ "mimeType": "text/\u{1}", "text": "" });
+        let entries = serde_json::json!([
+            bad_header,
+            bad_mime,
+            entry("GET", "https://exa mple.com/", "2024-05-01T10:00:02Z"),
+            entry("GET", "https://example.com/c", "2024-05-01T10:00:03Z"),
+        ]);
+        let scenario = load_entries("invalid", entries, &HostFilter::new(&[], &[])).unwrap();
+        assert_eq!(scenario.requests.len(), 1);
+        assert_eq!(label(&scenario.requests[0]), "#1 GET example.com/c");
+
+        let entries = serde_json::json!([entry("GET", "https://exa mple.com/", "")]);
+        let Err(error) = load_entries("none", entries, &HostFilter::new(&[], &[])) else {
+            panic!("an invalid entry was replayed");
+        };
+        assert!(error.to_string().contains("(1 invalid)"), "{error}");
+    }
+}
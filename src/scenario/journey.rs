@@ -0,0 +1,494 @@
+use super::Scenario;
+use crate::output::report::Labels;
+use http::header::CONTENT_TYPE;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Method;
+use http::Request;
+use http::Uri;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub const LABEL_STEP: &str = "Step";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JourneyFile {
+    name: Option<String>,
+    // Prefixed to step URLs starting with '/'
+    base_url: Option<String>,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    steps: Vec<StepFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepFile {
+    name: Option<String>,
+    method: Option<String>,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    // A string is sent as is, anything else as JSON
+    body: Option<Value>,
+    #[serde(default)]
+    extract: BTreeMap<String, ExtractFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtractFile {
+    json: Option<String>,
+    header: Option<String>,
+    regex: Option<String>,
+}
+
+/// Steps every worker walks in order, each able to use the values earlier steps extracted
+/// from their responses as {{name}} in its URL, headers and body.
+pub struct Journey {
+    name: String,
+    pub variables: HashMap<String, String>,
+    pub steps: Vec<Step>,
+}
+
+pub struct Step {
+    label: String,
+    method: Method,
+    url: String,
+    headers: Vec<(HeaderName, String)>,
+    body: String,
+    extract: Vec<(String, Extract)>,
+}
+
+/// Where a variable is read from in a response.
+enum Extract {
+    Json(Vec<PathSegment>),
+    // With a regex only its match in the header value is kept
+    Header(HeaderName, Option<Regex>),
+    Body(Regex),
+}
+
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Reads a journey file, checking that every step only uses variables it can know.
+pub fn load(path: &Path) -> Result<Journey, anyhow::Error> {
+    let content =
+        std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let file: JourneyFile = serde_json::from_slice(&content)
+        .map_err(|e| anyhow!("{} is not a valid journey file: {e}", path.display()))?;
+    if file.steps.is_empty() {
+        return Err(anyhow!("{} has no steps", path.display()));
+    }
+
+    let width = file.steps.len().to_string().len();
+    // Every {{name}} must be set up front or extracted by an earlier step. A stand-in value
+    // keeps the URL valid while checking.
+    let mut known: HashMap<String, String> = file
+        .variables
+        .keys()
+        .map(|name| (name.clone(), String::from("0")))
+        .collect();
+    let mut steps = vec![];
+    for (index, step) in file.steps.into_iter().enumerate() {
+        let label = format!(
+            "#{:0width$} {}",
+            index + 1,
+            step.name.as_deref().unwrap_or(&step.url)
+        );
+        let step = Step::new(step, label, file.base_url.as_deref())?;
+        step.render(&known)
+            .map_err(|e| anyhow!("Step {}: {e}", step.label))?;
+        for (name, _) in &step.extract {
+            known.insert(name.clone(), String::from("0"));
+        }
+        steps.push(step);
+    }
+    Ok(Journey {
+        name: format!(
+            "{} ({} steps)",
+            file.name.unwrap_or_else(|| path.display().to_string()),
+            steps.len()
+        ),
+        variables: file.variables,
+        steps,
+    })
+}
+
+impl Journey {
+    /// The first URL of the journey, standing in for the URL of the test plan.
+    pub fn first_url(&self) -> Result<Uri, anyhow::Error> {
+        Ok(self.steps[0].render(&self.variables)?.uri().clone())
+    }
+
+    /// Sends the header with every step, replacing the step's own. The value may use variables.
+    pub fn set_header(&mut self, name: HeaderName, value: &str) {
+        for step in &mut self.steps {
+            step.headers.retain(|(existing, _)| *existing != name);
+            step.headers.push((name.clone(), value.to_string()));
+        }
+    }
+
+    /// A scenario whose workers run the journey over and over.
+    pub fn into_scenario(self) -> Scenario {
+        Scenario {
+            name: Some(self.name.clone()),
+            requests: vec![],
+            sequential: true,
+            timed: false,
+            journey: Some(Arc::new(self)),
+        }
+    }
+}
+
+impl Step {
+    fn new(file: StepFile, label: String, base_url: Option<&str>) -> Result<Step, anyhow::Error> {
+        let url = match base_url {
+            Some(base) if file.url.starts_with('/') => {
+                format!("{}{}", base.trim_end_matches('/'), file.url)
+            }
+            _ => file.url,
+        };
+        let mut headers = vec![];
+        for (name, value) in file.headers {
+            headers.push((HeaderName::from_str(&name)?, value));
+        }
+        let body = match file.body {
+            None => String::new(),
+            Some(Value::String(text)) => text,
+            Some(json) => {
+                if !headers.iter().any(|(name, _)| name == CONTENT_TYPE) {
+                    headers.push((CONTENT_TYPE, String::from("application/json")));
+                }
+                json.to_string()
+            }
+        };
+        let method = match file.method {
+            Some(method) => Method::from_str(&method.to_uppercase())?,
+            None if body.is_empty() => Method::GET,
+            None => Method::POST,
+        };
+        let mut extract = vec![];
+        for (name, source) in file.extract {
+            let regex = source
+                .regex
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|e| anyhow!("Step {label}: invalid regex for {name}: {e}"))?;
+            let source = match (source.json, source.header, regex) {
+                (Some(path), None, None) => Extract::Json(parse_json_path(&path)?),
+                (None, Some(header), regex) => {
+                    Extract::Header(HeaderName::from_str(&header)?, regex)
+                }
+                (None, None, Some(regex)) => Extract::Body(regex),
+                _ => {
+                    return Err(anyhow!(
+                        "Step {label}: extract {name} with json, header, regex, or header and regex"
+                    ))
+                }
+            };
+            extract.push((name, source));
+        }
+        Ok(Step {
+            label,
+            method,
+            url,
+            headers,
+            body,
+            extract,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The request of this step with `variables` filled in, labelled for the per-step report.
+    pub fn render(
+        &self,
+        variables: &HashMap<String, String>,
+    ) -> Result<Request<Full<Bytes>>, anyhow::Error> {
+        let uri: Uri = render(&self.url, variables)?.parse()?;
+        let mut request = Request::builder()
+            .method(self.method.clone())
+            .uri(uri)
+            .body(Full::new(Bytes::from(render(&self.body, variables)?)))?;
+        for (name, value) in &self.headers {
+            request.headers_mut().append(
+                name.clone(),
+                HeaderValue::from_str(&render(value, variables)?)?,
+            );
+        }
+        let labels: Labels = vec![(LABEL_STEP, self.label.clone())];
+        request.extensions_mut().insert(labels);
+        Ok(request)
+    }
+
+    /// Reads this step's variables from its response into `variables`.
+    pub fn extract(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        variables: &mut HashMap<String, String>,
+    ) -> Result<(), anyhow::Error> {
+        for (name, source) in &self.extract {
+            let value = match source {
+                Extract::Json(path) => serde_json::from_slice::<Value>(body)
+                    .ok()
+                    .and_then(|json| lookup(&json, path)),
+                Extract::Header(header, regex) => headers
+                    .get_all(header)
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .find_map(|value| match regex {
+                        Some(regex) => first_match(regex, value),
+                        None => Some(value.to_string()),
+                    }),
+                Extract::Body(regex) => first_match(regex, &String::from_utf8_lossy(body)),
+            };
+            let value = value.ok_or_else(|| anyhow!("Step {}: no value for {name}", self.label))?;
+            variables.insert(name.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+/// The first capture group of the first match, or the whole match without groups.
+fn first_match(regex: &Regex, text: &str) -> Option<String> {
+    let captures = regex.captures(text)?;
+    let matched = captures.get(1).or_else(|| captures.get(0))?;
+    Some(matched.as_str().to_string())
+}
+
+/// `template` with every {{name}} replaced by its variable.
+fn render(template: &str, variables: &HashMap<String, String>) -> Result<String, anyhow::Error> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| anyhow!("Unclosed {{{{ in {template}"))?;
+        let name = rest[start + 2..start + end].trim();
+        let value = variables.get(name).ok_or_else(|| {
+            anyhow!("{name} is neither a variable nor extracted by an earlier step")
+        })?;
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Parses a JSON path such as `$.data.items[0].id` or `$['user name']`.
+fn parse_json_path(path: &str) -> Result<Vec<PathSegment>, anyhow::Error> {
+    let invalid = || anyhow!("Invalid JSON path {path}");
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut segments = vec![];
+    while !rest.is_empty() {
+        if let Some(bracketed) = rest.strip_prefix('[') {
+            let end = bracketed.find(']').ok_or_else(invalid)?;
+            let inner = &bracketed[..end];
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|inner| inner.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"')?.strip_suffix('"'));
+            segments.push(match quoted {
+                Some(key) => PathSegment::Key(key.to_string()),
+                None => PathSegment::Index(inner.parse().map_err(|_| invalid())?),
+            });
+            rest = &bracketed[end + 1..];
+        } else {
+            let key = rest.strip_prefix('.').unwrap_or(rest);
+            let end = key.find(['.', '[']).unwrap_or(key.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Key(key[..end].to_string()));
+            rest = &key[end..];
+        }
+    }
+    Ok(segments)
+}
+
+/// The value at `path` in `json`, with strings unquoted.
+fn lookup(json: &Value, path: &[PathSegment]) -> Option<String> {
+    let mut value = json;
+    for segment in path {
+        value = match segment {
+            PathSegment::Key(key) => value.get(key)?,
+            PathSegment::Index(index) => value.get(index)?,
+        };
+    }
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_json(name: &str, journey: Value) -> Result<Journey, anyhow::Error> {
+        let path = std::env::temp_dir().join(format!("kt-journey-{}-{name}", std::process::id()));
+        std::fs::write(&path, journey.to_string()).unwrap();
+        let journey = load(&path);
+        std::fs::remove_file(&path).unwrap();
+        journey
+    }
+
+    fn variables(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn at(json: &Value, path: &str) -> Option<String> {
+        lookup(json, &parse_json_path(path).unwrap())
+    }
+
+    #[test]
+    fn looks_up_json_paths() {
+        let json = serde_json::json!({
+            "data": { "items": [{ "id": 7 }, { "id": "b", "tags": ["x"] }] },
+            "user name": "ann",
+            "token": "abc",
+            "nothing": null,
+            "flag": true
+        });
+        assert_eq!(at(&json, "$.data.items[0].id").as_deref(), Some("7"));
+        assert_eq!(at(&json, "data.items[1].id").as_deref(), Some("b"));
+        assert_eq!(at(&json, "$.data.items[1].tags[0]").as_deref(), Some("x"));
+        assert_eq!(at(&json, "$['user name']").as_deref(), Some("ann"));
+        assert_eq!(at(&json, "$[\"token\"]").as_deref(), Some("abc"));
+        assert_eq!(at(&json, "$.flag").as_deref(), Some("true"));
+        assert_eq!(at(&json, "$.data.items[0]").as_deref(), Some("{\"id\":7}"));
+        assert_eq!(at(&json, "$.nothing"), None);
+        assert_eq!(at(&json, "$.data.items[2]"), None);
+        assert_eq!(at(&json, "$.data[0]"), None);
+        assert_eq!(at(&json, "$.missing.id"), None);
+        for path in ["$.a..b", "$.items[x]", "$.items[0", "$."] {
+            assert!(parse_json_path(path).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn renders_templates() {
+        let variables = variables(&[("id", "42"), ("token", "abc")]);
+        assert_eq!(
+            render("/users/{{id}}?t={{ token }}", &variables).unwrap(),
+            "/users/42?t=abc"
+        );
+        assert_eq!(render("no variables", &variables).unwrap(), "no variables");
+        assert_eq!(render("{{id}}{{id}}", &variables).unwrap(), "4242");
+        let error = render("/users/{{user}}", &variables).unwrap_err();
+        assert!(error.to_string().contains("user is neither"), "{error}");
+        let error = render("/users/{{id", &variables).unwrap_err();
+        assert!(error.to_string().contains("Unclosed {{"), "{error}");
+    }
+
+    #[test]
+    fn loads_steps_using_earlier_extractions() {
+        let journey = load_json(
+            "steps",
+            serde_json::json!({
+                "name": "checkout",
+                "base_url": "http://shop.test/",
+                "variables": { "sku": "a1" },
+                "steps": [
+                    {
+                        "name": "login",
+                        "url": "/login",
+                        "body": { "user": "ann" },
+                        "extract": {
+                            "token": { "json": "$.token" },
+                            "session": { "header": "set-cookie", "regex": "sid=(\\w+)" }
+                        }
+                    },
+                    {
+                        "method": "put",
+                        "url": "/cart/{{sku}}",
+                        "headers": { "Authorization": "Bearer {{token}}" },
+                        "extract": { "order": { "regex": "order-(\\d+)" } }
+                    },
+                    { "url": "/orders/{{order}}?s={{session}}" }
+                ]
+            }),
+        )
+        .unwrap();
+        assert_eq!(journey.name, "checkout (3 steps)");
+        assert_eq!(journey.first_url().unwrap(), "http://shop.test/login");
+
+        let mut variables = journey.variables.clone();
+        let login = &journey.steps[0];
+        assert_eq!(login.label(), "#1 login");
+        let request = login.render(&variables).unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        let mut headers = HeaderMap::new();
+        headers.append("set-cookie", HeaderValue::from_static("theme=dark"));
+        headers.append("set-cookie", HeaderValue::from_static("sid=s9; Path=/"));
+        login
+            .extract(&headers, br#"{"token":"t0k"}"#, &mut variables)
+            .unwrap();
+        assert_eq!(variables["token"], "t0k");
+        assert_eq!(variables["session"], "s9");
+
+        let cart = &journey.steps[1];
+        assert_eq!(cart.label(), "#2 /cart/{{sku}}");
+        let request = cart.render(&variables).unwrap();
+        assert_eq!(request.method(), Method::PUT);
+        assert_eq!(request.uri(), "http://shop.test/cart/a1");
+        assert_eq!(request.headers()["authorization"], "Bearer t0k");
+        let error = cart
+            .extract(&HeaderMap::new(), b"no order", &mut variables)
+            .unwrap_err();
+        assert!(error.to_string().contains("no value for order"), "{error}");
+        cart.extract(&HeaderMap::new(), b"created order-17", &mut variables)
+            .unwrap();
+
+        let request = journey.steps[2].render(&variables).unwrap();
+        assert_eq!(request.uri(), "http://shop.test/orders/17?s=s9");
+    }
+
+    #[test]
+    fn rejects_variables_no_earlier_step_extracts() {
+        let steps = serde_json::json!({
+            "steps": [
+                { "url": "http://shop.test/orders/{{order}}" },
+                { "url": "http://shop.test/", "extract": { "order": { "json": "$.id" } } }
+            ]
+        });
+        let Err(error) = load_json("order", steps) else {
+            panic!("a variable was used before it was extracted");
+        };
+        assert!(error.to_string().contains("order is neither"), "{error}");
+
+        let steps = serde_json::json!({
+            "steps": [{
+                "url": "http://shop.test/",
+                "extract": { "id": { "json": "$.id", "regex": "\\d+" } }
+            }]
+        });
+        let Err(error) = load_json("both", steps) else {
+            panic!("an extraction with two sources was accepted");
+        };
+        assert!(error.to_string().contains("extract id with"), "{error}");
+    }
+}
@@ -0,0 +1,115 @@
+pub mod access_log;
+pub mod curl;
+pub mod har;
+pub mod journey;
+
+use crate::calendar;
+use http::Request;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use journey::Journey;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The requests a test sends.
+pub struct Scenario {
+    pub requests: Vec<Request<Full<Bytes>>>,
+    /// Every worker walks the requests in order, waiting their ThinkTime first, instead of
+    /// all workers sharing one rotation.
+    pub sequential: bool,
+    /// Shown in reports instead of the URL.
+    pub name: Option<String>,
+    /// Every request is sent once at its SendAt offset, however long earlier ones take,
+    /// instead of by looping workers.
+    pub timed: bool,
+    /// Every worker runs the journey instead of sending `requests`.
+    pub journey: Option<Arc<Journey>>,
+}
+
+impl Scenario {
+    /// Requests all workers send in turn, e.g., the single request built from the command line.
+    pub fn rotating(requests: Vec<Request<Full<Bytes>>>) -> Self {
+        Scenario {
+            requests,
+            sequential: false,
+            name: None,
+            timed: false,
+            journey: None,
+        }
+    }
+}
+
+/// When the request carrying it is sent in a timed scenario, counted from the start.
+#[derive(Clone, Copy, Debug)]
+pub struct SendAt(pub Duration);
+
+/// How long a worker waits before sending the request carrying it in its extensions.
+#[derive(Clone, Copy, Debug)]
+pub struct ThinkTime(pub Duration);
+
+/// How long a worker pauses between its requests, drawn anew for every pause.
+#[derive(Clone, Copy, Debug)]
+pub enum ThinkTimeDistribution {
+    Fixed(Duration),
+    Uniform(Duration, Duration),
+    Exponential { mean: Duration },
+    Normal { mean: Duration, std_dev: Duration },
+}
+
+impl ThinkTimeDistribution {
+    pub fn sample(&self) -> Duration {
+        let mut rng = rand::thread_rng();
+        let seconds = match *self {
+            ThinkTimeDistribution::Fixed(duration) => return duration,
+            ThinkTimeDistribution::Uniform(low, high) => {
+                rng.gen_range(low.as_secs_f64()..=high.as_secs_f64())
+            }
+            ThinkTimeDistribution::Exponential { mean } => {
+                -mean.as_secs_f64() * (1.0 - rng.gen::<f64>()).ln()
+            }
+            ThinkTimeDistribution::Normal { mean, std_dev } => {
+                // Box-Muller transform
+                let radius = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt();
+                let angle = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+                mean.as_secs_f64() + std_dev.as_secs_f64() * radius * angle.cos()
+            }
+        };
+        // Negative draws of the normal distribution mean no pause.
+        Duration::from_secs_f64(seconds.max(0.0))
+    }
+}
+
+/// Milliseconds since the epoch of an ISO 8601 time such as 2024-05-01T10:11:12.345+02:00.
+pub fn parse_timestamp_ms(text: &str) -> Option<f64> {
+    let number = |range: std::ops::Range<usize>| text.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute) = (number(11..13)?, number(14..16)?);
+    let rest = text.get(17..)?;
+    let seconds_end = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    let seconds: f64 = rest[..seconds_end].parse().ok()?;
+    let offset_minutes = match &rest[seconds_end..] {
+        "" | "Z" | "z" => 0,
+        zone => {
+            let sign = match zone.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let digits: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            let hours: i64 = digits.get(0..2)?.parse().ok()?;
+            let minutes: i64 = digits.get(2..4).unwrap_or("0").parse().ok()?;
+            sign * (hours * 60 + minutes)
+        }
+    };
+    let minutes = (hour * 60 + minute - offset_minutes) as f64;
+    Some(epoch_ms(year, month, day) + minutes * 60_000.0 + seconds * 1000.0)
+}
+
+/// Milliseconds since the epoch of midnight UTC on the given day of the proleptic Gregorian
+/// calendar.
+pub fn epoch_ms(year: i64, month: i64, day: i64) -> f64 {
+    calendar::days_from_civil(year, month, day) as f64 * 86_400_000.0
+}
@@ -1,7 +1,291 @@
+use crate::client::retry::RetryCondition;
+use crate::scenario::ThinkTimeDistribution;
+use clap::Args;
 use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
+use http::HeaderValue;
 use http::Uri;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::time::Duration;
+
+/// The default port an agent listens on and a controller dials.
+pub const DEFAULT_AGENT_PORT: u16 = 7878;
+
 /// A simple yet powerful HTTP stress testing tool.
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about,
+    long_about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct App {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub cli: Option<Cli>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run as an agent that waits for a controller to send it a test plan.
+    Agent(AgentArgs),
+    /// Drive several agents with the same test plan and merge their results.
+    Controller(ControllerArgs),
+    /// Send raw payloads over TCP and time the responses, for non-HTTP protocols.
+    Tcp(SocketArgs),
+    /// Send raw payloads as UDP datagrams and time the responses.
+    Udp(SocketArgs),
+    /// Replay the requests recorded in a HAR file, e.g., exported from browser devtools.
+    Har(HarArgs),
+    /// Replay the requests of an nginx/Apache combined access log or a JSONL request log.
+    Replay(ReplayArgs),
+    /// Run a multi-step user journey from a JSON file, e.g., login, then call an API with
+    /// the token extracted from the login response.
+    Journey(JourneyArgs),
+    /// Print a curl, k6 or wrk script that sends the same requests as a kt test plan.
+    Export(ExportArgs),
+    /// Print the kt command sending the request of a curl command line.
+    FromCurl(FromCurlArgs),
+    /// Raise the concurrency in short steps until latency or errors break an SLO, to find
+    /// the highest load the target sustains.
+    FindMax(FindMaxArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct AgentArgs {
+    /// The address to accept controller connections on.
+    #[arg(short = 'l', long, default_value_t = format!("0.0.0.0:{DEFAULT_AGENT_PORT}"))]
+    pub listen: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ControllerArgs {
+    /// Comma separated agent addresses, e.g., host1,host2:7979.
+    /// The port defaults to 7878 when omitted.
+    #[arg(short = 'a', long, value_delimiter = ',', required = true)]
+    pub agents: Vec<String>,
+
+    /// The test plan every agent runs, given as regular kt arguments after `--`.
+    /// e.g., kt controller --agents host1,host2 -- -c 100 -d 30s http://target/
+    #[arg(last = true, required = true)]
+    pub plan: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct HarArgs {
+    /// The HAR file to replay.
+    pub file: PathBuf,
+
+    /// Only replay requests to this host. `*.example.com` matches example.com and its
+    /// subdomains. This option can be used multiple times.
+    #[arg(long = "include-host", value_name = "HOST")]
+    pub include_hosts: Vec<String>,
+
+    /// Drop requests to this host, e.g., third-party analytics. Same patterns as
+    /// --include-host. This option can be used multiple times.
+    #[arg(long = "exclude-host", value_name = "HOST")]
+    pub exclude_hosts: Vec<String>,
+
+    /// Wait before every request as long as the recorded session was idle.
+    #[arg(long)]
+    pub think_times: bool,
+
+    /// Load shape and client options as regular kt arguments after `--`, without a URL.
+    /// e.g., kt har session.har -- -c 10 -d 1m
+    #[arg(last = true)]
+    pub plan: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// The log file to replay.
+    pub file: PathBuf,
+
+    /// The log format. Detected from the first line when omitted.
+    #[arg(long, value_enum)]
+    pub format: Option<LogFormat>,
+
+    /// Replay this many times faster than recorded, e.g., 2 for twice as fast, 0.5 for
+    /// half speed. Requests go out at their own time however slow the responses are.
+    #[arg(long, default_value_t = 1.0, conflicts_with = "max_speed")]
+    pub speed: f64,
+
+    /// Ignore the recorded timing and send the requests as fast as --concurrency allows.
+    #[arg(long)]
+    pub max_speed: bool,
+
+    /// The target URL and other kt arguments after `--`. Only the scheme and host of the URL
+    /// are used, paths come from the log. e.g., kt replay access.log -- http://staging:8080/
+    #[arg(last = true, required = true)]
+    pub plan: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct JourneyArgs {
+    /// The journey file, a JSON object with `steps`, and optionally `name`, `base_url` and
+    /// initial `variables`.
+    pub file: PathBuf,
+
+    /// Load shape and client options as regular kt arguments after `--`, without a URL.
+    /// --requests counts whole journeys. e.g., kt journey checkout.json -- -c 10 -d 1m
+    #[arg(last = true)]
+    pub plan: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// The tool the script is written for.
+    #[arg(long, value_enum)]
+    pub format: ExportFormat,
+
+    /// The test plan to export as regular kt arguments after `--`.
+    /// e.g., kt export --format k6 -- -c 20 -d 1m -H "Accept: application/json" http://target/
+    #[arg(last = true, required = true)]
+    pub plan: Vec<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A shell script running curl in parallel loops
+    Curl,
+    /// A k6 JavaScript test
+    K6,
+    /// A wrk Lua script, with the wrk command line in its header
+    Wrk,
+}
+
+#[derive(Args, Debug)]
+pub struct FindMaxArgs {
+    /// How the next concurrency is chosen. `step` adds --step after every passing step,
+    /// `binary` doubles until a step fails and then bisects down to --step.
+    #[arg(long, value_enum, default_value = "step")]
+    pub search: SearchStrategy,
+
+    /// The concurrency of the first step.
+    #[arg(long, default_value_t = 10)]
+    pub start: u16,
+
+    /// The highest concurrency tried.
+    #[arg(long, default_value_t = 1000)]
+    pub max: u16,
+
+    /// The concurrency added per step, or with --search binary the precision to stop at.
+    #[arg(long, default_value_t = 10)]
+    pub step: u16,
+
+    /// How long every step runs. e.g., 10s, 1m.
+    #[arg(long, value_parser = parse_strict_duration, default_value = "10s")]
+    pub step_duration: Duration,
+
+    /// A step fails when its 99th percentile latency is above this. e.g., 200ms.
+    #[arg(long, value_parser = parse_strict_duration)]
+    pub max_p99: Option<Duration>,
+
+    /// A step fails when more than this percentage of its requests fail. Errors and 5xx
+    /// responses count as failed.
+    #[arg(long, default_value_t = 1.0)]
+    pub max_error_rate: f64,
+
+    /// The test plan as regular kt arguments after `--`. Its concurrency and duration are
+    /// replaced by those of each step. e.g., kt find-max --max-p99 100ms -- http://target/
+    #[arg(last = true, required = true)]
+    pub plan: Vec<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchStrategy {
+    Step,
+    Binary,
+}
+
+#[derive(Args, Debug)]
+pub struct FromCurlArgs {
+    /// The curl command line as one quoted string, e.g., kt from-curl 'curl -X PUT ...',
+    /// or as separate arguments.
+    #[arg(
+        required = true,
+        num_args = 1..,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    pub command: Vec<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coding {
+    Gzip,
+    Deflate,
+    Br,
+    Zstd,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The nginx and Apache combined (or common) log format
+    Combined,
+    /// One JSON object per line with time, method, path, headers and body
+    Jsonl,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct SocketArgs {
+    /// The service to test, e.g., localhost:6379.
+    #[arg(value_name = "HOST:PORT")]
+    pub target: String,
+
+    /// Number of concurrent connections (sockets for udp).
+    #[arg(short = 'c', long, default_value_t = 50)]
+    pub concurrency: u16,
+
+    /// Duration of the test, e.g., 30s, 10m. Mutually exclusive with --requests.
+    #[arg(short = 'd', long, value_parser = parse_strict_duration, conflicts_with = "requests")]
+    pub duration: Option<Duration>,
+
+    /// Total number of payloads to send. Mutually exclusive with --duration.
+    #[arg(
+        short = 'r',
+        long,
+        default_value = "500000",
+        conflicts_with = "duration"
+    )]
+    pub requests: u64,
+
+    /// The payload as text. \r, \n, \t, \0, \\ and \xHH escapes are decoded and {{conn}}
+    /// and {{seq}} are replaced with the connection and payload number,
+    /// e.g., 'GET key:{{seq}}\r\n'.
+    #[arg(short = 'p', long, value_name = "TEXT", group = "payload_source")]
+    pub payload: Option<String>,
+
+    /// The payload as hex, e.g., 0a0b0c. Whitespace is ignored.
+    #[arg(long, value_name = "HEX", group = "payload_source")]
+    pub payload_hex: Option<String>,
+
+    /// Send the bytes of this file as the payload.
+    #[arg(long, value_name = "FILE", group = "payload_source")]
+    pub payload_file: Option<PathBuf>,
+
+    /// A response is complete once this text arrives, e.g., '\r\n'. Escapes as in --payload.
+    /// Without it or --expect-length, the first read (or datagram) is the response.
+    #[arg(long, value_name = "TEXT", conflicts_with = "expect_length")]
+    pub delimiter: Option<String>,
+
+    /// A response is complete once this many bytes arrived.
+    #[arg(long, value_name = "BYTES")]
+    pub expect_length: Option<usize>,
+
+    /// How long to wait for a response before counting a timeout, e.g., 2s.
+    #[arg(long, value_parser = parse_strict_duration, default_value = "500ms")]
+    pub timeout: Duration,
+}
+
+/// The test plan: target, load shape and request contents.
 #[derive(Parser, Clone, Debug)]
 #[command(author, version, about, long_about)]
 pub struct Cli {
@@ -31,6 +315,27 @@ pub struct Cli {
         conflicts_with = "duration"
     )]
     pub requests: u64,
+
+    /// Send load for this long before measuring, e.g., 10s. Its results are left out of
+    /// the report and summarized on their own. Comes on top of --duration.
+    #[arg(long, value_parser = parse_strict_duration, conflicts_with = "warmup_requests")]
+    pub warmup: Option<Duration>,
+
+    /// Like --warmup, but for this many requests. Comes on top of --requests.
+    #[arg(long, value_name = "NUM")]
+    pub warmup_requests: Option<u64>,
+
+    /// Pause between the requests of every worker to model real users: a fixed duration
+    /// such as 1s, uniform:500ms-2s, exp:1s for an exponential distribution with that mean,
+    /// or normal:1s,200ms for a normal distribution with that mean and standard deviation.
+    #[arg(long, value_parser = parse_think_time, value_name = "SPEC")]
+    pub think_time: Option<ThinkTimeDistribution>,
+
+    /// Start one iteration per worker at most this often, e.g., 5s. An iteration is one
+    /// request, or one pass over a recorded session.
+    #[arg(long, value_parser = parse_strict_duration, value_name = "DURATION")]
+    pub pacing: Option<Duration>,
+
     /// Add a custom HTTP header to the request.
     /// This option can be used multiple times. Format: "Key:Value".
     #[arg(short = 'H', long = "header", value_parser = parse_key_val, name = "KEY_VALUE")]
@@ -41,6 +346,257 @@ pub struct Cli {
     /// and its content will be read as the body.
     #[arg(short = 'b', long = "body")]
     pub body: Option<String>,
+
+    /// The HTTP method, e.g., PUT. Defaults to POST with a body and GET without one.
+    #[arg(short = 'X', long)]
+    pub method: Option<String>,
+
+    /// Send a multipart/form-data body with this field, as NAME=VALUE for text or
+    /// NAME=@PATH[;type=MIME][;filename=NAME] for a file. When PATH is a directory, every
+    /// request uploads a random file from it. This option can be used multiple times.
+    #[arg(
+        short = 'F',
+        long,
+        value_name = "NAME=VALUE",
+        conflicts_with_all = ["body", "grpc", "graphql", "compress_body"]
+    )]
+    pub form: Vec<String>,
+
+    /// Compress the body once before the test and send it with a matching Content-Encoding.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CODING",
+        requires = "body",
+        conflicts_with_all = ["grpc", "graphql"]
+    )]
+    pub compress_body: Option<Coding>,
+
+    /// Ask for compressed responses with these codings, comma separated, e.g., gzip,br.
+    /// Response bodies are then read and decompressed, and the report shows both the
+    /// transferred and the decompressed size. Latency covers the whole body.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        value_name = "CODINGS",
+        conflicts_with_all = ["grpc", "graphql", "stream", "http3"]
+    )]
+    pub accept_encoding: Vec<Coding>,
+
+    /// Serve live Prometheus metrics on this address while the test runs,
+    /// e.g., 127.0.0.1:9100. Scrape them from /metrics.
+    #[arg(long, value_name = "ADDR")]
+    pub metrics_listen: Option<SocketAddr>,
+
+    /// Push per-interval metrics to a StatsD server over UDP, e.g., 127.0.0.1:8125.
+    #[arg(long, value_name = "HOST:PORT")]
+    pub statsd: Option<String>,
+
+    /// Push per-interval metrics in the InfluxDB line protocol over UDP, e.g., 127.0.0.1:8089.
+    #[arg(long, value_name = "HOST:PORT")]
+    pub influx_udp: Option<String>,
+
+    /// How often metrics are pushed to --statsd and --influx-udp. e.g., 1s, 500ms.
+    #[arg(long, value_parser = parse_strict_duration, default_value = "1s")]
+    pub push_interval: Duration,
+
+    /// Keep cookies from Set-Cookie responses and send them on later requests.
+    /// `isolated` gives every worker its own jar, `shared` uses one jar for all workers.
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub cookie_jar: Option<CookieJarMode>,
+
+    /// Follow 3xx redirects and record the status code of the final destination.
+    #[arg(short = 'L', long)]
+    pub follow_redirects: bool,
+
+    /// The maximum number of redirects followed for one request.
+    #[arg(long, default_value_t = 10, requires = "follow_redirects")]
+    pub max_redirects: u32,
+
+    /// Send a request again up to this many times when it fails with a --retry-on condition.
+    /// The recorded latency spans all attempts, including the waits between them.
+    #[arg(long, default_value_t = 0, value_name = "NUM")]
+    pub retries: u32,
+
+    /// What is retried, comma separated: status codes such as 429, classes such as 5xx,
+    /// `connect` for failed connections, `timeout`, or `error` for any failed request.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_retry_condition,
+        default_value = "429,503,connect,timeout",
+        value_name = "CONDITIONS"
+    )]
+    pub retry_on: Vec<RetryCondition>,
+
+    /// The longest wait before the first retry, doubled for every further retry. The actual
+    /// wait is a random fraction of it, so retrying workers spread out.
+    #[arg(long, value_parser = parse_strict_duration, default_value = "100ms")]
+    pub retry_backoff: Duration,
+
+    /// Caps the wait between two attempts, including waits asked for by Retry-After.
+    #[arg(long, value_parser = parse_strict_duration, default_value = "10s")]
+    pub retry_max_backoff: Duration,
+
+    /// Wait by --retry-backoff even when a 429 or 503 response has a Retry-After header.
+    #[arg(long)]
+    pub ignore_retry_after: bool,
+
+    /// Send requests through a proxy: http://[user:pass@]host:port (CONNECT is used for
+    /// https targets), socks5://[user:pass@]host:port or socks5h:// to resolve on the proxy.
+    /// Without it HTTP_PROXY, HTTPS_PROXY and ALL_PROXY are used. NO_PROXY is always honored.
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Connect to this Unix domain socket instead of the host in the URL.
+    /// The URL still provides the scheme, Host header and path, e.g., http://localhost/health.
+    #[arg(long, value_name = "PATH", conflicts_with = "proxy")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Speak HTTP/2 without negotiating it first, e.g., h2c over plain http.
+    #[arg(long)]
+    pub http2_prior_knowledge: bool,
+
+    /// Speak HTTP/3 over QUIC instead of HTTP/1.1 or HTTP/2. Needs an https:// URL.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "http2_prior_knowledge",
+            "proxy",
+            "unix_socket",
+            "follow_redirects",
+            "retries",
+            "cookie_jar",
+            "stream",
+            "grpc",
+            "graphql",
+            "interface",
+        ]
+    )]
+    pub http3: bool,
+
+    /// Resume QUIC sessions with 0-RTT when reconnecting, sending requests before the
+    /// handshake completes. Only safe for idempotent requests.
+    #[arg(long, requires = "http3")]
+    pub zero_rtt: bool,
+
+    /// Replace each worker's HTTP/3 connection after this many requests. With --zero-rtt the
+    /// new connections resume the session, so 0-RTT is measured all through the test.
+    #[arg(
+        long,
+        requires = "http3",
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub http3_reconnect_after: Option<u64>,
+
+    /// Trust the certificates in this PEM file as well as the built-in roots, e.g., for a
+    /// local server with a self-signed certificate.
+    #[arg(long, value_name = "FILE")]
+    pub cacert: Option<PathBuf>,
+
+    /// Connect to ADDR instead of resolving HOST:PORT. ADDR may be a comma separated list.
+    /// This option can be used multiple times. Format: "HOST:PORT:ADDR[,ADDR]".
+    #[arg(long, value_parser = parse_resolve, value_name = "HOST:PORT:ADDR")]
+    pub resolve: Vec<ResolveOverride>,
+
+    /// Spread new connections round-robin across every resolved A/AAAA record
+    /// instead of always starting with the first one.
+    #[arg(long)]
+    pub spread_ips: bool,
+
+    /// Bind outgoing connections to this source IP. Use a comma separated list or repeat the
+    /// option to rotate new connections across several addresses.
+    #[arg(long, value_delimiter = ',', value_name = "ADDR")]
+    pub local_address: Vec<IpAddr>,
+
+    /// Send outgoing connections through this network interface, e.g., eth1 (Linux only).
+    #[arg(long, value_name = "NAME")]
+    pub interface: Option<String>,
+
+    /// A message each WebSocket connection sends, for ws:// and wss:// URLs. Repeat it to send
+    /// a script of messages in turn. {{conn}}, {{seq}} and {{id}} are replaced with the
+    /// connection number, the message number and a unique id the server is expected to echo.
+    #[arg(long, value_name = "TEXT")]
+    pub ws_message: Vec<String>,
+
+    /// Messages per second each WebSocket connection sends.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_rate, value_name = "N")]
+    pub ws_rate: f64,
+
+    /// Hold streamed responses (Server-Sent Events, chunked streams) open and time their
+    /// events. Each worker reads one stream to the end before it sends the next request.
+    #[arg(long)]
+    pub stream: bool,
+
+    /// Cut a stream after this long, e.g., 30s. Streams are also cut when --duration ends.
+    #[arg(long, value_parser = parse_strict_duration, requires = "stream")]
+    pub max_stream_time: Option<Duration>,
+
+    /// Call this gRPC method over HTTP/2, e.g., helloworld.Greeter/SayHello. The URL names the
+    /// server and --body holds the request message. Results are grouped by gRPC status.
+    #[arg(long, value_name = "SERVICE/METHOD")]
+    pub grpc: Option<String>,
+
+    /// A descriptor set from `protoc --include_imports --descriptor_set_out=FILE`, used to
+    /// encode a JSON --body. Without it --body must be the serialized protobuf message.
+    #[arg(long, value_name = "FILE", requires = "grpc")]
+    pub proto_set: Option<PathBuf>,
+
+    /// Send the operations in this .graphql file as JSON POST requests to the URL. Repeat it to
+    /// rotate through several files. Responses with a non-empty `errors` array count as failures.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["body", "grpc"])]
+    pub graphql: Vec<PathBuf>,
+
+    /// Variables sent with every GraphQL operation, as a JSON object or @file.
+    #[arg(long, value_name = "JSON", requires = "graphql")]
+    pub graphql_variables: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<IpAddr>,
+}
+
+impl Cli {
+    /// Whether the URL is ws:// or wss://, which runs the WebSocket mode.
+    pub fn is_websocket(&self) -> bool {
+        matches!(self.url.scheme_str(), Some("ws") | Some("wss"))
+    }
+
+    pub fn method(&self) -> &str {
+        match &self.method {
+            Some(method) => method,
+            None if self.body.is_some() || !self.form.is_empty() => "POST",
+            None => "GET",
+        }
+    }
+
+    /// The Accept-Encoding header asking for the --accept-encoding codings.
+    pub fn accept_encoding_header(&self) -> Option<HeaderValue> {
+        if self.accept_encoding.is_empty() {
+            return None;
+        }
+        let codings: Vec<&str> = self.accept_encoding.iter().map(Coding::as_str).collect();
+        HeaderValue::from_str(&codings.join(", ")).ok()
+    }
+
+    /// The target as shown in reports and metrics.
+    pub fn target(&self) -> String {
+        match &self.unix_socket {
+            Some(path) => format!("{} (unix:{})", self.url, path.display()),
+            None => self.url.to_string(),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CookieJarMode {
+    Isolated,
+    Shared,
 }
 /// A strict duration parser that only accepts s, ms, m, d.
 fn parse_strict_duration(s: &str) -> Result<Duration, String> {
@@ -66,20 +622,110 @@ fn parse_strict_duration(s: &str) -> Result<Duration, String> {
     }
 }
 
+fn parse_retry_condition(s: &str) -> Result<RetryCondition, String> {
+    match s.trim().to_lowercase().as_str() {
+        "connect" => Ok(RetryCondition::Connect),
+        "timeout" => Ok(RetryCondition::Timeout),
+        "error" => Ok(RetryCondition::Error),
+        class if class.len() == 3 && class.ends_with("xx") => class[..1]
+            .parse()
+            .ok()
+            .filter(|digit| (1..=5).contains(digit))
+            .map(RetryCondition::StatusClass)
+            .ok_or_else(|| format!("Invalid status class {class}")),
+        code => code
+            .parse()
+            .ok()
+            .filter(|code| (100..600).contains(code))
+            .map(RetryCondition::Status)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid condition {code}. Use a status code, a class such as 5xx, \
+                     connect, timeout or error"
+                )
+            }),
+    }
+}
+
+fn parse_think_time(s: &str) -> Result<ThinkTimeDistribution, String> {
+    let Some((kind, params)) = s.split_once(':') else {
+        return parse_strict_duration(s).map(ThinkTimeDistribution::Fixed);
+    };
+    match kind {
+        "uniform" => {
+            let (low, high) = params
+                .split_once('-')
+                .ok_or_else(|| "Use uniform:MIN-MAX, e.g., uniform:500ms-2s".to_string())?;
+            let (low, high) = (parse_strict_duration(low)?, parse_strict_duration(high)?);
+            if low > high {
+                return Err(format!("The minimum {low:?} is above the maximum {high:?}"));
+            }
+            Ok(ThinkTimeDistribution::Uniform(low, high))
+        }
+        "exp" => Ok(ThinkTimeDistribution::Exponential {
+            mean: parse_strict_duration(params)?,
+        }),
+        "normal" => {
+            let (mean, std_dev) = params
+                .split_once(',')
+                .ok_or_else(|| "Use normal:MEAN,STDDEV, e.g., normal:1s,200ms".to_string())?;
+            Ok(ThinkTimeDistribution::Normal {
+                mean: parse_strict_duration(mean)?,
+                std_dev: parse_strict_duration(std_dev)?,
+            })
+        }
+        _ => Err(format!(
+            "Unknown distribution '{kind}'. Use a duration, uniform:, exp: or normal:"
+        )),
+    }
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|_| format!("Invalid number: '{s}'"))?;
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!(
+            "The rate must be a finite number greater than 0, got {s}"
+        ));
+    }
+    Ok(rate)
+}
+
 fn parse_key_val(s: &str) -> Result<(String, String), String> {
     s.split_once(':')
         .map(|(key, val)| (key.trim().to_string(), val.trim().to_string()))
         .ok_or_else(|| "Header must be in 'Key:Value' format".to_string())
 }
 
+fn parse_resolve(s: &str) -> Result<ResolveOverride, String> {
+    let format_error = || "Resolve entry must be in 'HOST:PORT:ADDR[,ADDR]' format".to_string();
+    let (host, rest) = s.split_once(':').ok_or_else(format_error)?;
+    let (port, addrs) = rest.split_once(':').ok_or_else(format_error)?;
+    let port: u16 = port
+        .parse()
+        .map_err(|_| format!("Invalid port: '{port}'"))?;
+    let addrs = addrs
+        .split(',')
+        .map(|addr| {
+            let addr = addr.trim().trim_start_matches('[').trim_end_matches(']');
+            addr.parse::<IpAddr>()
+                .map_err(|_| format!("Invalid IP address: '{addr}'"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ResolveOverride {
+        host: host.to_ascii_lowercase(),
+        port,
+        addrs,
+    })
+}
+
 fn parse_url(s: &str) -> Result<Uri, String> {
     let uri: Uri = s.parse().map_err(|e| format!("Invalid URL format: {e}"))?;
 
     match uri.scheme_str() {
-        Some("http") | Some("https") => (), // Scheme is valid, continue.
+        Some("http") | Some("https") | Some("ws") | Some("wss") => (), // Scheme is valid, continue.
         Some(other) => {
             return Err(format!(
-                "Unsupported scheme: '{other}'. Only 'http' or 'https' are supported."
+                "Unsupported scheme: '{other}'. Only 'http', 'https', 'ws' or 'wss' are supported."
             ))
         }
         None => return Err("URL must include a scheme (e.g., http:// or https://)".to_string()),
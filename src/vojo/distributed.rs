@@ -0,0 +1,29 @@
+use crate::output::report::StatisticAggregate;
+use serde::Deserialize;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Messages sent from the controller to an agent, one JSON document per line.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerMessage {
+    /// The kt arguments describing the test, without the program name.
+    Plan {
+        args: Vec<String>,
+    },
+    Start,
+}
+
+/// Messages sent from an agent back to the controller, one JSON document per line.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentMessage {
+    Ready,
+    Result {
+        actual_duration: Duration,
+        aggregate: Box<StatisticAggregate>,
+    },
+    Error {
+        message: String,
+    },
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::kt;
+use common::kt_output;
+use common::Background;
+
+fn agent() -> (Background, String) {
+    let address = format!("127.0.0.1:{}", common::free_port());
+    let agent = Background::start(&["agent", "--listen", &address], "Agent listening");
+    (agent, address)
+}
+
+#[test]
+fn merges_the_results_of_every_agent() {
+    let target = format!("http://{}/", common::serve_http());
+    let (_first, first_address) = agent();
+    let (_second, second_address) = agent();
+    let agents = format!("{first_address},{second_address}");
+    let stdout = kt(&[
+        "controller",
+        "--agents",
+        &agents,
+        "--",
+        "-c",
+        "2",
+        "-r",
+        "10",
+        &target,
+    ]);
+    assert!(stdout.contains("[Per-Agent Results]"), "{stdout}");
+    for address in [&first_address, &second_address] {
+        assert!(
+            stdout.lines().any(|line| line.contains(address.as_str())
+                && line.contains("| 10       | 10         |")),
+            "{stdout}"
+        );
+    }
+    assert!(stdout.contains("Concurrency:      4 threads"), "{stdout}");
+    assert!(stdout.contains("[200] 20 responses (100.00%)"), "{stdout}");
+
+    // The agents go back to waiting and take the next plan.
+    let stdout = kt(&["controller", "-a", &agents, "--", "-r", "3", &target]);
+    assert!(stdout.contains("[200] 6 responses (100.00%)"), "{stdout}");
+}
+
+#[test]
+fn reports_bad_plans_and_unreachable_agents() {
+    let (_agent, address) = agent();
+    // The plan is checked before any agent is contacted.
+    let output = kt_output(&[
+        "controller",
+        "-a",
+        &address,
+        "--",
+        "--no-such-option",
+        "http://127.0.0.1/",
+    ]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unexpected argument"), "{stderr}");
+
+    let missing = format!("127.0.0.1:{}", common::free_port());
+    let agents = format!("{address},{missing}");
+    let output = kt_output(&["controller", "-a", &agents, "--", "http://127.0.0.1/"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("Failed to connect to agent {missing}")),
+        "{stderr}"
+    );
+}
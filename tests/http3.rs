@@ -0,0 +1,119 @@
+mod common;
+
+use common::kt;
+use http::Response;
+use hyper::body::Bytes;
+use quinn::crypto::rustls::QuicServerConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::PrivateKeyDer;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const CERT: &[u8] = include_bytes!("fixtures/localhost.crt");
+const KEY: &[u8] = include_bytes!("fixtures/localhost.key");
+
+/// Starts an HTTP/3 server on a free local port that answers every request with "ok" and
+/// accepts 0-RTT data.
+fn serve() -> SocketAddr {
+    let cert = CertificateDer::from_pem_slice(CERT).unwrap();
+    let key = PrivateKeyDer::from_pem_slice(KEY).unwrap();
+    let mut tls_config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(vec![cert], key)
+    .unwrap();
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+    tls_config.max_early_data_size = u32::MAX;
+    let quic_config = QuicServerConfig::try_from(tls_config).unwrap();
+    let server_config = quinn::ServerConfig::with_crypto(Arc::new(quic_config));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let endpoint = runtime
+        .block_on(async { quinn::Endpoint::server(server_config, "127.0.0.1:0".parse().unwrap()) })
+        .unwrap();
+    let addr = endpoint.local_addr().unwrap();
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            while let Some(incoming) = endpoint.accept().await {
+                tokio::spawn(async move {
+                    let Ok(connection) = incoming.await else {
+                        return;
+                    };
+                    let Ok(mut h3) = h3::server::builder()
+                        .build::<_, Bytes>(h3_quinn::Connection::new(connection))
+                        .await
+                    else {
+                        return;
+                    };
+                    while let Ok(Some(resolver)) = h3.accept().await {
+                        tokio::spawn(async move {
+                            let Ok((_, mut stream)) = resolver.resolve_request().await else {
+                                return;
+                            };
+                            let response = Response::builder().status(200).body(()).unwrap();
+                            if stream.send_response(response).await.is_ok()
+                                && stream.send_data(Bytes::from_static(b"ok")).await.is_ok()
+                            {
+                                let _ = stream.finish().await;
+                            }
+                        });
+                    }
+                });
+            }
+        });
+    });
+    addr
+}
+
+#[test]
+fn sends_requests_over_http3() {
+    let addr = serve();
+    let port = addr.port().to_string();
+    let stdout = kt(&[
+        "-c",
+        "1",
+        "-r",
+        "3",
+        "--http3",
+        "--cacert",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.crt"),
+        "--resolve",
+        &format!("localhost:{port}:127.0.0.1"),
+        "--local-address",
+        "127.0.0.1",
+        &format!("https://localhost:{port}/"),
+    ]);
+    assert!(stdout.contains("[200] 3 responses"), "{stdout}");
+    assert!(
+        stdout.contains("QUIC Handshake:   1 connections"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn resumes_reconnects_with_zero_rtt() {
+    let addr = serve();
+    let port = addr.port().to_string();
+    let stdout = kt(&[
+        "-c",
+        "1",
+        "-d",
+        "1s",
+        "--http3",
+        "--zero-rtt",
+        "--http3-reconnect-after",
+        "1",
+        "--cacert",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.crt"),
+        "--resolve",
+        &format!("localhost:{port}:127.0.0.1"),
+        &format!("https://localhost:{port}/"),
+    ]);
+    assert!(stdout.contains("0-RTT Handshake:"), "{stdout}");
+    assert!(!stdout.contains("0-RTT Rejected"), "{stdout}");
+    assert!(stdout.contains("(No errors)"), "{stdout}");
+}
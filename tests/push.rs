@@ -0,0 +1,90 @@
+mod common;
+
+use common::kt;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+/// Every line pushed to `socket` so far.
+fn received_lines(socket: &UdpSocket) -> Vec<String> {
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    let mut lines = vec![];
+    let mut buffer = [0; 2048];
+    while let Ok(read) = socket.recv(&mut buffer) {
+        let datagram = String::from_utf8(buffer[..read].to_vec()).unwrap();
+        assert!(read <= 1400, "{datagram}");
+        lines.extend(datagram.lines().map(String::from));
+    }
+    lines
+}
+
+/// The sum of a counter over every interval.
+fn total(lines: &[String], prefix: &str, suffix: &str) -> u64 {
+    lines
+        .iter()
+        .filter_map(|line| line.strip_prefix(prefix)?.split(suffix).next())
+        .map(|value| value.parse::<u64>().unwrap())
+        .sum()
+}
+
+#[test]
+fn pushes_statsd_and_influx_metrics() {
+    let target = format!("http://{}/", common::serve_http());
+    let statsd = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let influx = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let statsd_address = statsd.local_addr().unwrap().to_string();
+    let influx_address = influx.local_addr().unwrap().to_string();
+    kt(&[
+        "-c",
+        "2",
+        "-r",
+        "40",
+        "--statsd",
+        &statsd_address,
+        "--influx-udp",
+        &influx_address,
+        "--push-interval",
+        "100ms",
+        &target,
+    ]);
+
+    // The last partial interval is flushed when the test ends, so nothing is lost.
+    let lines = received_lines(&statsd);
+    assert_eq!(total(&lines, "kt.requests:", "|c"), 40, "{lines:?}");
+    assert_eq!(total(&lines, "kt.status.200:", "|c"), 40, "{lines:?}");
+    assert_eq!(total(&lines, "kt.errors:", "|c"), 0, "{lines:?}");
+    assert_eq!(total(&lines, "kt.bytes:", "|c"), 80, "{lines:?}");
+    assert!(
+        lines.iter().any(|line| line.starts_with("kt.latency.p99:")),
+        "{lines:?}"
+    );
+
+    let lines = received_lines(&influx);
+    let endpoint = target.replace(',', "\\,").replace('=', "\\=");
+    let summaries: Vec<_> = lines
+        .iter()
+        .filter(|line| line.starts_with(&format!("kt,endpoint={endpoint} ")))
+        .collect();
+    assert!(!summaries.is_empty(), "{lines:?}");
+    let requests: u64 = summaries
+        .iter()
+        .map(|line| {
+            let fields = line.split(' ').nth(1).unwrap();
+            let requests = fields.split(',').next().unwrap();
+            requests["requests=".len()..requests.len() - 1]
+                .parse::<u64>()
+                .unwrap()
+        })
+        .sum();
+    assert_eq!(requests, 40, "{lines:?}");
+    assert_eq!(
+        total(
+            &lines,
+            &format!("kt_status,endpoint={endpoint},status_code=200 count="),
+            "i "
+        ),
+        40,
+        "{lines:?}"
+    );
+}